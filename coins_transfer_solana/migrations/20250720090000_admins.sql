@@ -0,0 +1,13 @@
+-- Sequence for generating unique identifiers for the admins table
+CREATE SEQUENCE IF NOT EXISTS admins_id_seq START WITH 1 INCREMENT BY 1;
+
+-- Admins Table: API clients allowed to call the module, authenticated with HMAC-signed requests
+CREATE TABLE IF NOT EXISTS admins (
+    id INTEGER PRIMARY KEY DEFAULT nextval('admins_id_seq'),
+    name VARCHAR(128) NOT NULL, -- Name of the admin
+    api_key VARCHAR(128) NOT NULL UNIQUE, -- Public key sent in the X-Api-Key header
+    api_secret VARCHAR(128) NOT NULL, -- Shared secret used to sign requests
+    status BOOLEAN NOT NULL DEFAULT TRUE, -- Whether the admin is active or not
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(), -- Timestamp when the admin was created
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW() -- Timestamp when the admin was last updated
+    );
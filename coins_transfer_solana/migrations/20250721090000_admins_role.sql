@@ -0,0 +1,4 @@
+-- Role of each admin, checked per route scope: every role may read, mutations need the role owning the scope
+ALTER TABLE admins
+    ADD COLUMN IF NOT EXISTS role VARCHAR(32) NOT NULL DEFAULT 'read-only'
+        CHECK (role IN ('read-only', 'deposits-operator', 'withdrawals-operator', 'config-admin'));
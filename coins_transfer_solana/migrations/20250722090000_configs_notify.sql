@@ -0,0 +1,12 @@
+-- Notifies listeners (the IP allowlist) whenever the configs table changes, so they reload without a restart
+CREATE OR REPLACE FUNCTION notify_configs_changed() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('configs_changed', TG_OP);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS configs_changed ON configs;
+CREATE TRIGGER configs_changed
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON configs
+    FOR EACH STATEMENT EXECUTE FUNCTION notify_configs_changed();
@@ -0,0 +1,7 @@
+-- Envelope encryption of users_wallets.private_key: the key is encrypted with a per-record data key,
+-- itself wrapped by a versioned master key. Existing plaintext rows (private_key_key_version IS NULL)
+-- are encrypted, and rows wrapped by an older master key rewrapped, by the service at startup.
+ALTER TABLE users_wallets
+ADD COLUMN IF NOT EXISTS private_key_data_key VARCHAR(200), -- Data key wrapped by the master key, hex of nonce || ciphertext
+ADD COLUMN IF NOT EXISTS private_key_key_version SMALLINT -- Version of the master key wrapping the data key, NULL while private_key is plaintext
+;
@@ -0,0 +1,9 @@
+-- Secret configs are masked in API responses and never cached in Redis; values are no longer limited to 128 characters
+ALTER TABLE configs
+ALTER COLUMN value TYPE TEXT,
+ADD COLUMN IF NOT EXISTS is_secret BOOLEAN NOT NULL DEFAULT FALSE -- Whether the value is write-only
+;
+
+-- Credentials already stored in the table
+UPDATE configs SET is_secret = TRUE
+WHERE name LIKE '%PASSWORD%' OR name LIKE '%API_KEY%' OR name LIKE '%SECRET%' OR name LIKE '%TOKEN%';
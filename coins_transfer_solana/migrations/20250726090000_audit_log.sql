@@ -0,0 +1,35 @@
+-- Audit Log Table: append-only trail of state-changing admin requests
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    action VARCHAR(32) NOT NULL, -- create, update, delete or rollback
+    entity VARCHAR(32) NOT NULL, -- Kind of record changed: config, coin, wallet, withdrawal, withdrawal_address or user
+    entity_id VARCHAR(128), -- Id or name of the changed record
+    actor_id INTEGER, -- Admin who signed the request
+    actor_name VARCHAR(128), -- Name of the admin at the time of the request
+    request_id VARCHAR(128) NOT NULL, -- X-Request-Id header, or a generated id
+    client_ip VARCHAR(64), -- Client address as seen by the IP allowlist
+    before JSONB, -- Record before the change, secrets masked
+    after JSONB, -- Record after the change, secrets masked
+    created_at TIMESTAMP NOT NULL DEFAULT NOW() -- Timestamp of the change
+    );
+
+CREATE INDEX IF NOT EXISTS audit_log_entity_idx ON audit_log (entity, entity_id);
+CREATE INDEX IF NOT EXISTS audit_log_actor_idx ON audit_log (actor_id);
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
+
+-- Entries can only be inserted
+CREATE OR REPLACE FUNCTION reject_audit_log_change() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log;
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION reject_audit_log_change();
+
+DROP TRIGGER IF EXISTS audit_log_no_truncate ON audit_log;
+CREATE TRIGGER audit_log_no_truncate
+    BEFORE TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION reject_audit_log_change();
@@ -0,0 +1,14 @@
+-- Withdrawal Addresses Table: addresses a user registered to withdraw a coin to, usable once `active_at` has passed
+CREATE TABLE IF NOT EXISTS withdrawal_addresses (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL, -- Reference to the user owning the address
+    coin_id SMALLINT NOT NULL, -- Reference to the coin withdrawn to the address
+    address VARCHAR(255) NOT NULL, -- Withdrawal address
+    label VARCHAR(128), -- Optional name given by the user
+    active_at TIMESTAMP NOT NULL, -- When the cool-down ends and withdrawals to the address are allowed
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(), -- Timestamp when the address was registered
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(), -- Timestamp when the address was last updated
+    UNIQUE (user_id, coin_id, address),
+    FOREIGN KEY (user_id) REFERENCES users (user_id), -- Foreign key reference to users table
+    FOREIGN KEY (coin_id) REFERENCES coins (id) -- Foreign key reference to coins table
+    );
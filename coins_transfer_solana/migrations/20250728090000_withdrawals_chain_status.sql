@@ -0,0 +1,7 @@
+-- On-chain state of each withdrawal: `pending` rows were broadcast but not confirmed yet and are reconciled by signature
+ALTER TABLE withdrawals
+    ADD COLUMN IF NOT EXISTS chain_status VARCHAR(16) NOT NULL DEFAULT 'confirmed'
+        CHECK (chain_status IN ('pending', 'confirmed', 'failed')),
+    ADD COLUMN IF NOT EXISTS last_valid_block_height BIGINT; -- Block height after which a pending transaction can no longer land
+
+CREATE INDEX IF NOT EXISTS withdrawals_pending_idx ON withdrawals (user_id) WHERE chain_status = 'pending';
@@ -0,0 +1,8 @@
+-- Signatures of accepted API requests, kept until their timestamp leaves the allowed window so a captured request can not be replayed
+CREATE TABLE IF NOT EXISTS api_request_signatures (
+    signature BYTEA PRIMARY KEY, -- HMAC-SHA256 sent in X-Signature
+    admin_id INTEGER NOT NULL, -- Admin who signed the request
+    expires_at TIMESTAMPTZ NOT NULL -- When the request timestamp leaves the window and the row can be dropped
+    );
+
+CREATE INDEX IF NOT EXISTS api_request_signatures_expires_at_idx ON api_request_signatures (expires_at);
@@ -0,0 +1,5 @@
+-- Encrypted private keys are authenticated with the id of their wallet, so a sealed key copied to another
+-- row fails to decrypt. Existing rows are sealed without it and are re-encrypted by the service at startup.
+ALTER TABLE users_wallets
+ADD COLUMN IF NOT EXISTS private_key_bound BOOLEAN NOT NULL DEFAULT FALSE -- Whether private_key is sealed with the wallet id as associated data
+;
@@ -0,0 +1,8 @@
+-- Envelope encryption of admins.api_secret with the master keys, authenticated with the admin id. Secrets
+-- inserted in plaintext (api_secret_key_version IS NULL) are encrypted, and secrets wrapped by an older
+-- master key rewrapped, by the service at startup.
+ALTER TABLE admins
+ALTER COLUMN api_secret TYPE VARCHAR(400), -- Shared secret used to sign requests, hex of nonce || ciphertext once encrypted
+ADD COLUMN IF NOT EXISTS api_secret_data_key VARCHAR(200), -- Data key wrapped by the master key, hex of nonce || ciphertext
+ADD COLUMN IF NOT EXISTS api_secret_key_version SMALLINT -- Version of the master key wrapping the data key, NULL while api_secret is plaintext
+;
@@ -28,8 +28,12 @@ pub async fn get_config_by_name_from_cache(pool: &Pool, name: &str) -> Option<Co
     }
 }
 
-/// Set/Update a config entry in cache by name.
+/// Set/Update a config entry in cache by name. Secret configs are never
+/// cached, a stale entry left from before the config became secret is removed.
 pub async fn set_config_cache(pool: &Pool, config: &Configs) -> Result<(), Error> {
+    if !is_cacheable(config) {
+        return delete_config_cache(pool, &config.name).await;
+    }
     let key = RedisKeys::Config { name: config.name.clone() }.to_string();
     if let Err(e) = set_cache(pool, &key, config, Some(CONFIGS_CACHE_EXPIRATION)).await {
         log!(Level::Error, "Error setting config cache for name {}: {}", config.name, e);
@@ -38,6 +42,12 @@ pub async fn set_config_cache(pool: &Pool, config: &Configs) -> Result<(), Error
     Ok(())
 }
 
+/// Whether `config` may be cached under its name. Secret values stay in the
+/// database, the AllConfigs list only ever holds them masked.
+fn is_cacheable(config: &Configs) -> bool {
+    !config.is_secret
+}
+
 /// Retrieve all configs from cache, if any.
 pub async fn get_all_configs_from_cache(pool: &Pool) -> Option<Vec<Configs>> {
     let key = RedisKeys::AllConfigs.to_string();
@@ -57,11 +67,12 @@ pub async fn get_all_configs_from_cache(pool: &Pool) -> Option<Vec<Configs>> {
     }
 }
 
-/// Push a list of configs into the cache for the AllConfigs key.
+/// Push a list of configs into the cache for the AllConfigs key. The list
+/// holds redacted copies, secret values are masked.
 pub async fn set_all_config_cache(pool: &Pool, config: &[Configs]) -> Result<(), Error> {
     let key = RedisKeys::AllConfigs.to_string();
     for c in config {
-        if let Err(e) = push_to_cache_list(pool, &key, c, Some(CONFIGS_CACHE_EXPIRATION)).await {
+        if let Err(e) = push_to_cache_list(pool, &key, &c.redacted(), Some(CONFIGS_CACHE_EXPIRATION)).await {
             log!(Level::Error, "Error setting all config cache: {}", e);
             return Err(e);
         }
@@ -72,7 +83,7 @@ pub async fn set_all_config_cache(pool: &Pool, config: &[Configs]) -> Result<(),
 /// Push one additional config into the AllConfigs cache list.
 pub async fn increment_all_config_cache(pool: &Pool, config: &Configs) -> Result<(), Error> {
     let key = RedisKeys::AllConfigs.to_string();
-    if let Err(e) = push_to_cache_list(pool, &key, config, Some(CONFIGS_CACHE_EXPIRATION)).await {
+    if let Err(e) = push_to_cache_list(pool, &key, &config.redacted(), Some(CONFIGS_CACHE_EXPIRATION)).await {
         log!(Level::Error, "Error incrementing all config cache: {}", e);
         return Err(e);
     }
@@ -82,7 +93,7 @@ pub async fn increment_all_config_cache(pool: &Pool, config: &Configs) -> Result
 /// Remove one config item from the AllConfigs cache list.
 pub async fn drop_a_config_from_all_config_cache(pool: &Pool, config: &Configs) -> Result<(), Error> {
     let key = RedisKeys::AllConfigs.to_string();
-    if let Err(e) = drop_from_cache_list(pool, &key, config).await {
+    if let Err(e) = drop_from_cache_list(pool, &key, &config.redacted()).await {
         log!(Level::Error, "Error dropping a config from all config cache: {}", e);
         return Err(e);
     }
@@ -98,3 +109,17 @@ pub async fn delete_config_cache(pool: &Pool, name: &str) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    #[test]
+    fn secret_configs_are_not_cached() {
+        let config = Configs { id: 1, name: "SIGNER_REMOTE_TOKEN".to_string(), value: "token".to_string(), is_secret: false, created_at: NaiveDateTime::default(), updated_at: NaiveDateTime::default() };
+        assert!(is_cacheable(&config));
+        assert!(!is_cacheable(&Configs { is_secret: true, ..config }));
+    }
+}
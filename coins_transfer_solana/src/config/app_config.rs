@@ -1,10 +1,16 @@
 use crate::responses::error_msgs::Error;
 use deadpool_redis::Pool;
 use sqlx::PgPool;
+use std::sync::{Arc, RwLock};
+
+use crate::services::ip_allowlist::IpAllowlist;
 
 pub struct AppState {
     pub db: PgPool,
     pub redis: Option<Pool>,
+    pub ip_allowlist: Arc<RwLock<IpAllowlist>>,
+    /// Accepted clock skew of signed requests in seconds, read once at startup.
+    pub signature_window_secs: i64,
 }
 
 impl AppState {
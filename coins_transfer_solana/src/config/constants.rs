@@ -1,6 +1,3 @@
-use serde::Deserialize;
-use strum::Display;
-
 pub const MODULE_NAME: &str = "coins_transfer_solana";
 pub const USER_CACHE_EXPIRATION: usize = 3600;
 pub const COIN_CACHE_EXPIRATION: usize = 36000;
@@ -32,79 +29,45 @@ pub const MAX_LIFETIME_SECS: u64 = 30; // Default maximum lifetime for a connect
 pub const REDIS_ENABLED_DEFAULT: bool = false;
 pub const REDIS_URL_DEFAULT: &str = "redis://127.0.0.1:6379";
 
-#[derive(Debug, Display, Deserialize)]
-pub enum Coin {
-    Litecoin=1,
-    USDT=2,
-    USDC=3,
-    Solana=4,
-}
+/// Default request signature settings.
+pub const API_SIGNATURE_WINDOW_SECS_DEFAULT: i64 = 300; // Accepted clock skew between the client timestamp and the server (in seconds)
+
+/// IP allowlist settings.
+pub const ALLOWED_IPS_CONFIG: &str = "ALLOWED_IPS"; // Allowlist for every scope, `ALLOWED_IPS_<SCOPE>` replaces it for one scope
+pub const TRUSTED_PROXIES_CONFIG: &str = "TRUSTED_PROXIES"; // Proxies whose X-Forwarded-For header is used to find the client
+pub const CONFIGS_CHANGED_CHANNEL: &str = "configs_changed"; // Postgres NOTIFY channel fired when the configs table changes
+pub const IP_ALLOWLIST_RETRY_SECS: u64 = 5; // Delay before reconnecting the configs listener (in seconds)
 
-impl Coin {
-    pub fn to_string(&self) -> &str {
-        match self {
-            Coin::Litecoin => "litecoin",
-            Coin::USDT => "usdt",
-            Coin::USDC => "usdc",
-            Coin::Solana => "solana",
-        }
-    }
+/// Secrets that live in the keystore or environment and are rejected from the configs table.
+pub const SECRET_CONFIG_NAMES: [&str; 4] = ["MNEMONIC_PHRASE", "MNEMONIC_KEYSTORE_PASSPHRASE", "SIGNER_KEYSTORE_PASSPHRASE", "WALLET_MASTER_KEYS"];
+pub const CREDENTIAL_CONFIG_MARKERS: [&str; 4] = ["PASSWORD", "API_KEY", "SECRET", "TOKEN"]; // Configs whose name contains one of these are always `is_secret`
+pub const SECRET_CONFIG_MASK: &str = "********"; // Returned instead of the value of configs flagged `is_secret`
 
-    pub fn from_i16(i: i16) -> Option<Self> {
-        match i {
-            1 => Some(Coin::Litecoin),
-            2 => Some(Coin::USDT),
-            3 => Some(Coin::USDC),
-            4 => Some(Coin::Solana),
-            _ => None,
-        }
-    }
+/// Transaction signer settings.
+pub const SIGNER_BACKEND_CONFIG: &str = "SIGNER_BACKEND"; // `local` (default), `keystore` or `remote`
+pub const SIGNER_KEYSTORE_DIR_CONFIG: &str = "SIGNER_KEYSTORE_DIR"; // Directory of `<address>.json` keystores for the `keystore` backend
+pub const SIGNER_REMOTE_URL_CONFIG: &str = "SIGNER_REMOTE_URL"; // Base URL of the signing service for the `remote` backend
+pub const SIGNER_REMOTE_TOKEN_CONFIG: &str = "SIGNER_REMOTE_TOKEN"; // Bearer token sent to, and checked by, the signing service
+pub const SIGNER_REMOTE_TIMEOUT_SECS: u64 = 10; // Timeout of a request to the signing service (in seconds)
 
-    pub fn to_i16(&self) -> i16 {
-        match self {
-            Coin::Litecoin => 1,
-            Coin::USDT => 2,
-            Coin::USDC => 3,
-            Coin::Solana => 4,
-        }
-    }
+/// Withdrawal address allowlist settings.
+pub const WITHDRAWAL_ADDRESS_DELAY_HOURS_CONFIG: &str = "WITHDRAWAL_ADDRESS_DELAY_HOURS"; // Cool-down before a registered address can be withdrawn to
+pub const WITHDRAWAL_ADDRESS_DELAY_HOURS_DEFAULT: i64 = 24;
+pub const WITHDRAWAL_ADDRESS_DELAY_HOURS_MAX: i64 = 8760; // Longest accepted cool-down, a year
+pub const WITHDRAWAL_ADDRESS_ALLOWLIST_CONFIG: &str = "WITHDRAWAL_ADDRESS_ALLOWLIST_ENFORCED"; // `true` to reject withdrawals to other addresses
 
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
-            "litecoin" => Some(Coin::Litecoin),
-            "usdt" => Some(Coin::USDT),
-            "usdc" => Some(Coin::USDC),
-            "solana" => Some(Coin::Solana),
-            _ => None,
-        }
-    }
+/// Solana transaction confirmation settings.
+pub const SOLANA_COMMITMENT_CONFIG: &str = "SOLANA_COMMITMENT"; // `processed`, `confirmed` (default) or `finalized`
+pub const SOLANA_REBROADCAST_INTERVAL_CONFIG: &str = "SOLANA_REBROADCAST_INTERVAL_SECS"; // Delay between status checks and rebroadcasts
+pub const SOLANA_MAX_RESIGNS_CONFIG: &str = "SOLANA_MAX_RESIGNS"; // Times an expired transaction is signed again with a fresh blockhash
+pub const SOLANA_CONFIRM_TIMEOUT_CONFIG: &str = "SOLANA_CONFIRM_TIMEOUT_SECS"; // How long a sent transaction is awaited before it is left pending
 
-    pub fn mint(&self) -> &'static str {
-        match self {
-            Coin::Litecoin => "ltc",
-            Coin::USDT => "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
-            Coin::USDC => "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
-            Coin::Solana => "sol",
-        }
-    }
+/// Registry program settings.
+pub const ROOT_WALLET_USER_ID: i64 = 1; // Owner of the root wallet that funds deposit wallet registrations
+pub const ROOT_WALLET_COIN_ID: i16 = 2; // Coin of the root wallet row, see `get_current_highest_wallet_index`
 
-    pub fn from_mint(mint: &str) -> Option<Self> {
-        match mint {
-            "ltc" => Some(Coin::Litecoin),
-            "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB" => Some(Coin::USDT),
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v" => Some(Coin::USDC),
-            "sol" => Some(Coin::Solana),
-            _ => None,
-        }
-    }
+/// Pending withdrawal reconciliation settings.
+pub const WITHDRAWAL_RECONCILE_INTERVAL_CONFIG: &str = "WITHDRAWAL_RECONCILE_INTERVAL_IN_SECONDS";
+pub const WITHDRAWAL_RECONCILE_INTERVAL_SECS_DEFAULT: u64 = 60;
 
-    /// Number of decimal places for each token
-    pub fn decimals(&self) -> u8 {
-        match self {
-            Coin::Litecoin => 8,
-            Coin::USDT       => 6,
-            Coin::USDC       => 6,
-            Coin::Solana     => 9,
-        }
-    }
-}
+pub use smart_contracts_types::coin::Coin;
@@ -0,0 +1,16 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct Admins {
+    pub id: i32,
+    pub name: String,
+    pub api_key: String,
+    #[serde(skip_serializing)]
+    pub api_secret: String,
+    pub role: String,
+    pub status: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
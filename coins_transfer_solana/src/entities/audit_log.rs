@@ -0,0 +1,19 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct AuditLog {
+    pub id: i64,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: Option<String>,
+    pub actor_id: Option<i32>,
+    pub actor_name: Option<String>,
+    pub request_id: String,
+    pub client_ip: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
@@ -2,11 +2,25 @@ use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+use crate::config::constants::SECRET_CONFIG_MASK;
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
 pub struct Configs {
     pub id: i16,
     pub name: String,
     pub value: String,
+    pub is_secret: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
+
+impl Configs {
+    /// Copy safe to return or cache: the value of a secret config is masked.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if config.is_secret {
+            config.value = SECRET_CONFIG_MASK.to_string();
+        }
+        config
+    }
+}
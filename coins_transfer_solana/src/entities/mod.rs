@@ -1,7 +1,10 @@
+pub mod admins;
+pub mod audit_log;
 pub mod coins;
 pub mod configs;
 pub mod conversion_rates;
 pub mod deposits;
 pub mod users;
 pub mod users_wallets;
+pub mod withdrawal_addresses;
 pub mod withdrawals;
@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct WithdrawalAddresses {
+    pub id: i64,
+    pub user_id: i64,
+    pub coin_id: i16,
+    pub address: String,
+    pub label: Option<String>,
+    pub active_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -0,0 +1,12 @@
+use crate::config::app_config::AppState;
+use crate::responses::http_response_impl::return_response;
+use crate::services::audit::get_audit_log;
+use crate::structs::audit::AuditLogQuery;
+use actix_web::{Responder, web};
+use std::sync::Arc;
+
+pub async fn get_audit_log_handler(app_state: web::Data<Arc<AppState>>, query: web::Query<AuditLogQuery>) -> impl Responder {
+    let pool = app_state.db.clone();
+    let result = get_audit_log(&pool, query.into_inner()).await;
+    return_response(result).await
+}
@@ -5,10 +5,11 @@ use crate::services::coins::{
     create_coin, generate_address, get_all_coins
 };
 use crate::structs::coins::{AddressGenerationRequest, CoinCreate};
+use crate::structs::audit::AuditContext;
 use actix_web::{Responder, web};
 use std::sync::Arc;
 
-pub async fn create_coin_handler(app_state: web::Data<Arc<AppState>>, coin: web::Json<CoinCreate>) -> impl Responder {
+pub async fn create_coin_handler(app_state: web::Data<Arc<AppState>>, coin: web::Json<CoinCreate>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -17,7 +18,7 @@ pub async fn create_coin_handler(app_state: web::Data<Arc<AppState>>, coin: web:
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = create_coin(&pool, &redis_pool, coin.into_inner()).await;
+    let result = create_coin(&pool, &redis_pool, coin.into_inner(), &audit).await;
     return_response(result).await
 }
 
@@ -34,7 +35,7 @@ pub async fn get_all_coins_handler(app_state: web::Data<Arc<AppState>>) -> impl
     return_response(result).await
 }
 
-pub async fn address_generation_handler(app_state: web::Data<Arc<AppState>>, coin: web::Json<AddressGenerationRequest>) -> impl Responder {
+pub async fn address_generation_handler(app_state: web::Data<Arc<AppState>>, coin: web::Json<AddressGenerationRequest>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -43,6 +44,6 @@ pub async fn address_generation_handler(app_state: web::Data<Arc<AppState>>, coi
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = generate_address(&pool, &redis_pool, coin.into_inner()).await;
+    let result = generate_address(&pool, &redis_pool, coin.into_inner(), &audit).await;
     return_response(result).await
 }
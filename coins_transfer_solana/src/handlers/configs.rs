@@ -2,11 +2,12 @@ use crate::config::app_config::AppState;
 use crate::responses::http_response_impl::return_response;
 use crate::responses::success_msgs_impl::create_error_response;
 use crate::services::configs::{create_config, delete_all_configs, delete_config, get_all_configs, get_config_by_name, update_config};
+use crate::structs::audit::AuditContext;
 use crate::structs::configs::{ConfigCreate, ConfigUpdate};
 use actix_web::{Responder, web};
 use std::sync::Arc;
 
-pub async fn create_config_handler(app_state: web::Data<Arc<AppState>>, configs: web::Json<ConfigCreate>) -> impl Responder {
+pub async fn create_config_handler(app_state: web::Data<Arc<AppState>>, configs: web::Json<ConfigCreate>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -15,7 +16,7 @@ pub async fn create_config_handler(app_state: web::Data<Arc<AppState>>, configs:
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = create_config(&pool, &redis_pool, configs.into_inner()).await;
+    let result = create_config(&pool, &redis_pool, configs.into_inner(), &audit).await;
     return_response(result).await
 }
 
@@ -45,7 +46,7 @@ pub async fn list_configs_handler(app_state: web::Data<Arc<AppState>>) -> impl R
     return_response(result).await
 }
 
-pub async fn update_config_handler(app_state: web::Data<Arc<AppState>>, name: web::Path<String>, configs_update: web::Json<ConfigUpdate>) -> impl Responder {
+pub async fn update_config_handler(app_state: web::Data<Arc<AppState>>, name: web::Path<String>, configs_update: web::Json<ConfigUpdate>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -54,11 +55,11 @@ pub async fn update_config_handler(app_state: web::Data<Arc<AppState>>, name: we
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = update_config(&pool, &redis_pool, name.into_inner(), configs_update.into_inner()).await;
+    let result = update_config(&pool, &redis_pool, name.into_inner(), configs_update.into_inner(), &audit).await;
     return_response(result).await
 }
 
-pub async fn delete_config_handler(app_state: web::Data<Arc<AppState>>, name: web::Path<String>) -> impl Responder {
+pub async fn delete_config_handler(app_state: web::Data<Arc<AppState>>, name: web::Path<String>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -67,11 +68,11 @@ pub async fn delete_config_handler(app_state: web::Data<Arc<AppState>>, name: we
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = delete_config(&pool, &redis_pool, name.into_inner()).await;
+    let result = delete_config(&pool, &redis_pool, name.into_inner(), &audit).await;
     return_response(result).await
 }
 
-pub async fn delete_all_config_handler(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+pub async fn delete_all_config_handler(app_state: web::Data<Arc<AppState>>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -80,6 +81,6 @@ pub async fn delete_all_config_handler(app_state: web::Data<Arc<AppState>>) -> i
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = delete_all_configs(&pool, &redis_pool).await;
+    let result = delete_all_configs(&pool, &redis_pool, &audit).await;
     return_response(result).await
 }
@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod coins;
 pub mod configs;
 pub mod conversion_rates;
@@ -6,4 +7,5 @@ pub mod health;
 pub mod notify_webhooks;
 pub mod users;
 pub mod wallets;
+pub mod withdrawal_addresses;
 pub mod withdrawals;
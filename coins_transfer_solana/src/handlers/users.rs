@@ -3,6 +3,7 @@ use crate::responses::http_response_impl::return_response;
 use crate::responses::success_msgs_impl::create_error_response;
 use crate::services::users::{create_user, rollback_user_creation};
 use crate::structs::users::UserCreate;
+use crate::structs::audit::AuditContext;
 use actix_web::{Responder, web};
 use std::sync::Arc;
 
@@ -19,7 +20,7 @@ pub async fn create_user_handler(app_state: web::Data<Arc<AppState>>, user: web:
     return_response(result).await
 }
 
-pub async fn rollback_user_creation_handler(app_state: web::Data<Arc<AppState>>, event_id: web::Path<i64>) -> impl Responder {
+pub async fn rollback_user_creation_handler(app_state: web::Data<Arc<AppState>>, event_id: web::Path<i64>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -28,6 +29,6 @@ pub async fn rollback_user_creation_handler(app_state: web::Data<Arc<AppState>>,
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = rollback_user_creation(&pool, &redis_pool, event_id.into_inner()).await;
+    let result = rollback_user_creation(&pool, &redis_pool, event_id.into_inner(), &audit).await;
     return_response(result).await
 }
\ No newline at end of file
@@ -3,6 +3,7 @@ use crate::responses::http_response_impl::return_response;
 use crate::responses::success_msgs_impl::create_error_response;
 use crate::services::wallets::{create_wallet, get_wallets, update_wallet};
 use crate::structs::wallets::{WalletCreate, WalletQuery, WalletUpdate};
+use crate::structs::audit::AuditContext;
 use actix_web::{Responder, web};
 use std::sync::Arc;
 
@@ -33,7 +34,7 @@ pub async fn get_wallet_handler(app_state: web::Data<Arc<AppState>>, wallet_quer
     return_response(result).await
 }
 
-pub async fn update_wallet_handler(app_state: web::Data<Arc<AppState>>, wallet_id: web::Path<i64>, wallet_update: web::Json<WalletUpdate>) -> impl Responder {
+pub async fn update_wallet_handler(app_state: web::Data<Arc<AppState>>, wallet_id: web::Path<i64>, wallet_update: web::Json<WalletUpdate>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -43,6 +44,6 @@ pub async fn update_wallet_handler(app_state: web::Data<Arc<AppState>>, wallet_i
         }
     };
     let wallet_id = wallet_id.into_inner();
-    let result = update_wallet(&pool, &redis_pool, wallet_id, None, wallet_update.into_inner()).await;
+    let result = update_wallet(&pool, &redis_pool, wallet_id, None, wallet_update.into_inner(), &audit).await;
     return_response(result).await
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+use crate::config::app_config::AppState;
+use crate::responses::http_response_impl::return_response;
+use crate::responses::success_msgs::SuccessMessages;
+use crate::responses::success_msgs_impl::create_error_response;
+use crate::services::withdrawal_addresses::{create_withdrawal_address, delete_withdrawal_address, get_withdrawal_address_from_db, get_withdrawal_addresses, update_withdrawal_address};
+use crate::structs::audit::AuditContext;
+use crate::structs::withdrawal_addresses::{WithdrawalAddressCreate, WithdrawalAddressQuery, WithdrawalAddressUpdate};
+use actix_web::{Responder, web};
+use std::sync::Arc;
+
+pub async fn create_withdrawal_address_handler(app_state: web::Data<Arc<AppState>>, address: web::Json<WithdrawalAddressCreate>, audit: AuditContext) -> impl Responder {
+    let pool = app_state.db.clone();
+    let redis_pool = match app_state.get_redis_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            let (message_key, message, data, status_code) = e.to_response();
+            return create_error_response(message_key, message, data, status_code);
+        }
+    };
+    let result = create_withdrawal_address(&pool, &redis_pool, address.into_inner(), &audit).await;
+    return_response(result).await
+}
+
+pub async fn list_withdrawal_addresses_handler(app_state: web::Data<Arc<AppState>>, query: web::Query<WithdrawalAddressQuery>) -> impl Responder {
+    let pool = app_state.db.clone();
+    let result = get_withdrawal_addresses(&pool, query.into_inner()).await;
+    return_response(result).await
+}
+
+pub async fn get_withdrawal_address_handler(app_state: web::Data<Arc<AppState>>, address_id: web::Path<i64>) -> impl Responder {
+    let pool = app_state.db.clone();
+    let result = get_withdrawal_address_from_db(&pool, address_id.into_inner()).await.map(|withdrawal_address| SuccessMessages::FoundWithdrawalAddresses { user_id: withdrawal_address.user_id, addresses_list: vec![withdrawal_address] });
+    return_response(result).await
+}
+
+pub async fn update_withdrawal_address_handler(app_state: web::Data<Arc<AppState>>, address_id: web::Path<i64>, address_update: web::Json<WithdrawalAddressUpdate>, audit: AuditContext) -> impl Responder {
+    let pool = app_state.db.clone();
+    let redis_pool = match app_state.get_redis_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            let (message_key, message, data, status_code) = e.to_response();
+            return create_error_response(message_key, message, data, status_code);
+        }
+    };
+    let result = update_withdrawal_address(&pool, &redis_pool, address_id.into_inner(), address_update.into_inner(), &audit).await;
+    return_response(result).await
+}
+
+pub async fn delete_withdrawal_address_handler(app_state: web::Data<Arc<AppState>>, address_id: web::Path<i64>, audit: AuditContext) -> impl Responder {
+    let pool = app_state.db.clone();
+    let result = delete_withdrawal_address(&pool, address_id.into_inner(), &audit).await;
+    return_response(result).await
+}
@@ -4,10 +4,11 @@ use crate::responses::success_msgs_impl::create_error_response;
 use crate::services::withdrawals::{create_withdrawal, get_withdrawals_history, rollback_withdrawal_request};
 use crate::structs::history::WithdrawalsHistoryRequest;
 use crate::structs::withdrawals::WithdrawalCreate;
+use crate::structs::audit::AuditContext;
 use actix_web::{Responder, web};
 use std::sync::Arc;
 
-pub async fn create_withdrawal_handler(app_state: web::Data<Arc<AppState>>, withdrawal: web::Json<WithdrawalCreate>) -> impl Responder {
+pub async fn create_withdrawal_handler(app_state: web::Data<Arc<AppState>>, withdrawal: web::Json<WithdrawalCreate>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -16,10 +17,10 @@ pub async fn create_withdrawal_handler(app_state: web::Data<Arc<AppState>>, with
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = create_withdrawal(&pool, &redis_pool, withdrawal.into_inner()).await;
+    let result = create_withdrawal(&pool, &redis_pool, withdrawal.into_inner(), &audit).await;
     return_response(result).await
 }
-pub async fn rollback_withdrawal_request_handler(app_state: web::Data<Arc<AppState>>, event_id: web::Path<i64>) -> impl Responder {
+pub async fn rollback_withdrawal_request_handler(app_state: web::Data<Arc<AppState>>, event_id: web::Path<i64>, audit: AuditContext) -> impl Responder {
     let pool = app_state.db.clone();
     let redis_pool = match app_state.get_redis_pool() {
         Ok(pool) => pool,
@@ -28,7 +29,7 @@ pub async fn rollback_withdrawal_request_handler(app_state: web::Data<Arc<AppSta
             return create_error_response(message_key, message, data, status_code);
         }
     };
-    let result = rollback_withdrawal_request(&pool, &redis_pool, event_id.into_inner()).await;
+    let result = rollback_withdrawal_request(&pool, &redis_pool, event_id.into_inner(), &audit).await;
     return_response(result).await
 }
 
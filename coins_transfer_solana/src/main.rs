@@ -11,9 +11,18 @@ use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use log::Level;
 use std::env;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
 use crate::config::redis_connection::initialize_redis_connection;
+use crate::services::ip_allowlist::{load_ip_allowlist, watch_ip_allowlist};
+use crate::services::configs::check_no_secret_configs;
+use crate::services::wallets::{drop_derived_wallet_private_keys, encrypt_wallet_private_keys};
+use crate::services::admins::encrypt_admin_api_secrets;
+use crate::middlewares::api_auth::load_signature_window_secs;
+use crate::utils::encryption::init_master_keys;
+use crate::services::signers::serve_signer;
+use crate::utils::keystore::{seal_keypair, seal_mnemonic, unlock_hd_seed};
+use crate::tasks::withdrawal_reconciler::reconcile_interval_secs;
 
 mod cache;
 mod config;
@@ -38,8 +47,37 @@ async fn main() -> Result<(), ModuleError> {
     // Logs initializing
     initialize_logs();
 
+    // `seal-mnemonic <path>` and `seal-keypair <keypair file> <dir>` write a new keystore and exit
+    let args: Vec<String> = env::args().collect();
+    let arg = |index: usize, name: &str| args.get(index).ok_or(ModuleError::EnvVarMissing(name.to_string()));
+    match args.get(1).map(String::as_str) {
+        Some("seal-mnemonic") => {
+            seal_mnemonic(arg(2, "keystore path")?)?;
+            return Ok(());
+        }
+        Some("seal-keypair") => {
+            seal_keypair(arg(2, "keypair path")?, arg(3, "keystore directory")?)?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // HD seed, unlocked from the keystore before anything can derive wallets
+    unlock_hd_seed()?;
+
+    // `serve-signer <host:port>` runs the signing service for the `remote` signer backend instead of the API
+    if args.get(1).map(String::as_str) == Some("serve-signer") {
+        serve_signer(arg(2, "signer address")?).await?;
+        return Ok(());
+    }
+
     // PostgreSQL Connection
     let db_connection = connect_to_db().await?;
+    check_no_secret_configs(&db_connection).await?;
+    // Wallet private keys and admin API secrets are stored encrypted, encrypt rows left in plaintext or wrapped by an older master key
+    init_master_keys()?;
+    encrypt_wallet_private_keys(&db_connection).await?;
+    encrypt_admin_api_secrets(&db_connection).await?;
     // Redis Connection Manager
     let redis_data = initialize_redis_connection().await?;
 
@@ -61,12 +99,22 @@ async fn main() -> Result<(), ModuleError> {
         log!(Level::Info, "Flushing Redis Cache for Coins module V2.");
         let _ = flush_cache(pool).await;
     }
+    // Derived wallets are re-derived when signing, drop their stored keys unless configured to keep them
+    let redis_pool = redis_data.as_ref().ok_or(ModuleError::RedisIssue)?;
+    drop_derived_wallet_private_keys(&db_connection, redis_pool).await?;
+    // The reconciler reads its interval in the background, refuse to start with an invalid one
+    reconcile_interval_secs(&db_connection, redis_pool).await?;
     // Making dynamic server URL by using host and port
     let server_url = format!("{host}:{port}");
 
     log!(Level::Info, "Application is starting");
 
-    let shared_state = Arc::new(AppState { db: db_connection, redis: redis_data });
+    // IP allowlist, reloaded whenever the configs table changes
+    let ip_allowlist = Arc::new(RwLock::new(load_ip_allowlist(&db_connection).await?));
+    tokio::spawn(watch_ip_allowlist(db_connection.clone(), ip_allowlist.clone()));
+
+    let signature_window_secs = load_signature_window_secs()?;
+    let shared_state = Arc::new(AppState { db: db_connection, redis: redis_data, ip_allowlist, signature_window_secs });
     let server = HttpServer::new(move || {
         let app = App::new()
             .wrap(
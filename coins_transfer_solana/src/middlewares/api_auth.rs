@@ -0,0 +1,190 @@
+use std::env;
+use std::sync::Arc;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, web};
+use chrono::DateTime;
+use crypsol_logger::log;
+use hmac::{Hmac, Mac};
+use log::Level;
+use sha2::Sha256;
+
+use crate::config::app_config::AppState;
+use crate::config::constants::API_SIGNATURE_WINDOW_SECS_DEFAULT;
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs_impl::create_error_response;
+use crate::services::admins::{get_active_admin_by_api_key, record_request_signature};
+use crate::structs::admins_api::{AuthenticatedAdmin, Role};
+use crate::utils::time::TimeHandler;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+pub const TIMESTAMP_HEADER: &str = "X-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const SIGNATURE_WINDOW_ENV: &str = "API_SIGNATURE_WINDOW_SECS";
+
+/// Authenticates a request signed by an admin. The client sends its API key,
+/// the current unix time in seconds and the hex HMAC-SHA256, keyed with its
+/// API secret, of `METHOD\nPATH\nTIMESTAMP\nBODY` where `PATH` includes the
+/// query string. Requests whose timestamp is outside the allowed window are
+/// rejected, and so is a signature already used within it, so a captured
+/// request can not be replayed. On success the `AuthenticatedAdmin` is
+/// stored in the request extensions for handlers
+/// (`web::ReqData<AuthenticatedAdmin>`).
+pub async fn verify_api_signature<B: MessageBody>(mut req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let body = req.extract::<web::Bytes>().await?;
+    req.set_payload(Payload::from(body.clone()));
+
+    match authenticate(&req, &body).await {
+        Ok(admin) => {
+            req.extensions_mut().insert(admin);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(e) => {
+            let (message_key, message, data, status_code) = e.to_response();
+            let response = create_error_response(message_key, message, data, status_code);
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+async fn authenticate(req: &ServiceRequest, body: &[u8]) -> Result<AuthenticatedAdmin, Error> {
+    let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::trim).filter(|value| !value.is_empty());
+    let (Some(api_key), Some(timestamp), Some(signature)) = (header(API_KEY_HEADER), header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)) else {
+        log!(Level::Warn, "Rejected {} {}: missing authentication headers", req.method(), req.path());
+        return Err(Error::Unauthorized);
+    };
+
+    let app_state = req.app_data::<web::Data<Arc<AppState>>>().ok_or(Error::TechnicalIssue)?;
+    let window = app_state.signature_window_secs;
+    let Some(timestamp_secs) = check_timestamp(timestamp, TimeHandler::new().get_current_time().timestamp(), window) else {
+        log!(Level::Warn, "Rejected {} {}: timestamp {} is invalid or outside the allowed window", req.method(), req.path(), timestamp);
+        return Err(Error::Unauthorized);
+    };
+
+    let Some(admin) = get_active_admin_by_api_key(&app_state.db, api_key).await? else {
+        log!(Level::Warn, "Rejected {} {}: unknown or inactive api key", req.method(), req.path());
+        return Err(Error::Unauthorized);
+    };
+
+    let path = req.uri().path_and_query().map_or(req.path(), |path| path.as_str());
+    let signature = match verify_signature(&admin.api_secret, req.method().as_str(), path, timestamp, body, signature) {
+        Ok(signature) => signature,
+        Err(e) => {
+            log!(Level::Warn, "Rejected {} {} for admin {}: {}", req.method(), path, admin.id, e);
+            return Err(Error::Unauthorized);
+        }
+    };
+    let expires_at = DateTime::from_timestamp(timestamp_secs.saturating_add(window), 0).ok_or(Error::TechnicalIssue)?;
+    if !record_request_signature(&app_state.db, admin.id, &signature, expires_at).await? {
+        log!(Level::Warn, "Rejected {} {} for admin {}: signature already used", req.method(), path, admin.id);
+        return Err(Error::Unauthorized);
+    }
+
+    let Ok(role) = admin.role.parse::<Role>() else {
+        log!(Level::Error, "Rejected {} {} for admin {}: unknown role {}", req.method(), path, admin.id, admin.role);
+        return Err(Error::Forbidden);
+    };
+
+    Ok(AuthenticatedAdmin { id: admin.id, name: admin.name, role })
+}
+
+/// Parses the request timestamp, `None` unless it is within `window`
+/// seconds of `now`.
+fn check_timestamp(timestamp: &str, now: i64, window: i64) -> Option<i64> {
+    let timestamp = timestamp.parse::<i64>().ok()?;
+    (now.abs_diff(timestamp) <= window.unsigned_abs()).then_some(timestamp)
+}
+
+#[derive(Debug, PartialEq)]
+enum SignatureError {
+    NotHex,
+    Mismatch,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::NotHex => f.write_str("signature is not hex"),
+            SignatureError::Mismatch => f.write_str("signature mismatch"),
+        }
+    }
+}
+
+/// Checks the hex `signature` of a request against `secret`, returning the
+/// decoded signature.
+fn verify_signature(secret: &str, method: &str, path: &str, timestamp: &str, body: &[u8], signature: &str) -> Result<Vec<u8>, SignatureError> {
+    let signature = hex::decode(signature).map_err(|_| SignatureError::NotHex)?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| SignatureError::Mismatch)?;
+    mac.update(format!("{}\n{}\n{}\n", method, path, timestamp).as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature).map_err(|_| SignatureError::Mismatch)?;
+    Ok(signature)
+}
+
+/// Reads `API_SIGNATURE_WINDOW_SECS`, the accepted clock skew of signed
+/// requests, once at startup. It must be a positive number of seconds.
+pub fn load_signature_window_secs() -> Result<i64, Error> {
+    let Ok(value) = env::var(SIGNATURE_WINDOW_ENV) else {
+        return Ok(API_SIGNATURE_WINDOW_SECS_DEFAULT);
+    };
+    value.trim().parse::<i64>().ok().filter(|window| *window > 0).ok_or_else(|| {
+        log!(Level::Error, "Invalid {} {}, expected a positive number of seconds", SIGNATURE_WINDOW_ENV, value);
+        Error::InvalidConfiguration
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+    const BODY: &[u8] = br#"{"user_id":42,"usd_amount":10}"#;
+
+    fn sign(method: &str, path: &str, timestamp: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(format!("{}\n{}\n{}\n", method, path, timestamp).as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let signature = sign("POST", "/api/v1/withdrawals/?dry=1", "1700000000", BODY);
+        let decoded = verify_signature(SECRET, "POST", "/api/v1/withdrawals/?dry=1", "1700000000", BODY, &signature).unwrap();
+        assert_eq!(hex::encode(decoded), signature);
+        assert!(verify_signature(SECRET, "POST", "/api/v1/withdrawals/?dry=1", "1700000000", BODY, &signature.to_uppercase()).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_tampered_request() {
+        let signature = sign("POST", "/api/v1/withdrawals/", "1700000000", BODY);
+        let tampered = br#"{"user_id":42,"usd_amount":1000}"#;
+        assert_eq!(verify_signature(SECRET, "POST", "/api/v1/withdrawals/", "1700000000", tampered, &signature), Err(SignatureError::Mismatch));
+        assert_eq!(verify_signature(SECRET, "PUT", "/api/v1/withdrawals/", "1700000000", BODY, &signature), Err(SignatureError::Mismatch));
+        assert_eq!(verify_signature(SECRET, "POST", "/api/v1/configs/", "1700000000", BODY, &signature), Err(SignatureError::Mismatch));
+        assert_eq!(verify_signature(SECRET, "POST", "/api/v1/withdrawals/", "1700000001", BODY, &signature), Err(SignatureError::Mismatch));
+        assert_eq!(verify_signature("other", "POST", "/api/v1/withdrawals/", "1700000000", BODY, &signature), Err(SignatureError::Mismatch));
+    }
+
+    #[test]
+    fn rejects_a_skewed_timestamp() {
+        let now = 1_700_000_000;
+        assert_eq!(check_timestamp("1700000000", now, 300), Some(now));
+        assert_eq!(check_timestamp("1699999700", now, 300), Some(now - 300));
+        assert_eq!(check_timestamp("1700000300", now, 300), Some(now + 300));
+        assert_eq!(check_timestamp("1699999699", now, 300), None);
+        assert_eq!(check_timestamp("1700000301", now, 300), None);
+        assert_eq!(check_timestamp(&i64::MIN.to_string(), now, 300), None);
+        assert_eq!(check_timestamp("1700000000.5", now, 300), None);
+    }
+
+    #[test]
+    fn rejects_a_non_hex_signature() {
+        let signature = sign("GET", "/api/v1/configs/", "1700000000", b"");
+        for signature in ["not hex", "abc", &signature[1..], &format!("{}zz", &signature[2..])] {
+            assert_eq!(verify_signature(SECRET, "GET", "/api/v1/configs/", "1700000000", b"", signature), Err(SignatureError::NotHex));
+        }
+    }
+}
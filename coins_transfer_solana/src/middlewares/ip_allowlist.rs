@@ -0,0 +1,57 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, web};
+use crypsol_logger::log;
+use log::Level;
+
+use crate::config::app_config::AppState;
+use crate::middlewares::GuardFuture;
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs_impl::create_error_response;
+
+pub const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Builds a middleware for `from_fn` rejecting with `403` clients whose
+/// address is not allowed for `scope` by the `IpAllowlist` in `AppState`.
+/// Wrap it last on a scope so it runs before authentication.
+pub fn require_allowed_ip<B: MessageBody + 'static>(scope: &'static str) -> impl Fn(ServiceRequest, Next<B>) -> GuardFuture<B> {
+    move |req, next| Box::pin(check_ip(req, next, scope))
+}
+
+/// Address of the client behind `req`: the peer, or the address it forwarded
+/// in `X-Forwarded-For` when it is a trusted proxy of the `IpAllowlist`.
+/// `None` when the peer address or app state is unavailable.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let app_state = req.app_data::<web::Data<Arc<AppState>>>()?;
+    let peer = req.peer_addr()?;
+    let forwarded_for = req.headers().get(FORWARDED_FOR_HEADER).and_then(|value| value.to_str().ok());
+    let allowlist = app_state.ip_allowlist.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    Some(allowlist.client_ip(peer.ip(), forwarded_for))
+}
+
+async fn check_ip<B: MessageBody>(req: ServiceRequest, next: Next<B>, scope: &'static str) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let allowed = match (req.app_data::<web::Data<Arc<AppState>>>(), client_ip(req.request())) {
+        (Some(app_state), Some(client)) => {
+            let allowed = app_state.ip_allowlist.read().unwrap_or_else(|poisoned| poisoned.into_inner()).allows(scope, client);
+            if !allowed {
+                log!(Level::Warn, "Rejected {} {}: {} is not allowed for scope {}", req.method(), req.path(), client, scope);
+            }
+            allowed
+        }
+        _ => {
+            log!(Level::Error, "Rejected {} {}: peer address or app state unavailable", req.method(), req.path());
+            false
+        }
+    };
+
+    if allowed {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+    let (message_key, message, data, status_code) = Error::Forbidden.to_response();
+    let response = create_error_response(message_key, message, data, status_code);
+    Ok(req.into_response(response).map_into_right_body())
+}
@@ -0,0 +1,13 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::ServiceResponse;
+
+pub mod api_auth;
+pub mod ip_allowlist;
+pub mod roles;
+
+/// Future returned by the parameterised middlewares built for `from_fn`; the
+/// right body carries their own error response.
+pub type GuardFuture<B> = Pin<Box<dyn Future<Output = Result<ServiceResponse<EitherBody<B>>, actix_web::Error>>>>;
@@ -0,0 +1,122 @@
+use actix_web::HttpMessage;
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use crypsol_logger::log;
+use log::Level;
+
+use crate::middlewares::GuardFuture;
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs_impl::create_error_response;
+use crate::structs::admins_api::{AuthenticatedAdmin, Role};
+
+pub const ALL_ROLES: &[Role] = &[Role::ReadOnly, Role::DepositsOperator, Role::WithdrawalsOperator, Role::ConfigAdmin];
+
+/// Roles allowed to read (`GET`/`HEAD`) and to mutate (any other method) the
+/// routes wrapped by [`require_roles`].
+#[derive(Clone, Copy, Debug)]
+pub struct Permissions {
+    pub read: &'static [Role],
+    pub write: &'static [Role],
+}
+
+impl Permissions {
+    /// Readable by every role, writable by `write` only.
+    pub const fn write(write: &'static [Role]) -> Self {
+        Permissions { read: ALL_ROLES, write }
+    }
+
+    /// Every method limited to `roles`, for `GET` routes that change state.
+    pub const fn only(roles: &'static [Role]) -> Self {
+        Permissions { read: roles, write: roles }
+    }
+}
+
+/// Builds a middleware for `from_fn` rejecting with `403` admins whose role
+/// is not allowed by `permissions`. It must run inside `verify_api_signature`,
+/// i.e. be wrapped before it on the same scope.
+pub fn require_roles(permissions: Permissions) -> impl Fn(ServiceRequest, Next<BoxBody>) -> GuardFuture<BoxBody> {
+    move |req, next| Box::pin(check_role(req, next, permissions))
+}
+
+async fn check_role(req: ServiceRequest, next: Next<BoxBody>, permissions: Permissions) -> Result<ServiceResponse<EitherBody<BoxBody>>, actix_web::Error> {
+    let allowed = if matches!(*req.method(), Method::GET | Method::HEAD) { permissions.read } else { permissions.write };
+    let admin = req.extensions().get::<AuthenticatedAdmin>().cloned();
+
+    match admin {
+        Some(admin) if allowed.contains(&admin.role) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        admin => {
+            match admin {
+                Some(admin) => log!(Level::Warn, "Rejected {} {} for admin {}: role {} is not allowed", req.method(), req.path(), admin.id, admin.role),
+                None => log!(Level::Error, "Rejected {} {}: role check ran without an authenticated admin", req.method(), req.path()),
+            }
+            let (message_key, message, data, status_code) = Error::Forbidden.to_response();
+            let response = create_error_response(message_key, message, data, status_code);
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, test, web};
+
+    use super::*;
+
+    /// Sends `method path` as an admin with `role` (anonymous without one)
+    /// through routes guarded like the `/coins` scope.
+    async fn status(role: Option<Role>, method: Method, path: &str) -> StatusCode {
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/coins")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::ConfigAdmin]))))
+                    .wrap_fn(move |req, srv| {
+                        if let Some(role) = role {
+                            req.extensions_mut().insert(AuthenticatedAdmin { id: 1, name: "admin".to_string(), role });
+                        }
+                        srv.call(req)
+                    })
+                    .route("/", web::to(HttpResponse::Ok))
+                    .service(web::resource("/address/new").wrap(from_fn(require_roles(Permissions::only(&[Role::DepositsOperator])))).route(web::get().to(HttpResponse::Ok))),
+            ),
+        )
+        .await;
+        test::call_service(&app, test::TestRequest::default().method(method).uri(path).to_request()).await.status()
+    }
+
+    #[actix_web::test]
+    async fn read_only_may_only_read() {
+        assert_eq!(status(Some(Role::ReadOnly), Method::GET, "/coins/").await, StatusCode::OK);
+        assert_eq!(status(Some(Role::ReadOnly), Method::HEAD, "/coins/").await, StatusCode::OK);
+        for method in [Method::POST, Method::PUT, Method::DELETE, Method::PATCH] {
+            assert_eq!(status(Some(Role::ReadOnly), method, "/coins/").await, StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_the_scope_owner_may_write() {
+        for role in ALL_ROLES {
+            let expected = if *role == Role::ConfigAdmin { StatusCode::OK } else { StatusCode::FORBIDDEN };
+            assert_eq!(status(Some(*role), Method::POST, "/coins/").await, expected, "{}", role);
+            assert_eq!(status(Some(*role), Method::GET, "/coins/").await, StatusCode::OK, "{}", role);
+        }
+    }
+
+    #[actix_web::test]
+    async fn address_generation_is_limited_to_deposits_operators() {
+        for role in ALL_ROLES {
+            let expected = if *role == Role::DepositsOperator { StatusCode::OK } else { StatusCode::FORBIDDEN };
+            assert_eq!(status(Some(*role), Method::GET, "/coins/address/new").await, expected, "{}", role);
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_requests_without_an_admin() {
+        assert_eq!(status(None, Method::GET, "/coins/").await, StatusCode::FORBIDDEN);
+        assert_eq!(status(None, Method::POST, "/coins/").await, StatusCode::FORBIDDEN);
+    }
+}
@@ -34,6 +34,9 @@ pub enum Error {
     #[error("Error making transaction.")]
     RpcIssue,
 
+    #[error("Error signing transaction.")]
+    SignerIssue,
+
     #[error("Error making transaction: {0} is required.")]
     MissingField(String),
 
@@ -44,7 +47,7 @@ pub enum Error {
     SerializationIssue,
 
     #[error("Transaction is not yet confirmed.")]
-    TransactionNotConfirmed,
+    TransactionNotConfirmed(String, Option<u64>),
 
     #[error("The requested transfer amount is greater than the maximum allowed.")]
     GreaterThanMaximumTransfer,
@@ -58,11 +61,23 @@ pub enum Error {
     #[error("Invalid Address")]
     InvalidAddress,
 
+    #[error("Withdrawals to this address are not allowed. Register it and wait for the cool-down to end.")]
+    WithdrawalAddressNotAllowed,
+
     #[error("This deposit has already been processed.")]
     DepositAlreadyRecorded,
 
     #[error("User can not request deposit for other users. Though, you can transfer funds to other users.")]
     UserIdMismatch,
+
+    #[error("Unauthorized request.")]
+    Unauthorized,
+
+    #[error("You do not have permission to perform this action.")]
+    Forbidden,
+
+    #[error("Secrets can not be stored in configs.")]
+    SecretConfig,
 }
 
 #[derive(Debug, Error, Serialize, Deserialize, EnumIter)]
@@ -16,15 +16,20 @@ impl Error {
             Error::DuplicateEntry => ("duplicate_entry", message, data, StatusCode::CONFLICT),
             Error::MissingField(field) => ("missing_field", message, json!({ "field": field }), StatusCode::BAD_REQUEST),
             Error::RpcIssue => ("rpc_issue", message, data, StatusCode::INTERNAL_SERVER_ERROR),
+            Error::SignerIssue => ("signer_issue", message, data, StatusCode::INTERNAL_SERVER_ERROR),
             Error::InvalidAmount => ("invalid_amount", message, data, StatusCode::BAD_REQUEST),
             Error::TransactionInProgress => ("transaction_in_progress", message, data, StatusCode::BAD_REQUEST),
-            Error::TransactionNotConfirmed => ("transaction_not_confirmed", message, data, StatusCode::BAD_REQUEST),
+            Error::TransactionNotConfirmed(signature, _) => ("transaction_not_confirmed", message, json!({ "signature": signature }), StatusCode::BAD_REQUEST),
             Error::GreaterThanMaximumTransfer => ("greater_than_maximum_transfer", message, data, StatusCode::BAD_REQUEST),
             Error::InsufficientBalance => ("insufficient_balance", message, data, StatusCode::BAD_REQUEST),
             Error::LessThanMinimumTransfer => ("less_than_minimum_transfer", message, data, StatusCode::BAD_REQUEST),
             Error::InvalidAddress => ("invalid_address", message, data, StatusCode::BAD_REQUEST),
+            Error::WithdrawalAddressNotAllowed => ("withdrawal_address_not_allowed", message, data, StatusCode::FORBIDDEN),
             Error::DepositAlreadyRecorded => ("deposit_already_recorded", message, data, StatusCode::BAD_REQUEST),
             Error::UserIdMismatch => ("user_id_mismatch", message, data, StatusCode::BAD_REQUEST),
+            Error::Unauthorized => ("unauthorized", message, data, StatusCode::UNAUTHORIZED),
+            Error::Forbidden => ("forbidden", message, data, StatusCode::FORBIDDEN),
+            Error::SecretConfig => ("secret_config", message, data, StatusCode::BAD_REQUEST),
             _ => {
                 let status_code = StatusCode::INTERNAL_SERVER_ERROR;
                 let error_key = match self {
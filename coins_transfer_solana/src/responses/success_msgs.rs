@@ -2,10 +2,12 @@ use actix_web::http::StatusCode;
 use bigdecimal::{BigDecimal, ToPrimitive};
 use serde_json::{Value, json};
 
+use crate::entities::audit_log::AuditLog;
 use crate::entities::configs::Configs;
 use crate::entities::deposits::Deposits;
 use crate::entities::users::Users;
 use crate::entities::users_wallets::UsersWallets;
+use crate::entities::withdrawal_addresses::WithdrawalAddresses;
 use crate::entities::withdrawals::Withdrawals;
 use crate::structs::coins::CoinInfo;
 use crate::structs::deposits::DepositsDetails;
@@ -155,6 +157,21 @@ pub enum SuccessMessages {
         data: Vec<Withdrawals>,
     },
 
+    // withdrawal addresses
+    CreatedWithdrawalAddress {
+        withdrawal_address: WithdrawalAddresses,
+    },
+    FoundWithdrawalAddresses {
+        user_id: i64,
+        addresses_list: Vec<WithdrawalAddresses>,
+    },
+    UpdatedWithdrawalAddress {
+        withdrawal_address: WithdrawalAddresses,
+    },
+    DeletedWithdrawalAddress {
+        address_id: i64,
+    },
+
     // config
     CreatedConfig {
         config_id: i16,
@@ -172,6 +189,15 @@ pub enum SuccessMessages {
     },
     DeletedAllConfigs,
 
+    // audit
+    FoundAuditLog {
+        page: i32,
+        per_page: i32,
+        total_results: i32,
+        total_pages: i32,
+        data: Vec<AuditLog>,
+    },
+
     // payments
     PaymentProcessed {
         tx_id: String,
@@ -399,6 +425,21 @@ impl SuccessMessages {
                 ("BulkWithdrawalsHistoryFetched", message, data, StatusCode::OK)
             }
 
+            // withdrawal addresses
+            SuccessMessages::CreatedWithdrawalAddress { withdrawal_address } => {
+                let message = format!("Withdrawal address with ID {} is created successfully", withdrawal_address.id);
+                ("WithdrawalAddressCreated", message, json!({ "withdrawal_address": withdrawal_address }), StatusCode::CREATED)
+            }
+            SuccessMessages::FoundWithdrawalAddresses { user_id, addresses_list } => {
+                let message = format!("Withdrawal addresses for user with ID {user_id} found successfully");
+                ("WithdrawalAddressesFound", message, json!({ "user_id": user_id, "addresses": addresses_list }), StatusCode::OK)
+            }
+            SuccessMessages::UpdatedWithdrawalAddress { withdrawal_address } => {
+                let message = format!("Withdrawal address with ID {} updated successfully", withdrawal_address.id);
+                ("WithdrawalAddressUpdated", message, json!({ "withdrawal_address": withdrawal_address }), StatusCode::OK)
+            }
+            SuccessMessages::DeletedWithdrawalAddress { address_id } => ("WithdrawalAddressDeleted", format!("Withdrawal address with ID {address_id} deleted successfully"), json!({ "address_id": address_id }), StatusCode::OK),
+
             // config
             SuccessMessages::CreatedConfig { config_id } => {
                 let message = format!("Config with ID {config_id} is created successfully");
@@ -432,6 +473,18 @@ impl SuccessMessages {
             SuccessMessages::DeletedConfig { config } => ("ConfigDeleted", format!("Config with name {config} deleted successfully"), json!({ "config": config }), StatusCode::OK),
             SuccessMessages::DeletedAllConfigs => ("ConfigsDeleted", "All configs deleted successfully".to_string(), json!({}), StatusCode::OK),
 
+            // audit
+            SuccessMessages::FoundAuditLog { page, per_page, total_results, total_pages, data } => {
+                let data = json!({
+                    "page": page,
+                    "per_page": per_page,
+                    "total_results": total_results,
+                    "total_pages": total_pages,
+                    "data": data
+                });
+                ("AuditLogFetched", "Audit log found successfully".to_string(), data, StatusCode::OK)
+            }
+
             // webhooks
             // payments
             SuccessMessages::PaymentProcessed { tx_id, details, confirmations } => {
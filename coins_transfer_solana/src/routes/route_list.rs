@@ -1,5 +1,7 @@
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
+use crate::handlers::audit::get_audit_log_handler;
 use crate::handlers::configs::{
     create_config_handler, delete_all_config_handler, delete_config_handler, get_config_handler, list_configs_handler, update_config_handler,
 };
@@ -12,6 +14,13 @@ use crate::handlers::notify_webhooks::validate_usdt_payment;
 use crate::handlers::users::{create_user_handler, rollback_user_creation_handler};
 use crate::handlers::coins::{create_coin_handler, get_all_coins_handler, address_generation_handler};
 use crate::handlers::wallets::{get_wallet_handler, update_wallet_handler};
+use crate::middlewares::api_auth::verify_api_signature;
+use crate::middlewares::ip_allowlist::require_allowed_ip;
+use crate::middlewares::roles::{Permissions, require_roles};
+use crate::structs::admins_api::Role;
+use crate::handlers::withdrawal_addresses::{
+    create_withdrawal_address_handler, delete_withdrawal_address_handler, get_withdrawal_address_handler, list_withdrawal_addresses_handler, update_withdrawal_address_handler,
+};
 use crate::handlers::withdrawals::{
     create_withdrawal_handler, rollback_withdrawal_request_handler, get_withdrawal_history_handler,
 };
@@ -21,11 +30,15 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         web::scope("/api/v1")
             .service(
                 web::scope("/health")
+                    .wrap(from_fn(require_allowed_ip("health")))
                     .route("/", web::post().to(health_check))
                     .route("/", web::get().to(health_check)),
             )
             .service(
                 web::scope("/configs")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::ConfigAdmin]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("configs")))
                     .route("/", web::post().to(create_config_handler))
                     .route("/", web::get().to(list_configs_handler))
                     .route("/{name}", web::get().to(get_config_handler))
@@ -35,29 +48,66 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             )
             .service(
                 web::scope("/users")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::DepositsOperator, Role::WithdrawalsOperator]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("users")))
                     .route("/", web::post().to(create_user_handler))
                     .route("/rollback/{event_id}", web::post().to(rollback_user_creation_handler)),
             )
             .service(
                 web::scope("/coins")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::ConfigAdmin]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("coins")))
                     .route("/", web::post().to(create_coin_handler))
                     .route("/", web::get().to(get_all_coins_handler))
                     .route("/rate/{symbol}", web::get().to(conversion_rate_get_handler))
-                    .route("/address/new", web::get().to(address_generation_handler)),
+                    .service(
+                        web::resource("/address/new")
+                            .wrap(from_fn(require_roles(Permissions::only(&[Role::DepositsOperator]))))
+                            .route(web::get().to(address_generation_handler)),
+                    ),
             )
             .service(
                 web::scope("/wallets")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::ConfigAdmin]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("wallets")))
                     .route("/", web::get().to(get_wallet_handler))
                     .route("/{wallet_id}", web::put().to(update_wallet_handler)),
             )
+            .service(
+                web::scope("/withdrawals/addresses")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::ConfigAdmin]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("withdrawals")))
+                    .route("/", web::post().to(create_withdrawal_address_handler))
+                    .route("/", web::get().to(list_withdrawal_addresses_handler))
+                    .route("/{address_id}", web::get().to(get_withdrawal_address_handler))
+                    .route("/{address_id}", web::put().to(update_withdrawal_address_handler))
+                    .route("/{address_id}", web::delete().to(delete_withdrawal_address_handler)),
+            )
             .service(
                 web::scope("/withdrawals")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::WithdrawalsOperator]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("withdrawals")))
                     .route("/", web::post().to(create_withdrawal_handler))
                     .route("/rollback/{event_id}", web::post().to(rollback_withdrawal_request_handler))
                     .route("/history", web::get().to(get_withdrawal_history_handler)),
             )
+            .service(
+                web::scope("/audit")
+                    .wrap(from_fn(require_roles(Permissions::only(&[Role::ReadOnly, Role::ConfigAdmin]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("audit")))
+                    .route("/", web::get().to(get_audit_log_handler)),
+            )
             .service(
                 web::scope("/deposits")
+                    .wrap(from_fn(require_roles(Permissions::write(&[Role::DepositsOperator]))))
+                    .wrap(from_fn(verify_api_signature))
+                    .wrap(from_fn(require_allowed_ip("deposits")))
                     .route("/usdt/validate_deposit", web::post().to(validate_usdt_payment))
                     .route("/history", web::get().to(get_transaction_history_handler)),
             ),
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use crypsol_logger::log;
+use log::Level;
+use sqlx::{PgPool, query};
+
+use crate::entities::admins::Admins;
+use crate::responses::error_msgs::Error;
+use crate::utils::encryption::{EncryptedSecret, master_keys};
+
+/// Looks up the active admin owning `api_key`, with its API secret
+/// decrypted. Secrets are read from the database on every request and never
+/// cached. A secret still in plaintext, inserted since the service started,
+/// is encrypted on the way.
+pub async fn get_active_admin_by_api_key(pool: &PgPool, api_key: &str) -> Result<Option<Admins>, Error> {
+    let result = query!(
+        r#"
+        SELECT id, name, api_key, api_secret, api_secret_data_key, api_secret_key_version, role, status, created_at, updated_at
+        FROM admins WHERE api_key = $1 AND status = TRUE
+        "#,
+        api_key
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on fetching admin by api key: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    let Some(admin) = result else {
+        return Ok(None);
+    };
+    let api_secret = match (admin.api_secret_data_key, admin.api_secret_key_version) {
+        (Some(data_key), Some(key_version)) => decrypt_api_secret(admin.id, EncryptedSecret { ciphertext: admin.api_secret, data_key, key_version })?,
+        _ => {
+            let sealed = master_keys()?.encrypt(admin.api_secret.as_bytes(), &admin_aad(admin.id))?;
+            store_sealed_api_secret(pool, admin.id, &sealed, &admin.api_secret, None).await?;
+            log!(Level::Info, "Encrypted the API secret of admin {} on first use", admin.id);
+            admin.api_secret
+        }
+    };
+    Ok(Some(Admins {
+        api_secret,
+        id: admin.id,
+        name: admin.name,
+        api_key: admin.api_key,
+        role: admin.role,
+        status: admin.status,
+        created_at: admin.created_at,
+        updated_at: admin.updated_at,
+    }))
+}
+
+fn admin_aad(admin_id: i32) -> Vec<u8> {
+    format!("admins:{admin_id}").into_bytes()
+}
+
+fn decrypt_api_secret(admin_id: i32, api_secret: EncryptedSecret) -> Result<String, Error> {
+    let plaintext = master_keys()?.decrypt(&api_secret, &admin_aad(admin_id))?;
+    String::from_utf8(plaintext).map_err(|_| Error::TechnicalIssue)
+}
+
+/// Replaces the API secret of `admin_id` with `sealed`, unless the row no
+/// longer holds `api_secret` wrapped by `key_version` (`None` for plaintext).
+async fn store_sealed_api_secret(pool: &PgPool, admin_id: i32, sealed: &EncryptedSecret, api_secret: &str, key_version: Option<i16>) -> Result<(), Error> {
+    query!(
+        r#"
+        UPDATE admins SET api_secret = $1, api_secret_data_key = $2, api_secret_key_version = $3
+        WHERE id = $4 AND api_secret = $5 AND api_secret_key_version IS NOT DISTINCT FROM $6
+        "#,
+        sealed.ciphertext,
+        sealed.data_key,
+        sealed.key_version,
+        admin_id,
+        api_secret,
+        key_version
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+        log!(Level::Error, "Database error while encrypting the API secret of admin {}: {:?}", admin_id, e);
+        Error::DatabaseIssue
+    })
+}
+
+/// Encrypts API secrets inserted in plaintext and rewraps data keys of older
+/// master key versions with the current one. Run at startup, after the master
+/// keys are loaded; secrets inserted later are encrypted when first used.
+pub async fn encrypt_admin_api_secrets(pool: &PgPool) -> Result<(), Error> {
+    let keys = master_keys()?;
+    let current_version = keys.current_version();
+    let rows = query!(
+        r#"
+        SELECT id, api_secret, api_secret_data_key, api_secret_key_version FROM admins
+        WHERE api_secret_key_version IS NULL OR api_secret_key_version <> $1
+        "#,
+        current_version
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error while loading admin API secrets: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    for row in &rows {
+        let aad = admin_aad(row.id);
+        let sealed = match (&row.api_secret_data_key, row.api_secret_key_version) {
+            (Some(data_key), Some(key_version)) => keys.rewrap(&EncryptedSecret { ciphertext: row.api_secret.clone(), data_key: data_key.clone(), key_version }, &aad)?,
+            _ => keys.encrypt(row.api_secret.as_bytes(), &aad)?,
+        };
+        // Only update the row if nobody else changed it meanwhile.
+        store_sealed_api_secret(pool, row.id, &sealed, &row.api_secret, row.api_secret_key_version).await?;
+    }
+
+    if !rows.is_empty() {
+        log!(Level::Info, "Encrypted {} admin API secrets with master key version {}", rows.len(), current_version);
+    }
+    Ok(())
+}
+
+/// Remembers the signature of an accepted request until `expires_at`, when
+/// its timestamp leaves the allowed window. Returns `false` when it was
+/// already used, i.e. the request is a replay. Expired signatures are
+/// dropped on the way.
+pub async fn record_request_signature(pool: &PgPool, admin_id: i32, signature: &[u8], expires_at: DateTime<Utc>) -> Result<bool, Error> {
+    let result = query!(
+        r#"
+        WITH expired AS (DELETE FROM api_request_signatures WHERE expires_at < NOW())
+        INSERT INTO api_request_signatures (signature, admin_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (signature) DO NOTHING
+        "#,
+        signature,
+        admin_id,
+        expires_at
+    )
+    .execute(pool)
+    .await;
+
+    result.map(|done| done.rows_affected() == 1).map_err(|e| {
+        log!(Level::Error, "Database error on recording request signature: {:?}", e);
+        Error::DatabaseIssue
+    })
+}
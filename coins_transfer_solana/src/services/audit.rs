@@ -0,0 +1,134 @@
+use crypsol_logger::log;
+use log::Level;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool, query, query_as};
+
+use crate::entities::audit_log::AuditLog;
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs::SuccessMessages;
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity, AuditLogQuery};
+
+const AUDIT_PER_PAGE_MAX: i32 = 100;
+
+/// Serializes a record for the `before` and `after` columns. Callers pass
+/// redacted copies, audit entries must never hold secrets.
+pub fn audit_value<T: Serialize>(record: &T) -> Option<Value> {
+    serde_json::to_value(record).ok()
+}
+
+/// [`audit_value`] of `record` with the fields of `extra` added, for changes
+/// to columns `record` doesn't serialize.
+pub fn audit_value_with<T: Serialize>(record: &T, extra: Value) -> Option<Value> {
+    let mut value = audit_value(record)?;
+    if let (Value::Object(fields), Value::Object(extra)) = (&mut value, extra) {
+        fields.extend(extra);
+    }
+    Some(value)
+}
+
+/// Appends an entry to `audit_log`, logging a failure rather than returning
+/// it. Only for entries of requests that fail anyway, like a withdrawal whose
+/// transfer could not be sent.
+pub async fn record_audit(pool: &PgPool, audit: &AuditContext, action: AuditAction, entity: AuditEntity, entity_id: &str, before: Option<Value>, after: Option<Value>) {
+    let _ = try_record_audit(pool, audit, action, entity, entity_id, before, after).await;
+}
+
+/// Like [`record_audit`], but returns the failure. Changes are written with
+/// their entry in one transaction, so a change is never left unaudited.
+pub async fn try_record_audit<'e>(executor: impl PgExecutor<'e>, audit: &AuditContext, action: AuditAction, entity: AuditEntity, entity_id: &str, before: Option<Value>, after: Option<Value>) -> Result<(), Error> {
+    query!(
+        r#"
+        INSERT INTO audit_log (action, entity, entity_id, actor_id, actor_name, request_id, client_ip, before, after, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+        "#,
+        action.to_string(),
+        entity.to_string(),
+        entity_id,
+        audit.actor.as_ref().map(|admin| admin.id),
+        audit.actor.as_ref().map(|admin| admin.name.clone()),
+        audit.request_id.clone(),
+        audit.client_ip.clone(),
+        before,
+        after
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+        log!(Level::Error, "Failed to record audit entry {} {} {} for request {}: {:?}", action, entity, entity_id, audit.request_id, e);
+        Error::DatabaseIssue
+    })
+}
+
+/// Audit entries matching every filter set in `query`, newest first.
+pub async fn get_audit_log(pool: &PgPool, query: AuditLogQuery) -> Result<SuccessMessages, Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(10).clamp(1, AUDIT_PER_PAGE_MAX);
+    let action = query.action.map(|action| action.to_string());
+    let entity = query.entity.map(|entity| entity.to_string());
+
+    let total_results = query!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM audit_log
+        WHERE ($1::INTEGER IS NULL OR actor_id = $1)
+            AND ($2::VARCHAR IS NULL OR action = $2)
+            AND ($3::VARCHAR IS NULL OR entity = $3)
+            AND ($4::VARCHAR IS NULL OR entity_id = $4)
+            AND ($5::VARCHAR IS NULL OR request_id = $5)
+            AND ($6::VARCHAR IS NULL OR client_ip = $6)
+            AND ($7::DATE IS NULL OR DATE(created_at) >= $7)
+            AND ($8::DATE IS NULL OR DATE(created_at) <= $8)
+        "#,
+        query.actor_id,
+        action,
+        entity,
+        query.entity_id,
+        query.request_id,
+        query.client_ip,
+        query.from_date,
+        query.to_date
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on counting audit entries: {:?}", e);
+        Error::DatabaseIssue
+    })?
+    .count;
+
+    let data = query_as!(
+        AuditLog,
+        r#"
+        SELECT id, action, entity, entity_id, actor_id, actor_name, request_id, client_ip, before, after, created_at FROM audit_log
+        WHERE ($1::INTEGER IS NULL OR actor_id = $1)
+            AND ($2::VARCHAR IS NULL OR action = $2)
+            AND ($3::VARCHAR IS NULL OR entity = $3)
+            AND ($4::VARCHAR IS NULL OR entity_id = $4)
+            AND ($5::VARCHAR IS NULL OR request_id = $5)
+            AND ($6::VARCHAR IS NULL OR client_ip = $6)
+            AND ($7::DATE IS NULL OR DATE(created_at) >= $7)
+            AND ($8::DATE IS NULL OR DATE(created_at) <= $8)
+        ORDER BY id DESC LIMIT $9 OFFSET $10
+        "#,
+        query.actor_id,
+        action,
+        entity,
+        query.entity_id,
+        query.request_id,
+        query.client_ip,
+        query.from_date,
+        query.to_date,
+        i64::from(per_page),
+        (i64::from(page) - 1).saturating_mul(i64::from(per_page))
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on listing audit entries: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    let total_pages = (total_results as f64 / per_page as f64).ceil() as i32;
+    Ok(SuccessMessages::FoundAuditLog { page, per_page, total_results: total_results as i32, total_pages, data })
+}
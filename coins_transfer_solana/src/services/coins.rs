@@ -11,15 +11,22 @@ use crate::cache::coins::{
 use crate::entities::coins::Coins;
 use crate::responses::error_msgs::Error;
 use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::{audit_value, try_record_audit};
 use crate::services::conversion_rates::get_rate_by_coin_id;
 use crate::services::rpc_client::RpcClient;
+use crate::services::solana_client::register_deposit_wallet;
 use crate::services::users::fetch_a_user;
-use crate::services::wallets::{get_user_address, get_wallet_by_address, get_wallet_by_user_and_coin, validate_user_address};
+use crate::services::wallets::{get_user_address, get_wallet_by_address, get_wallet_by_user_and_coin, is_solana_coin, validate_user_address};
 use crate::services::withdrawals::{get_user_withdrawal_details, process_withdrawal};
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
 use crate::structs::coins::{AddressGenerationRequest, AddressValidationRequest, CoinCreate, CoinInfo};
 use crate::structs::withdrawals::WithdrawalCreate;
 
-pub async fn create_coin(pool: &PgPool, redis_pool: &Pool, coin: CoinCreate) -> Result<SuccessMessages, Error> {
+pub async fn create_coin(pool: &PgPool, redis_pool: &Pool, coin: CoinCreate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let mut trx = pool.begin().await.map_err(|e| {
+        log!(Level::Error, "Database error on starting coin insert: {:?}", e);
+        Error::DatabaseIssue
+    })?;
     let result = query!(
         r#"
         INSERT INTO coins (coin_name, symbol, status, created_at, updated_at)
@@ -30,7 +37,7 @@ pub async fn create_coin(pool: &PgPool, redis_pool: &Pool, coin: CoinCreate) ->
         coin.symbol.clone(),
         coin.status
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *trx)
     .await;
 
     let new_coin = match result {
@@ -53,6 +60,12 @@ pub async fn create_coin(pool: &PgPool, redis_pool: &Pool, coin: CoinCreate) ->
             return Err(Error::DatabaseIssue);
         }
     };
+    try_record_audit(&mut *trx, audit, AuditAction::Create, AuditEntity::Coin, &new_coin.id.to_string(), None, audit_value(&new_coin)).await?;
+    trx.commit().await.map_err(|e| {
+        log!(Level::Error, "Database error on committing coin insert: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
     let _ = set_coin_cache_by_id(redis_pool, &new_coin).await;
     let _ = set_coin_cache_by_name(redis_pool, &new_coin).await;
     let _ = set_coin_cache_by_symbol(redis_pool, &new_coin).await;
@@ -169,7 +182,7 @@ pub async fn get_unconfirmed_user_balance(pool: &PgPool, redis_pool: &Pool, addr
     Ok(SuccessMessages::UnconfirmedBalance { address, unconfirmed_balance, confirmations })
 }
 
-pub async fn generate_address(pool: &PgPool, redis_pool: &Pool, address_request: AddressGenerationRequest) -> Result<SuccessMessages, Error> {
+pub async fn generate_address(pool: &PgPool, redis_pool: &Pool, address_request: AddressGenerationRequest, audit: &AuditContext) -> Result<SuccessMessages, Error> {
     let _user = fetch_a_user(pool, redis_pool, address_request.user_id).await?;
 
     let coin = fetch_a_coin_by_id(pool, redis_pool, address_request.coin_id).await?;
@@ -189,11 +202,15 @@ pub async fn generate_address(pool: &PgPool, redis_pool: &Pool, address_request:
         Err(e) => return Err(e),
     };
 
-    if wallet.address.is_some() {
-        return Ok(SuccessMessages::AddressGenerated { coin: coin.id, address: wallet.address.unwrap() });
+    if let Some(address) = wallet.address {
+        // Retries a registration that failed after the address was saved
+        if is_solana_coin(&coin) {
+            register_deposit_wallet(pool, redis_pool, wallet.user_id, wallet.coin_id).await?;
+        }
+        return Ok(SuccessMessages::AddressGenerated { coin: coin.id, address });
     }
 
-    get_user_address(pool, redis_pool, wallet, coin).await
+    get_user_address(pool, redis_pool, wallet, coin, audit).await
 }
 
 pub async fn validate_address(pool: &PgPool, redis_pool: &Pool, address_request: AddressValidationRequest) -> Result<SuccessMessages, Error> {
@@ -233,7 +250,7 @@ pub async fn transfer_amount(pool: &PgPool, redis_pool: &Pool, transfer: Withdra
     let network_fee = get_user_withdrawal_details(rpc_client, &hash, coin_amount.clone()).await?;
     let network_fee_usd = network_fee.clone() * BigDecimal::from_f64(rate).unwrap();
 
-    let withdrawal = match process_withdrawal(&mut trx, redis_pool, transfer.clone(), coin.clone(), transfer.usd_amount, hash.clone(), network_fee, network_fee_usd).await {
+    let withdrawal = match process_withdrawal(&mut trx, redis_pool, transfer.clone(), coin.clone(), transfer.usd_amount, hash.clone(), network_fee, network_fee_usd, None).await {
         Ok(withdrawal) => withdrawal,
         Err(e) => {
             let _ = trx.rollback().await.map_err(|e| {
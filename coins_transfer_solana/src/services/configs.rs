@@ -4,33 +4,86 @@ use log::Level;
 use sqlx::{PgPool, query, query_as};
 
 use crate::cache::configs::{delete_config_cache, drop_a_config_from_all_config_cache, get_all_configs_from_cache, get_config_by_name_from_cache, increment_all_config_cache, set_all_config_cache, set_config_cache};
+use crate::config::constants::{CREDENTIAL_CONFIG_MARKERS, SECRET_CONFIG_NAMES};
 use crate::entities::configs::Configs;
 use crate::responses::error_msgs::Error;
 use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::{audit_value, try_record_audit};
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
 use crate::structs::configs::{ConfigCreate, ConfigUpdate};
 use crate::utils::time::TimeHandler;
 
-pub async fn create_config(pool: &PgPool, redis_pool: &Pool, configs: ConfigCreate) -> Result<SuccessMessages, Error> {
+/// Whether `name` designates a secret, such as the HD mnemonic, which must
+/// live in the keystore or environment rather than the configs table.
+pub fn is_secret_config_name(name: &str) -> bool {
+    SECRET_CONFIG_NAMES.iter().any(|secret| name.trim().eq_ignore_ascii_case(secret))
+}
+
+/// Whether `name` holds a credential, i.e. contains `PASSWORD`, `API_KEY`,
+/// `SECRET` or `TOKEN`. Such configs are stored as secret even when the
+/// request doesn't flag them.
+pub fn is_credential_config_name(name: &str) -> bool {
+    let name = name.to_uppercase();
+    CREDENTIAL_CONFIG_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/// Whether a config written under `name` is stored as secret.
+fn config_is_secret(name: &str, requested: Option<bool>) -> bool {
+    requested.unwrap_or(false) || is_credential_config_name(name)
+}
+
+fn reject_secret_config_name(name: &str) -> Result<(), Error> {
+    if is_secret_config_name(name) {
+        log!(Level::Error, "Rejected secret config {}", name);
+        return Err(Error::SecretConfig);
+    }
+    Ok(())
+}
+
+/// Refuses to start while secrets are still stored in the configs table,
+/// they must be moved to the keystore (see `seal-mnemonic`) and deleted.
+pub async fn check_no_secret_configs(pool: &PgPool) -> Result<(), Error> {
+    let names = query!(r#"SELECT name FROM configs"#).fetch_all(pool).await.map_err(|e| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+    let secrets: Vec<String> = names.into_iter().map(|row| row.name).filter(|name| is_secret_config_name(name)).collect();
+    if !secrets.is_empty() {
+        log!(Level::Error, "Secrets found in the configs table: {}. Move them to the keystore and delete them", secrets.join(", "));
+        return Err(Error::SecretConfig);
+    }
+    Ok(())
+}
+
+pub async fn create_config(pool: &PgPool, redis_pool: &Pool, configs: ConfigCreate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    reject_secret_config_name(&configs.name)?;
+
+    let is_secret = config_is_secret(&configs.name, configs.is_secret);
     let time_handler = TimeHandler::new();
     let now = time_handler.get_current_time().naive_utc();
 
+    let mut trx = pool.begin().await.map_err(|e| {
+        log!(Level::Error, "Database error on starting config insert: {:?}", e);
+        Error::DatabaseIssue
+    })?;
     let result = query!(
         r#"
-        INSERT INTO configs (name, value, created_at, updated_at)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO configs (name, value, is_secret, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5)
         ON CONFLICT (name) DO NOTHING
         RETURNING id, created_at
         "#,
         configs.name.clone(),
         configs.value.clone(),
+        is_secret,
         now,
         now
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *trx)
     .await;
 
     let new_config = match result {
-        Ok(record) => Configs { id: record.id, name: configs.name.clone(), value: configs.value, created_at: record.created_at, updated_at: record.created_at },
+        Ok(record) => Configs { id: record.id, name: configs.name.clone(), value: configs.value, is_secret, created_at: record.created_at, updated_at: record.created_at },
         Err(sqlx::Error::RowNotFound) => {
             log!(Level::Error, "Duplicate Entry: Config with ID {} already exists", configs.name);
             return Err(Error::DuplicateEntry);
@@ -41,6 +94,12 @@ pub async fn create_config(pool: &PgPool, redis_pool: &Pool, configs: ConfigCrea
         }
     };
 
+    try_record_audit(&mut *trx, audit, AuditAction::Create, AuditEntity::Config, &new_config.name, None, audit_value(&new_config.redacted())).await?;
+    trx.commit().await.map_err(|e| {
+        log!(Level::Error, "Database error on committing config insert: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
     let _ = set_config_cache(redis_pool, &new_config).await;
     let _ = increment_all_config_cache(redis_pool, &new_config).await;
 
@@ -48,22 +107,25 @@ pub async fn create_config(pool: &PgPool, redis_pool: &Pool, configs: ConfigCrea
 }
 
 pub async fn get_config_by_name(pool: &PgPool, redis_pool: &Pool, name: String) -> Result<SuccessMessages, Error> {
+    reject_secret_config_name(&name)?;
+
+    let configs = fetch_config(pool, redis_pool, name).await?;
+
+    Ok(SuccessMessages::FoundConfig { config_id: configs.id, configs_list: Some(vec![configs.redacted()]) })
+}
+
+/// Looks `name` up in the cache, then the environment, then the database.
+/// The value is returned verbatim, callers exposing it must redact it.
+async fn fetch_config(pool: &PgPool, redis_pool: &Pool, name: String) -> Result<Configs, Error> {
     if let Some(configs) = get_config_by_name_from_cache(redis_pool, &name).await {
-        return Ok(SuccessMessages::FoundConfig {
-            config_id: 0, // use 0 to indicate a list, as there is no specific ID
-            configs_list: Some(vec![configs]),
-        });
+        return Ok(configs);
     }
 
     let configs = get_from_env(&name).await;
     if configs.is_some() {
         let config = configs.unwrap();
         if !config.is_empty() {
-            let configs = Configs { id: 0, name, value: config, created_at: TimeHandler::new().get_current_time().naive_utc(), updated_at: TimeHandler::new().get_current_time().naive_utc() };
-            return Ok(SuccessMessages::FoundConfig {
-                config_id: 0, // use 0 to indicate a list, as there is no specific ID
-                configs_list: Some(vec![configs]),
-            });
+            return Ok(env_config(name, config));
         }
     }
 
@@ -72,35 +134,65 @@ pub async fn get_config_by_name(pool: &PgPool, redis_pool: &Pool, name: String)
     let _ = set_config_cache(redis_pool, &configs).await;
     let _ = increment_all_config_cache(redis_pool, &configs).await;
 
-    Ok(SuccessMessages::FoundConfig { config_id: configs.id, configs_list: Some(vec![configs]) })
+    Ok(configs)
+}
+
+/// A config read from the environment. Environment values are never exposed
+/// through the API, so it is flagged secret.
+fn env_config(name: String, value: String) -> Configs {
+    let now = TimeHandler::new().get_current_time().naive_utc();
+    Configs { id: 0, name, value, is_secret: true, created_at: now, updated_at: now }
 }
 
 #[allow(dead_code, unused)]
 pub async fn get_a_config(pool: &PgPool, redis_pool: &Pool, name: String) -> Result<String, Error> {
-    let conf = match get_config_by_name(pool, redis_pool, name.clone()).await {
-        Ok(SuccessMessages::FoundConfig { config_id, configs_list }) => {
-            let _ = config_id;
-            if let Some(config) = configs_list {
-                config[0].value.clone()
-            } else {
-                return Err(Error::EnvVarMissing(name));
-            }
-        }
-        Ok(_) => return Err(Error::EnvVarMissing(name)),
+    match fetch_config(pool, redis_pool, name.clone()).await {
+        Ok(config) => Ok(config.value),
         Err(_) => {
             log!(Level::Error, "{} is not set in .env file", name);
-            return Err(Error::EnvVarMissing(name));
+            Err(Error::EnvVarMissing(name))
         }
-    };
+    }
+}
+
+/// Like [`get_a_config`], but `None` when `name` is set nowhere, so callers
+/// can fall back to a default without hiding database or cache failures.
+pub async fn get_optional_config(pool: &PgPool, redis_pool: &Pool, name: &str) -> Result<Option<String>, Error> {
+    match fetch_config(pool, redis_pool, name.to_string()).await {
+        Ok(config) => Ok(Some(config.value)),
+        Err(Error::NotFound(_)) => Ok(None),
+        Err(e) => {
+            log!(Level::Error, "Failed to read config {}: {}", name, e);
+            Err(e)
+        }
+    }
+}
 
-    Ok(conf)
+/// Reads the `true` or `false` config `name`, `None` when it is set nowhere.
+/// Any other value is `InvalidConfiguration`.
+pub async fn get_bool_config(pool: &PgPool, redis_pool: &Pool, name: &str) -> Result<Option<bool>, Error> {
+    match get_optional_config(pool, redis_pool, name).await? {
+        Some(value) => parse_bool_config(name, &value).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn parse_bool_config(name: &str, value: &str) -> Result<bool, Error> {
+    match value.trim().to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => {
+            log!(Level::Error, "Invalid {} {}, expected true or false", name, value);
+            Err(Error::InvalidConfiguration)
+        }
+    }
 }
 
 pub async fn get_a_config_from_db(pool: &PgPool, name: &String) -> Result<Configs, Error> {
     let result = query_as!(
         Configs,
         r#"
-        SELECT id, name, value, created_at, updated_at FROM configs WHERE name = $1
+        SELECT id, name, value, is_secret, created_at, updated_at FROM configs WHERE name = $1
         "#,
         name.clone()
     )
@@ -131,15 +223,17 @@ pub async fn get_all_configs(pool: &PgPool, redis_pool: &Pool) -> Result<Success
     let result = query_as!(
         Configs,
         r#"
-        SELECT id, name, value, created_at, updated_at FROM configs
+        SELECT id, name, value, is_secret, created_at, updated_at FROM configs
         "#
     )
     .fetch_all(pool)
     .await;
 
     match result {
-        Ok(configs) => {
+        Ok(mut configs) => {
+            configs.retain(|config| !is_secret_config_name(&config.name));
             let _ = set_all_config_cache(redis_pool, &configs).await;
+            let configs = configs.iter().map(Configs::redacted).collect();
             Ok(SuccessMessages::FoundConfig { config_id: 0, configs_list: Some(configs) })
         }
         Err(e) => {
@@ -149,98 +243,122 @@ pub async fn get_all_configs(pool: &PgPool, redis_pool: &Pool) -> Result<Success
     }
 }
 
-pub async fn update_config(pool: &PgPool, redis_pool: &Pool, name: String, configs_update: ConfigUpdate) -> Result<SuccessMessages, Error> {
+pub async fn update_config(pool: &PgPool, redis_pool: &Pool, name: String, configs_update: ConfigUpdate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    reject_secret_config_name(&name)?;
+    if let Some(new_name) = &configs_update.name {
+        reject_secret_config_name(new_name)?;
+    }
     let configs = get_a_config_from_db(pool, &name).await?;
+    // Renaming a config to a credential name makes it secret as well
+    let is_secret = config_is_secret(&name, configs_update.is_secret) || configs_update.name.as_deref().is_some_and(is_credential_config_name);
 
     let time_handler = TimeHandler::new();
     let now = time_handler.get_current_time().naive_utc();
 
-    let update_result = query!(
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let record = query!(
         r#"
         UPDATE configs
         SET name = COALESCE($1, name),
             value = COALESCE($2, value),
+            is_secret = is_secret OR $5,
             updated_at = $3
         WHERE name = $4
-        RETURNING id, updated_at
+        RETURNING id, is_secret, updated_at
         "#,
         configs_update.name.clone(),
         configs_update.value.clone(),
         now,
-        name.clone()
+        name.clone(),
+        is_secret
     )
-    .fetch_one(pool)
-    .await;
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(db_error)?;
 
-    match update_result {
-        Ok(record) => {
-            let updated_configs = Configs { id: record.id, name, created_at: configs.created_at, updated_at: record.updated_at, value: configs_update.value.unwrap_or(configs.value.clone()) };
-            let _ = drop_a_config_from_all_config_cache(redis_pool, &configs).await;
-            let _ = set_config_cache(redis_pool, &updated_configs).await;
-            let _ = increment_all_config_cache(redis_pool, &updated_configs).await;
+    let updated_configs = Configs { id: record.id, name: configs_update.name.unwrap_or(name), is_secret: record.is_secret, created_at: configs.created_at, updated_at: record.updated_at, value: configs_update.value.unwrap_or(configs.value.clone()) };
+    let (before, after) = redacted_update(&configs, &updated_configs);
+    try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::Config, &configs.name, audit_value(&before), audit_value(&after)).await?;
+    trx.commit().await.map_err(db_error)?;
 
-            Ok(SuccessMessages::UpdatedConfig { config_id: configs.id, configs_list: Some(vec![updated_configs]) })
-        }
-        Err(e) => {
-            log!(Level::Error, "Database error: {:?}", e);
-            Err(Error::DatabaseIssue)
-        }
-    }
+    let _ = drop_a_config_from_all_config_cache(redis_pool, &configs).await;
+    let _ = set_config_cache(redis_pool, &updated_configs).await;
+    let _ = increment_all_config_cache(redis_pool, &updated_configs).await;
+
+    Ok(SuccessMessages::UpdatedConfig { config_id: configs.id, configs_list: Some(vec![updated_configs.redacted()]) })
+}
+
+/// Copies of a config before and after an update that are safe to audit.
+/// Both are masked once the config is secret, so a value can not leak by
+/// flagging it.
+fn redacted_update(before: &Configs, after: &Configs) -> (Configs, Configs) {
+    (Configs { is_secret: after.is_secret, ..before.clone() }.redacted(), after.redacted())
 }
 
-pub async fn delete_config(pool: &PgPool, redis_pool: &Pool, name: String) -> Result<SuccessMessages, Error> {
+pub async fn delete_config(pool: &PgPool, redis_pool: &Pool, name: String, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
     let delete_result = query_as!(
         Configs,
         r#"
         DELETE FROM configs WHERE name = $1
-        RETURNING id, name, value, created_at, updated_at
+        RETURNING id, name, value, is_secret, created_at, updated_at
         "#,
         name.clone()
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *trx)
     .await;
 
-    match delete_result {
-        Ok(config) => {
-            let _ = delete_config_cache(redis_pool, &name).await;
-            let _ = drop_a_config_from_all_config_cache(redis_pool, &config).await;
-            Ok(SuccessMessages::DeletedConfig { config: name })
-        }
+    let config = match delete_result {
+        Ok(config) => config,
         Err(sqlx::Error::RowNotFound) => {
             log!(Level::Error, "Config with name {} not found", name);
-            Err(Error::NotFound(format!("Config with name {name} not found")))
+            return Err(Error::NotFound(format!("Config with name {name} not found")));
         }
-        Err(e) => {
-            log!(Level::Error, "Database error: {:?}", e);
-            Err(Error::DatabaseIssue)
-        }
-    }
+        Err(e) => return Err(db_error(e)),
+    };
+    try_record_audit(&mut *trx, audit, AuditAction::Delete, AuditEntity::Config, &name, audit_value(&config.redacted()), None).await?;
+    trx.commit().await.map_err(db_error)?;
+
+    let _ = delete_config_cache(redis_pool, &name).await;
+    let _ = drop_a_config_from_all_config_cache(redis_pool, &config).await;
+    Ok(SuccessMessages::DeletedConfig { config: name })
 }
 
-pub async fn delete_all_configs(pool: &PgPool, redis_pool: &Pool) -> Result<SuccessMessages, Error> {
-    let delete_result = query_as!(
+pub async fn delete_all_configs(pool: &PgPool, redis_pool: &Pool, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let configs = query_as!(
         Configs,
         r#"
         DELETE FROM configs
-        RETURNING id, name, value, created_at, updated_at
+        RETURNING id, name, value, is_secret, created_at, updated_at
         "#
     )
-    .fetch_all(pool)
-    .await;
+    .fetch_all(&mut *trx)
+    .await
+    .map_err(db_error)?;
 
-    match delete_result {
-        Ok(configs) => {
-            for config in configs {
-                let _ = delete_config_cache(redis_pool, &config.name).await;
-                let _ = drop_a_config_from_all_config_cache(redis_pool, &config).await;
-            }
-            Ok(SuccessMessages::DeletedAllConfigs)
-        }
-        Err(e) => {
-            log!(Level::Error, "Database error: {:?}", e);
-            Err(Error::DatabaseIssue)
-        }
+    for config in &configs {
+        try_record_audit(&mut *trx, audit, AuditAction::Delete, AuditEntity::Config, &config.name, audit_value(&config.redacted()), None).await?;
     }
+    trx.commit().await.map_err(db_error)?;
+
+    for config in configs {
+        let _ = delete_config_cache(redis_pool, &config.name).await;
+        let _ = drop_a_config_from_all_config_cache(redis_pool, &config).await;
+    }
+    Ok(SuccessMessages::DeletedAllConfigs)
 }
 
 pub async fn get_from_env(config_name: &str) -> Option<String> {
@@ -276,3 +394,61 @@ pub async fn parse_float(config_val: String) -> Result<f32, Error> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::constants::SECRET_CONFIG_MASK;
+
+    use super::*;
+
+    fn config(value: &str, is_secret: bool) -> Configs {
+        let now = TimeHandler::new().get_current_time().naive_utc();
+        Configs { id: 1, name: "SIGNER_REMOTE_TOKEN".to_string(), value: value.to_string(), is_secret, created_at: now, updated_at: now }
+    }
+
+    #[test]
+    fn redaction_masks_secret_values_only() {
+        assert_eq!(config("token", true).redacted().value, SECRET_CONFIG_MASK);
+        assert_eq!(config("token", false).redacted().value, "token");
+        assert_eq!(env_config("COIN_MARKET_CAP_API_KEY".to_string(), "key".to_string()).redacted().value, SECRET_CONFIG_MASK);
+    }
+
+    #[test]
+    fn updates_of_secret_configs_never_return_a_value() {
+        // Flagging a public config secret masks its old value as well
+        let (before, after) = redacted_update(&config("old", false), &config("new", true));
+        assert_eq!((before.value.as_str(), after.value.as_str()), (SECRET_CONFIG_MASK, SECRET_CONFIG_MASK));
+
+        let (before, after) = redacted_update(&config("old", false), &config("new", false));
+        assert_eq!((before.value.as_str(), after.value.as_str()), ("old", "new"));
+    }
+
+    #[test]
+    fn bool_configs_are_only_true_or_false() {
+        assert!(parse_bool_config("REGISTRY_MODE_ENABLED", " TRUE ").unwrap());
+        assert!(!parse_bool_config("REGISTRY_MODE_ENABLED", "false").unwrap());
+        for value in ["1", "yes", "on", "ture", ""] {
+            assert!(matches!(parse_bool_config("REGISTRY_MODE_ENABLED", value), Err(Error::InvalidConfiguration)));
+        }
+    }
+
+    #[test]
+    fn credential_configs_are_never_returned() {
+        let create = ConfigCreate { name: "FOO_API_KEY".to_string(), value: "key".to_string(), is_secret: None };
+        let stored = Configs { name: create.name.clone(), ..config(&create.value, config_is_secret(&create.name, create.is_secret)) };
+        assert_eq!(stored.redacted().value, SECRET_CONFIG_MASK);
+
+        assert!(config_is_secret("litecoin_rpc_password", Some(false)));
+        assert!(config_is_secret("SIGNER_REMOTE_TOKEN", None));
+        assert!(config_is_secret("WITHDRAWAL_MAXIMUM", Some(true)));
+        assert!(!config_is_secret("WITHDRAWAL_MAXIMUM", None));
+    }
+
+    #[test]
+    fn secret_config_names_match_exactly() {
+        assert!(is_secret_config_name("MNEMONIC_PHRASE"));
+        assert!(is_secret_config_name(" wallet_master_keys "));
+        assert!(!is_secret_config_name("SEED_FUNDING_AMOUNT"));
+        assert!(!is_secret_config_name("MNEMONIC_PHRASE_HINT"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crypsol_logger::log;
+use ipnet::IpNet;
+use log::Level;
+use sqlx::postgres::PgListener;
+use sqlx::{PgPool, query};
+
+use crate::config::constants::{ALLOWED_IPS_CONFIG, CONFIGS_CHANGED_CHANNEL, IP_ALLOWLIST_RETRY_SECS, TRUSTED_PROXIES_CONFIG};
+use crate::responses::error_msgs::Error;
+
+/// Networks allowed to reach each route scope, built from the `ALLOWED_IPS`,
+/// `ALLOWED_IPS_<SCOPE>` and `TRUSTED_PROXIES` configs. An empty list blocks
+/// every address.
+#[derive(Clone, Debug, Default)]
+pub struct IpAllowlist {
+    pub default: Vec<IpNet>,
+    pub scopes: HashMap<String, Vec<IpNet>>,
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl IpAllowlist {
+    /// Builds the allowlist from `(name, value)` config pairs, ignoring
+    /// unrelated names. Values are comma or whitespace separated addresses
+    /// or CIDR blocks; invalid entries are logged and skipped.
+    pub fn from_configs<'a>(configs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut allowlist = IpAllowlist::default();
+        for (name, value) in configs {
+            if name == ALLOWED_IPS_CONFIG {
+                allowlist.default = parse_networks(name, value);
+            } else if name == TRUSTED_PROXIES_CONFIG {
+                allowlist.trusted_proxies = parse_networks(name, value);
+            } else if let Some(scope) = name.strip_prefix(ALLOWED_IPS_CONFIG).and_then(|rest| rest.strip_prefix('_')) {
+                allowlist.scopes.insert(scope.to_lowercase(), parse_networks(name, value));
+            }
+        }
+        allowlist
+    }
+
+    /// Whether `ip` may call the routes of `scope`. A scope without its own
+    /// list uses the default one.
+    pub fn allows(&self, scope: &str, ip: IpAddr) -> bool {
+        let networks = self.scopes.get(scope).unwrap_or(&self.default);
+        networks.iter().any(|network| network.contains(&ip.to_canonical()))
+    }
+
+    /// Address of the client behind `peer`. `X-Forwarded-For` is only used
+    /// when `peer` is a trusted proxy, in which case the right-most entry not
+    /// belonging to a trusted proxy is the client.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.is_trusted_proxy(peer) {
+            return peer;
+        }
+        let Some(forwarded_for) = forwarded_for else {
+            return peer;
+        };
+
+        let mut client = peer;
+        for entry in forwarded_for.rsplit(',') {
+            let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = ip.to_canonical();
+            if !self.is_trusted_proxy(client) {
+                break;
+            }
+        }
+        client
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|network| network.contains(&ip))
+    }
+}
+
+fn parse_networks(name: &str, value: &str) -> Vec<IpNet> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.parse::<IpNet>().or_else(|_| entry.parse::<IpAddr>().map(IpNet::from)) {
+            Ok(network) => Some(network),
+            Err(_) => {
+                log!(Level::Warn, "Ignoring invalid address {} in {}", entry, name);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Loads the allowlist from the configs table. Entries missing from the
+/// table fall back to the environment variables of the same name.
+pub async fn load_ip_allowlist(pool: &PgPool) -> Result<IpAllowlist, Error> {
+    let rows = query!(
+        r#"
+        SELECT name, value FROM configs WHERE name = $1 OR name LIKE $2 OR name = $3
+        "#,
+        ALLOWED_IPS_CONFIG,
+        format!("{ALLOWED_IPS_CONFIG}\\_%"),
+        TRUSTED_PROXIES_CONFIG
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on loading the IP allowlist: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    let mut configs: HashMap<String, String> = env::vars()
+        .filter(|(name, _)| name == ALLOWED_IPS_CONFIG || name == TRUSTED_PROXIES_CONFIG || name.starts_with(&format!("{ALLOWED_IPS_CONFIG}_")))
+        .collect();
+    configs.extend(rows.into_iter().map(|row| (row.name, row.value)));
+
+    Ok(IpAllowlist::from_configs(configs.iter().map(|(name, value)| (name.as_str(), value.as_str()))))
+}
+
+/// Keeps `allowlist` in sync with the configs table by listening to the
+/// notifications of its trigger. Runs until the process exits.
+pub async fn watch_ip_allowlist(pool: PgPool, allowlist: Arc<RwLock<IpAllowlist>>) {
+    loop {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log!(Level::Error, "Error connecting the configs listener: {:?}", e);
+                tokio::time::sleep(Duration::from_secs(IP_ALLOWLIST_RETRY_SECS)).await;
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(CONFIGS_CHANGED_CHANNEL).await {
+            log!(Level::Error, "Error listening on {}: {:?}", CONFIGS_CHANGED_CHANNEL, e);
+            tokio::time::sleep(Duration::from_secs(IP_ALLOWLIST_RETRY_SECS)).await;
+            continue;
+        }
+
+        // Reload once per (re)connection, changes made while disconnected were not notified.
+        reload_ip_allowlist(&pool, &allowlist).await;
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(_)) => reload_ip_allowlist(&pool, &allowlist).await,
+                Ok(None) => {
+                    log!(Level::Warn, "Configs listener lost its connection, reloading the IP allowlist");
+                    reload_ip_allowlist(&pool, &allowlist).await;
+                }
+                Err(e) => {
+                    log!(Level::Error, "Error receiving configs notifications: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(IP_ALLOWLIST_RETRY_SECS)).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn reload_ip_allowlist(pool: &PgPool, allowlist: &RwLock<IpAllowlist>) {
+    match load_ip_allowlist(pool).await {
+        Ok(loaded) => {
+            log!(Level::Info, "IP allowlist reloaded: {} default networks, {} scope overrides", loaded.default.len(), loaded.scopes.len());
+            match allowlist.write() {
+                Ok(mut current) => *current = loaded,
+                Err(poisoned) => *poisoned.into_inner() = loaded,
+            }
+        }
+        Err(_) => log!(Level::Error, "Keeping the previous IP allowlist"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn allowlist() -> IpAllowlist {
+        IpAllowlist::from_configs([
+            ("ALLOWED_IPS", "10.0.0.0/8, 192.168.1.5"),
+            ("ALLOWED_IPS_ADMINS", "172.16.0.1"),
+            ("TRUSTED_PROXIES", "127.0.0.1 10.1.0.0/16"),
+            ("UNRELATED", "0.0.0.0/0"),
+        ])
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let allowlist = allowlist();
+        assert_eq!(allowlist.client_ip(ip("203.0.113.7"), Some("10.0.0.1")), ip("203.0.113.7"));
+        assert_eq!(allowlist.client_ip(ip("127.0.0.1"), None), ip("127.0.0.1"));
+    }
+
+    #[test]
+    fn skips_a_chain_of_trusted_proxies() {
+        let allowlist = allowlist();
+        assert_eq!(allowlist.client_ip(ip("127.0.0.1"), Some("203.0.113.7, 10.1.0.2, 10.1.0.3")), ip("203.0.113.7"));
+        // Entries left of the first untrusted address are client controlled.
+        assert_eq!(allowlist.client_ip(ip("127.0.0.1"), Some("10.0.0.1, 203.0.113.7, 10.1.0.2")), ip("203.0.113.7"));
+        // A malformed entry stops the walk at the last trusted hop.
+        assert_eq!(allowlist.client_ip(ip("127.0.0.1"), Some("203.0.113.7, garbage, 10.1.0.2")), ip("10.1.0.2"));
+    }
+
+    #[test]
+    fn skips_invalid_entries() {
+        let allowlist = IpAllowlist::from_configs([("ALLOWED_IPS", "10.0.0.1,not-an-ip 10.0.0.0/33;192.168.0.1")]);
+        assert_eq!(allowlist.default, vec!["10.0.0.1/32".parse::<IpNet>().unwrap()]);
+        assert!(!allowlist.allows("coins", ip("192.168.0.1")));
+    }
+
+    #[test]
+    fn matches_ipv4_mapped_ipv6_peers() {
+        let allowlist = allowlist();
+        assert!(allowlist.allows("coins", ip("::ffff:10.2.3.4")));
+        assert!(!allowlist.allows("coins", ip("::ffff:203.0.113.7")));
+        assert_eq!(allowlist.client_ip(ip("::ffff:127.0.0.1"), Some("::ffff:203.0.113.7")), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn scope_overrides_replace_the_default_list() {
+        let allowlist = allowlist();
+        assert!(allowlist.allows("admins", ip("172.16.0.1")));
+        assert!(!allowlist.allows("admins", ip("10.0.0.1")));
+        assert!(allowlist.allows("coins", ip("10.0.0.1")));
+        assert!(!allowlist.allows("coins", ip("172.16.0.1")));
+    }
+}
@@ -1,10 +1,15 @@
+pub mod admins;
+pub mod audit;
 pub mod coins;
 pub mod configs;
 pub mod conversion_rates;
 pub mod deposits;
+pub mod ip_allowlist;
 pub mod notifications;
 pub mod rpc_client;
+pub mod signers;
 pub mod users;
 pub mod wallets;
+pub mod withdrawal_addresses;
 pub mod withdrawals;
 pub mod solana_client;
\ No newline at end of file
@@ -0,0 +1,346 @@
+use std::env;
+use std::future::{Future, ready};
+use std::net::TcpListener;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::dev::Server;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+use crypsol_logger::log;
+use deadpool_redis::Pool;
+use log::Level;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use sqlx::PgPool;
+
+use crate::config::constants::{SIGNER_BACKEND_CONFIG, SIGNER_KEYSTORE_DIR_CONFIG, SIGNER_REMOTE_TIMEOUT_SECS, SIGNER_REMOTE_TOKEN_CONFIG, SIGNER_REMOTE_URL_CONFIG};
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs_impl::create_error_response;
+use crate::services::configs::{get_a_config, get_optional_config};
+use crate::services::solana_client::get_verified_wallet_keypair;
+use crate::utils::keystore::open_keypair;
+
+pub const SIGN_PATH: &str = "/sign";
+
+pub type SignerFuture<'a> = Pin<Box<dyn Future<Output = Result<Signature, Error>> + Send + 'a>>;
+
+/// Signs the transactions of one wallet, wherever its key lives.
+pub trait TransactionSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    /// Signs the serialized transaction `message`.
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a>;
+}
+
+/// A key held in memory, derived from the HD seed or opened from a keystore.
+pub struct LocalSigner(pub Keypair);
+
+impl TransactionSigner for LocalSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.0.pubkey()
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a> {
+        Box::pin(ready(Ok(self.0.sign_message(message))))
+    }
+}
+
+/// Body of a `POST /sign` request to a signing service. `message` is hex.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub pubkey: String,
+    pub wallet_index: Option<i64>,
+    pub message: String,
+}
+
+/// Body of a successful `POST /sign` response. `signature` is base58.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signature: String,
+}
+
+/// Signs through a signing service speaking the `POST /sign` protocol, so the
+/// key never reaches this process. Returned signatures are verified against
+/// `pubkey` before they are used.
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+    pubkey: Pubkey,
+    wallet_index: Option<i64>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str, token: &str, pubkey: Pubkey, wallet_index: Option<i64>) -> Result<Self, Error> {
+        let client = reqwest::Client::builder().timeout(Duration::from_secs(SIGNER_REMOTE_TIMEOUT_SECS)).build().map_err(|e| {
+            log!(Level::Error, "Failed to build the signing service client: {}", e);
+            Error::TechnicalIssue
+        })?;
+        Ok(RemoteSigner { client, url: format!("{}{}", url.trim_end_matches('/'), SIGN_PATH), token: token.to_string(), pubkey, wallet_index })
+    }
+
+    async fn request_signature(&self, message: &[u8]) -> Result<Signature, Error> {
+        let request = SignRequest { pubkey: self.pubkey.to_string(), wallet_index: self.wallet_index, message: hex::encode(message) };
+        let response = self.client.post(&self.url).bearer_auth(&self.token).json(&request).send().await.map_err(|e| {
+            log!(Level::Error, "Failed to reach the signing service {}: {}", self.url, e);
+            Error::SignerIssue
+        })?;
+        if !response.status().is_success() {
+            log!(Level::Error, "Signing service refused to sign for {}: {}", self.pubkey, response.status());
+            return Err(Error::SignerIssue);
+        }
+        let body: SignResponse = response.json().await.map_err(|e| {
+            log!(Level::Error, "Signing service returned a malformed response: {}", e);
+            Error::SignerIssue
+        })?;
+
+        let signature = Signature::from_str(&body.signature).map_err(|_| {
+            log!(Level::Error, "Signing service returned an invalid signature");
+            Error::SignerIssue
+        })?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            log!(Level::Error, "Signing service returned a signature that does not verify for {}", self.pubkey);
+            return Err(Error::SignerIssue);
+        }
+        Ok(signature)
+    }
+}
+
+impl TransactionSigner for RemoteSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a> {
+        Box::pin(self.request_signature(message))
+    }
+}
+
+/// Builds `instructions` into a transaction paid and signed by `signer`.
+pub async fn sign_transaction(instructions: &[Instruction], signer: &dyn TransactionSigner, blockhash: Hash) -> Result<Transaction, Error> {
+    sign_cosigned_transaction(instructions, signer, &[], blockhash).await
+}
+
+/// Like [`sign_transaction`], with `cosigners` signing next to the payer,
+/// such as a new deposit wallet signing its own registration.
+pub async fn sign_cosigned_transaction(instructions: &[Instruction], signer: &dyn TransactionSigner, cosigners: &[&Keypair], blockhash: Hash) -> Result<Transaction, Error> {
+    let mut tx = Transaction::new_with_payer(instructions, Some(&signer.pubkey()));
+    tx.message.recent_blockhash = blockhash;
+    tx.try_partial_sign(cosigners, blockhash).map_err(|e| {
+        log!(Level::Error, "Failed to co-sign the transaction: {}", e);
+        Error::TechnicalIssue
+    })?;
+    if tx.signatures.iter().skip(1).any(|signature| *signature == Signature::default()) {
+        log!(Level::Error, "Transaction needs {} signatures, only the payer and {} co-signers can sign", tx.message.header.num_required_signatures, cosigners.len());
+        return Err(Error::TechnicalIssue);
+    }
+    tx.signatures[0] = signer.sign_message(&tx.message_data()).await?;
+    Ok(tx)
+}
+
+/// Signer of the wallet at `wallet_index` with `address`, picked by the
+/// `SIGNER_BACKEND` config: `local` derives the key from the HD seed,
+/// `keystore` opens it from `SIGNER_KEYSTORE_DIR`, and `remote` asks the
+/// signing service at `SIGNER_REMOTE_URL`. Without the config the backend
+/// is `local`.
+pub async fn wallet_signer(pool: &PgPool, redis_pool: &Pool, wallet_index: Option<i64>, address: Option<&str>) -> Result<Box<dyn TransactionSigner>, Error> {
+    let backend = get_optional_config(pool, redis_pool, SIGNER_BACKEND_CONFIG).await?.unwrap_or_else(|| "local".to_string());
+    match backend.trim().to_lowercase().as_str() {
+        "local" => Ok(Box::new(LocalSigner(get_verified_wallet_keypair(wallet_index, address)?))),
+        "keystore" => {
+            let pubkey = wallet_pubkey(address)?;
+            let dir = get_a_config(pool, redis_pool, SIGNER_KEYSTORE_DIR_CONFIG.to_string()).await?;
+            Ok(Box::new(LocalSigner(open_keypair(&dir, &pubkey)?)))
+        }
+        "remote" => {
+            let pubkey = wallet_pubkey(address)?;
+            let url = get_a_config(pool, redis_pool, SIGNER_REMOTE_URL_CONFIG.to_string()).await?;
+            let token = get_a_config(pool, redis_pool, SIGNER_REMOTE_TOKEN_CONFIG.to_string()).await?;
+            Ok(Box::new(RemoteSigner::new(&url, &token, pubkey, wallet_index)?))
+        }
+        other => {
+            log!(Level::Error, "Unknown {} {}, expected local, keystore or remote", SIGNER_BACKEND_CONFIG, other);
+            Err(Error::InvalidConfiguration)
+        }
+    }
+}
+
+/// Parses the stored `address` of a wallet, which can be changed through the
+/// wallets API, before it names a keystore file or a remote key.
+fn wallet_pubkey(address: Option<&str>) -> Result<Pubkey, Error> {
+    let address = address.ok_or_else(|| Error::NotFound("Wallet keys not found".to_string()))?;
+    Pubkey::from_str(address).map_err(|e| {
+        log!(Level::Error, "Wallet address {:?} is not a valid public key: {}", address, e);
+        Error::InvalidAddress
+    })
+}
+
+/// Looks up the keypair a stand-in signer signs a request with.
+pub type StandInKeys = Box<dyn Fn(&SignRequest) -> Result<Keypair, Error> + Send + Sync>;
+
+/// Minimal signing service serving the `POST /sign` protocol, for local runs
+/// and tests of the `remote` backend. Only signs transaction messages whose
+/// fee payer is the requested `pubkey`, so its key never signs for a
+/// transaction paid by somebody else.
+pub struct SignerStandIn {
+    pub token: String,
+    pub keys: StandInKeys,
+}
+
+async fn sign(stand_in: web::Data<SignerStandIn>, req: HttpRequest, body: web::Json<SignRequest>) -> HttpResponse {
+    let token = req.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer ")).unwrap_or_default();
+    let error = |error: Error| {
+        let (message_key, message, data, status_code) = error.to_response();
+        create_error_response(message_key, message, data, status_code)
+    };
+    if Sha256::digest(token) != Sha256::digest(&stand_in.token) {
+        log!(Level::Warn, "Rejected a signing request with an invalid token");
+        return error(Error::Unauthorized);
+    }
+    let Ok(message) = hex::decode(&body.message) else {
+        return error(Error::MissingField("message".to_string()));
+    };
+    let Ok(pubkey) = Pubkey::from_str(&body.pubkey) else {
+        return error(Error::InvalidAddress);
+    };
+    let Ok(transaction_message) = bincode::deserialize::<Message>(&message) else {
+        return error(Error::MissingField("message".to_string()));
+    };
+    if transaction_message.header.num_required_signatures == 0 || transaction_message.account_keys.first() != Some(&pubkey) {
+        log!(Level::Warn, "Refused to sign a message that is not paid by {}", pubkey);
+        return error(Error::Forbidden);
+    }
+    match (stand_in.keys)(&body) {
+        Ok(keypair) if keypair.pubkey() != pubkey => {
+            log!(Level::Warn, "Refused to sign for {} with the key of {}", pubkey, keypair.pubkey());
+            error(Error::Forbidden)
+        }
+        Ok(keypair) => {
+            log!(Level::Info, "Signed a transaction for {}", keypair.pubkey());
+            HttpResponse::Ok().json(SignResponse { signature: keypair.sign_message(&message).to_string() })
+        }
+        Err(e) => error(e),
+    }
+}
+
+/// Serves `stand_in` on `listener` until the returned server is stopped.
+pub fn run_signer_stand_in(listener: TcpListener, stand_in: SignerStandIn) -> std::io::Result<Server> {
+    let stand_in = web::Data::new(stand_in);
+    Ok(HttpServer::new(move || App::new().app_data(stand_in.clone()).route(SIGN_PATH, web::post().to(sign))).listen(listener)?.run())
+}
+
+/// Serves the wallets of the unlocked HD seed on `address`, authenticating
+/// requests with `SIGNER_REMOTE_TOKEN`. Keys are derived per request from
+/// `wallet_index` and must match `pubkey`.
+pub async fn serve_signer(address: &str) -> Result<(), Error> {
+    let token = env::var(SIGNER_REMOTE_TOKEN_CONFIG).map_err(|_| {
+        log!(Level::Error, "{} is not set in .env file", SIGNER_REMOTE_TOKEN_CONFIG);
+        Error::EnvVarMissing(SIGNER_REMOTE_TOKEN_CONFIG.to_string())
+    })?;
+    let keys: StandInKeys = Box::new(|request| get_verified_wallet_keypair(request.wallet_index, Some(&request.pubkey)));
+    let listener = TcpListener::bind(address).map_err(|e| {
+        log!(Level::Error, "Failed to bind the signer to {}: {}", address, e);
+        Error::TechnicalIssue
+    })?;
+    log!(Level::Info, "Signer listening on {}", address);
+    run_signer_stand_in(listener, SignerStandIn { token, keys })
+        .map_err(|_| Error::TechnicalIssue)?
+        .await
+        .map_err(|e| {
+            log!(Level::Error, "Signer stopped: {}", e);
+            Error::TechnicalIssue
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_system_interface::instruction::transfer;
+
+    use super::*;
+
+    fn start_stand_in(keys: StandInKeys) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        actix_web::rt::spawn(run_signer_stand_in(listener, SignerStandIn { token: "token".to_string(), keys }).unwrap());
+        url
+    }
+
+    #[test]
+    fn rejects_a_wallet_address_that_is_not_a_public_key() {
+        let pubkey = Pubkey::new_unique();
+        assert_eq!(wallet_pubkey(Some(&pubkey.to_string())).unwrap(), pubkey);
+        assert!(matches!(wallet_pubkey(Some("../../etc/passwd")), Err(Error::InvalidAddress)));
+        assert!(matches!(wallet_pubkey(None), Err(Error::NotFound(_))));
+    }
+
+    #[actix_web::test]
+    async fn remote_signer_signs_through_stand_in() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let url = start_stand_in(Box::new(move |_| Ok(keypair.insecure_clone())));
+
+        let signer = RemoteSigner::new(&url, "token", pubkey, Some(1)).unwrap();
+        let tx = sign_transaction(&[transfer(&pubkey, &Pubkey::new_unique(), 1)], &signer, Hash::new_unique()).await.unwrap();
+        assert!(tx.verify().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn stand_in_only_signs_messages_of_the_fee_payer() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let url = start_stand_in(Box::new(move |_| Ok(keypair.insecure_clone())));
+        let signer = RemoteSigner::new(&url, "token", pubkey, None).unwrap();
+
+        let other = Pubkey::new_unique();
+        let paid_by_other = Message::new(&[transfer(&other, &pubkey, 1)], Some(&other));
+        assert!(matches!(signer.sign_message(&paid_by_other.serialize()).await, Err(Error::SignerIssue)));
+
+        let cosigned_for_other = Message::new(&[transfer(&pubkey, &other, 1)], Some(&other));
+        assert!(matches!(signer.sign_message(&cosigned_for_other.serialize()).await, Err(Error::SignerIssue)));
+
+        assert!(matches!(signer.sign_message(b"not a message").await, Err(Error::SignerIssue)));
+
+        let paid_by_signer = Message::new(&[transfer(&pubkey, &other, 1)], Some(&pubkey));
+        assert!(signer.sign_message(&paid_by_signer.serialize()).await.is_ok());
+
+        let cosigned_by_other = Message::new(&[transfer(&pubkey, &other, 1), transfer(&other, &pubkey, 1)], Some(&pubkey));
+        assert!(signer.sign_message(&cosigned_by_other.serialize()).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn remote_payer_signs_next_to_local_cosigners() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let url = start_stand_in(Box::new(move |_| Ok(keypair.insecure_clone())));
+        let signer = RemoteSigner::new(&url, "token", pubkey, None).unwrap();
+
+        // Funding a new wallet that signs its own instruction, as registrations do
+        let owner = Keypair::new();
+        let instructions = [transfer(&pubkey, &owner.pubkey(), 1), transfer(&owner.pubkey(), &pubkey, 1)];
+        let tx = sign_cosigned_transaction(&instructions, &signer, &[&owner], Hash::new_unique()).await.unwrap();
+        assert!(tx.verify().is_ok());
+
+        assert!(matches!(sign_transaction(&instructions, &signer, Hash::new_unique()).await, Err(Error::TechnicalIssue)));
+    }
+
+    #[actix_web::test]
+    async fn remote_signer_rejects_other_keys_and_tokens() {
+        let pubkey = Pubkey::new_unique();
+        let url = start_stand_in(Box::new(|_| Ok(Keypair::new())));
+        let instructions = [transfer(&pubkey, &Pubkey::new_unique(), 1)];
+
+        let signer = RemoteSigner::new(&url, "token", pubkey, None).unwrap();
+        assert!(matches!(sign_transaction(&instructions, &signer, Hash::new_unique()).await, Err(Error::SignerIssue)));
+
+        let signer = RemoteSigner::new(&url, "wrong", pubkey, None).unwrap();
+        assert!(matches!(sign_transaction(&instructions, &signer, Hash::new_unique()).await, Err(Error::SignerIssue)));
+    }
+}
@@ -1,5 +1,6 @@
+use std::time::Duration;
+
 use bigdecimal::{BigDecimal, FromPrimitive};
-use bip39::Mnemonic;
 use bitcoincore_rpc::bitcoin::hex::{Case, DisplayHex};
 use crypsol_logger::{log, log_custom};
 use deadpool_redis::Pool;
@@ -8,6 +9,8 @@ use serde::Deserialize;
 use solana_derivation_path::DerivationPath;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{keypair_from_seed_and_derivation_path, Keypair, Signature, Signer};
 use solana_sdk::transaction::Transaction;
@@ -17,15 +20,41 @@ use solana_transaction_status::option_serializer::OptionSerializer;
 use spl_associated_token_account::get_associated_token_address;
 use spl_associated_token_account::instruction::create_associated_token_account;
 use spl_token::instruction::transfer_checked;
+use smart_contracts_client::confirm::{send_and_confirm_with_async_resign, ConfirmConfig, TxOutcome};
+use smart_contracts_client::events::{parse_registry_logs, RegistryEvent};
+use smart_contracts_client::instruction::{register_user_ix, transfer_sol_ix, transfer_spl_ix, validate_txn_ix};
+use smart_contracts_types::seeds::find_user_address;
+use smart_contracts_types::state::UserAccount;
 use sqlx::PgPool;
 use walletd_hd_key::prelude::*;
-use crate::config::constants::Coin;
+use crate::config::constants::{Coin, ROOT_WALLET_COIN_ID, ROOT_WALLET_USER_ID, SOLANA_COMMITMENT_CONFIG, SOLANA_CONFIRM_TIMEOUT_CONFIG, SOLANA_MAX_RESIGNS_CONFIG, SOLANA_REBROADCAST_INTERVAL_CONFIG};
 use crate::responses::error_msgs::Error;
-use crate::services::configs::get_a_config;
+use crate::services::configs::{get_a_config, get_bool_config, get_optional_config};
+use crate::services::signers::{sign_cosigned_transaction, wallet_signer, TransactionSigner};
+use crate::services::wallets::get_wallet_keys_by_user_id;
+use crate::utils::keystore::hd_seed;
 
 pub struct SolanaClient {
     pub endpoint: String,
     pub client: RpcClient,
+    pub confirm: ConfirmConfig,
+    /// Registry program custody movements are routed through, when the
+    /// registry mode is enabled in the configs table.
+    pub registry: Option<Pubkey>,
+}
+
+/// Where a sent transaction stands, as seen at the configured commitment.
+#[derive(Debug, PartialEq)]
+pub enum SignatureState {
+    /// Reached the configured commitment.
+    Confirmed,
+    /// Executed and failed.
+    Failed(String),
+    /// Seen by the cluster but below the configured commitment.
+    Processing,
+    /// Unknown to the cluster. Once the block height is past
+    /// `last_valid_block_height` the transaction can no longer land.
+    NotFound { block_height: u64 },
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,10 +71,14 @@ impl SolanaClient {
     pub async fn new(pool: &PgPool, redis_pool: &Pool) -> Result<Self, Error> {
         let endpoint = get_a_config(pool, redis_pool, "SOLANA_ADDRESS".to_string()).await?;
         eprintln!("[SOLANA_CLIENT] Using endpoint: {}", endpoint);
-        let client = RpcClient::new_with_commitment(endpoint.clone(), CommitmentConfig::confirmed());
+        let confirm = get_confirm_config(pool, redis_pool).await?;
+        let client = RpcClient::new_with_commitment(endpoint.clone(), confirm.commitment);
+        let registry = get_registry_program_id(pool, redis_pool).await?;
         Ok(Self {
             endpoint,
             client,
+            confirm,
+            registry,
         })
     }
 
@@ -157,35 +190,27 @@ impl SolanaClient {
 
     pub async fn transfer_sol(
         &self,
-        from: &Keypair,
+        from: &dyn TransactionSigner,
         to: &Pubkey,
         sols: f64,
     ) -> Result<String, Error> {
         let lamports = (sols * 1_000_000_000.0) as u64; // Convert SOL to lamports
-        let recent_blockhash = self.client.get_latest_blockhash().await.unwrap();
-
-        let tx = Transaction::new_signed_with_payer(
-            &[transfer(&from.pubkey(), to, lamports)],
-            Some(&from.pubkey()),
-            &[from],
-            recent_blockhash,
-        );
-
-        match self.client.send_and_confirm_transaction_with_spinner(&tx).await {
-            Ok(signature) => {
-                log_custom!(Level::Info, "SOLANA_CLIENT", "Transfer successful: {}", signature);
-                Ok(signature.to_string())
-            }
-            Err(e) => {
-                log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to transfer SOL: {}", e);
-                Err(Error::TechnicalIssue)
+        let instructions = match self.registry {
+            Some(program_id) => {
+                let pre_balance = self.get_balance(&from.pubkey()).await?;
+                let instructions = vec![transfer_sol_ix(&program_id, &from.pubkey(), to, lamports), validate_txn_ix(&program_id, &from.pubkey(), pre_balance, lamports)];
+                self.assert_registry_transfer(&instructions, &from.pubkey(), RegistryEvent::SolTransferred { lamports }, lamports).await?;
+                instructions
             }
-        }
+            None => vec![transfer(&from.pubkey(), to, lamports)],
+        };
+
+        self.send_signed(&instructions, from, "SOL transfer").await
     }
 
     pub async fn transfer_token(
         &self,
-        from: &Keypair,
+        from: &dyn TransactionSigner,
         to: &Pubkey,
         coin: Coin,
         coin_amount: f64,    // in raw units, e.g. for 1 USDT you'd pass 1_000_000
@@ -202,13 +227,6 @@ impl SolanaClient {
 
         let amount: u64 = (coin_amount * 10u64.pow(decimals as u32) as f64) as u64;
 
-        let recent_blockhash = match self.client.get_latest_blockhash().await {
-            Ok(hash) => hash,
-            Err(e) => {
-                log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get latest blockhash: {}", e);
-                return Err(Error::TechnicalIssue);
-            }
-        };
         let payer = &from.pubkey();
         log_custom!(Level::Info, "SOLANA_CLIENT", "Payer pubkey: {}", payer);
 
@@ -243,6 +261,16 @@ impl SolanaClient {
         }
 
         log_custom!(Level::Info, "SOLANA_CLIENT", "Transferring {}  decimals {}", amount, decimals);
+        if let Some(program_id) = self.registry {
+            // The fee lowers the lamports of the payer anyway, so the token
+            // amount of the source account is what must drop
+            let pre_balance = self.get_token_balance(&from_ata).await?;
+            instructions.push(transfer_spl_ix(&program_id, payer, to, &mint, amount));
+            instructions.push(validate_txn_ix(&program_id, &from_ata, pre_balance, amount));
+            self.assert_registry_transfer(&instructions, payer, RegistryEvent::TokensTransferred { amount }, amount).await?;
+            return self.send_signed(&instructions, from, "Registry token transfer").await;
+        }
+
         // Transfer checked ensures correct mint and decimals
         let checked_transfer = match transfer_checked(
             &spl_token::id(),
@@ -263,42 +291,244 @@ impl SolanaClient {
         };
         instructions.push(checked_transfer);
 
-        let tx = Transaction::new_signed_with_payer(
-            &instructions,
-            Some(payer),
-            &[from],
-            recent_blockhash,
-        );
-
-        match self.client.send_and_confirm_transaction_with_spinner(&tx).await {
-            Ok(signature) => {
-                log_custom!(Level::Info, "SOLANA_CLIENT", "Token transfer successful: {}", signature);
-                Ok(signature.to_string())
-            }
-            Err(e) => {
-                log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to transfer token: {}", e);
-                Err(Error::TechnicalIssue)
-            }
-        }
+        self.send_signed(&instructions, from, "Token transfer").await
     }
 }
 
-pub async fn get_solana_wallet_keypair(pool: &PgPool, redis_pool: &Pool, account_index: u32) -> Result<(String, String, Keypair), Error> {
-    // let mnemonic = Mnemonic::generate_in(Language::English, 24).unwrap();
-    // let phrase = mnemonic.to_string();
-    let phrase = get_a_config(pool, redis_pool, "MNEMONIC_PHRASE".to_string()).await?;
-    let phrase = phrase.trim();
-    let mnemonic = Mnemonic::from_str(phrase)
-        .map_err(|e| {
-            log_custom!(Level::Error, "HD_WALLET", "Failed to create mnemonic: {}", e);
+impl SolanaClient {
+    async fn get_balance(&self, address: &Pubkey) -> Result<u64, Error> {
+        self.client.get_balance(address).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get balance of {}: {}", address, e);
+            Error::RpcIssue
+        })
+    }
+
+    /// Looks `signature` up, including transactions older than the status
+    /// cache, to reconcile a transfer whose outcome was not known when sent.
+    pub async fn get_signature_state(&self, signature: &str) -> Result<SignatureState, Error> {
+        let signature = Signature::from_str(signature).map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to parse signature {}: {}", signature, e);
             Error::TechnicalIssue
         })?;
+        let status = self.client.get_signature_statuses_with_history(&[signature]).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get status of {}: {}", signature, e);
+            Error::RpcIssue
+        })?;
+        match status.value.into_iter().next().flatten() {
+            Some(status) => match status.err {
+                Some(error) => Ok(SignatureState::Failed(error.to_string())),
+                None if status.satisfies_commitment(self.confirm.commitment) => Ok(SignatureState::Confirmed),
+                None => Ok(SignatureState::Processing),
+            },
+            None => {
+                let block_height = self.client.get_block_height_with_commitment(self.confirm.commitment).await.map_err(|e| {
+                    log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get block height: {}", e);
+                    Error::RpcIssue
+                })?;
+                Ok(SignatureState::NotFound { block_height })
+            }
+        }
+    }
+
+    /// Token amount of `token_account`, in base units.
+    async fn get_token_balance(&self, token_account: &Pubkey) -> Result<u64, Error> {
+        let balance = self.client.get_token_account_balance(token_account).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get token balance of {}: {}", token_account, e);
+            Error::RpcIssue
+        })?;
+        balance.amount.parse::<u64>().map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Invalid token balance {} of {}: {}", balance.amount, token_account, e);
+            Error::RpcIssue
+        })
+    }
+
+    /// Signs `instructions` with `from` and sends them until confirmed,
+    /// asking the signer again whenever the blockhash has to be refreshed.
+    async fn send_signed(&self, instructions: &[Instruction], from: &dyn TransactionSigner, action: &str) -> Result<String, Error> {
+        self.send_cosigned(instructions, from, &[], action).await
+    }
+
+    /// Like [`Self::send_signed`], with `cosigners` signing next to `from`.
+    async fn send_cosigned(&self, instructions: &[Instruction], from: &dyn TransactionSigner, cosigners: &[&Keypair], action: &str) -> Result<String, Error> {
+        let outcome = send_and_confirm_with_async_resign(&self.client, &self.confirm, |recent_blockhash| async move {
+            Ok(sign_cosigned_transaction(instructions, from, cosigners, recent_blockhash).await?)
+        })
+        .await;
+        settle_transfer(outcome, action)
+    }
+
+    /// Simulates a registry transfer and checks the events the program logs:
+    /// `expected` must be among them, and `ValidateTxn` must report that the
+    /// sender's balance dropped by at least `min_decrease`. The program fails
+    /// the transaction otherwise as well, this catches it before anything is
+    /// signed. Nothing is sent unless the program behaves as expected. The simulation skips
+    /// signature checks, so the signer is only asked to sign what is sent.
+    async fn assert_registry_transfer(&self, instructions: &[Instruction], payer: &Pubkey, expected: RegistryEvent, min_decrease: u64) -> Result<(), Error> {
+        let Some(program_id) = self.registry else {
+            return Ok(());
+        };
+        let blockhash = self.client.get_latest_blockhash().await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get latest blockhash: {}", e);
+            Error::RpcIssue
+        })?;
+        let tx = Transaction::new_unsigned(Message::new_with_blockhash(instructions, Some(payer), &blockhash));
+        let simulation = self.client.simulate_transaction(&tx).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to simulate registry transfer: {}", e);
+            Error::RpcIssue
+        })?;
+        if let Some(err) = simulation.value.err {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Registry transfer simulation failed: {}", err);
+            return Err(Error::TechnicalIssue);
+        }
+
+        let events = parse_registry_logs(&program_id, &simulation.value.logs.unwrap_or_default());
+        let validated = events.iter().any(|event| match event {
+            RegistryEvent::BalanceValidated { pre_balance, balance, decreased } => *decreased && pre_balance.saturating_sub(*balance) >= min_decrease,
+            _ => false,
+        });
+        if !events.contains(&expected) || !validated {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Registry transfer did not log the expected events, expected {} and a balance decrease of at least {} base units, got {:?}", expected, min_decrease, events);
+            return Err(Error::TechnicalIssue);
+        }
+        Ok(())
+    }
+
+    /// Registers `owner` with the registry program unless it already has a
+    /// `UserAccount`. Fresh deposit wallets hold no SOL, so `funder` pays the
+    /// fees and sends `owner` the rent of the account in the same transaction,
+    /// signing through whichever backend holds its key.
+    pub async fn register_user(&self, funder: &dyn TransactionSigner, owner: &Keypair) -> Result<Option<String>, Error> {
+        let Some(program_id) = self.registry else {
+            return Ok(None);
+        };
+        let (user_pda, _) = find_user_address(&program_id, &owner.pubkey());
+        let existing = self.client.get_account_with_commitment(&user_pda, self.confirm.commitment).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get registry account {}: {}", user_pda, e);
+            Error::RpcIssue
+        })?;
+        // Lamports sent to the address beforehand leave a system account there,
+        // which the program still registers
+        if existing.value.is_some_and(|account| account.owner == program_id) {
+            log_custom!(Level::Info, "SOLANA_CLIENT", "{} is already registered at {}", owner.pubkey(), user_pda);
+            return Ok(None);
+        }
+
+        let rent = self.client.get_minimum_balance_for_rent_exemption(UserAccount::LEN).await.map_err(|e| {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to get rent exemption: {}", e);
+            Error::RpcIssue
+        })?;
+        let instructions = [transfer(&funder.pubkey(), &owner.pubkey(), rent), register_user_ix(&program_id, &owner.pubkey())];
+        self.send_cosigned(&instructions, funder, &[owner], "User registration").await.map(Some)
+    }
+}
+
+/// Reads how transactions are confirmed from the configs, falling back to
+/// [`ConfirmConfig::default`] for each setting that is not set.
+async fn get_confirm_config(pool: &PgPool, redis_pool: &Pool) -> Result<ConfirmConfig, Error> {
+    let defaults = ConfirmConfig::default();
+    let commitment = match get_optional_config(pool, redis_pool, SOLANA_COMMITMENT_CONFIG).await? {
+        Some(value) => match value.trim().to_lowercase().as_str() {
+            "processed" => CommitmentConfig::processed(),
+            "confirmed" => CommitmentConfig::confirmed(),
+            "finalized" => CommitmentConfig::finalized(),
+            _ => {
+                log_custom!(Level::Error, "SOLANA_CLIENT", "Invalid {} {}, expected processed, confirmed or finalized", SOLANA_COMMITMENT_CONFIG, value);
+                return Err(Error::InvalidConfiguration);
+            }
+        },
+        None => defaults.commitment,
+    };
+    let rebroadcast_interval = get_u64_config(pool, redis_pool, SOLANA_REBROADCAST_INTERVAL_CONFIG).await?.map_or(defaults.rebroadcast_interval, Duration::from_secs);
+    let max_resigns = get_u64_config(pool, redis_pool, SOLANA_MAX_RESIGNS_CONFIG).await?.map_or(defaults.max_resigns, |resigns| resigns as usize);
+    let timeout = get_u64_config(pool, redis_pool, SOLANA_CONFIRM_TIMEOUT_CONFIG).await?.map_or(defaults.timeout, Duration::from_secs);
+    Ok(ConfirmConfig { commitment, rebroadcast_interval, max_resigns, timeout })
+}
+
+async fn get_u64_config(pool: &PgPool, redis_pool: &Pool, name: &str) -> Result<Option<u64>, Error> {
+    let Some(value) = get_optional_config(pool, redis_pool, name).await? else {
+        return Ok(None);
+    };
+    value.trim().parse::<u64>().map(Some).map_err(|e| {
+        log_custom!(Level::Error, "SOLANA_CLIENT", "Invalid {} {}: {}", name, value, e);
+        Error::InvalidConfiguration
+    })
+}
 
-    let seed = mnemonic.to_seed("");
-    let seed = Seed::from(seed.as_slice());
+/// Reads the opt-in registry mode from the configs table. When
+/// `REGISTRY_MODE_ENABLED` is `true`, `REGISTRY_PROGRAM_ID` must hold the id
+/// of the deployed registry program. The mode is off when the flag is unset
+/// or `false`.
+pub async fn get_registry_program_id(pool: &PgPool, redis_pool: &Pool) -> Result<Option<Pubkey>, Error> {
+    let enabled = get_bool_config(pool, redis_pool, "REGISTRY_MODE_ENABLED").await?.unwrap_or(false);
+    if !enabled {
+        return Ok(None);
+    }
+    let program_id = get_a_config(pool, redis_pool, "REGISTRY_PROGRAM_ID".to_string()).await?;
+    Pubkey::from_str(program_id.trim()).map(Some).map_err(|e| {
+        log_custom!(Level::Error, "SOLANA_CLIENT", "Invalid REGISTRY_PROGRAM_ID {}: {}", program_id, e);
+        Error::InvalidConfiguration
+    })
+}
+
+/// Registers the derived deposit wallet of `user_id` and `coin_id` with the
+/// registry program when the registry mode is enabled. The root wallet, the
+/// wallet of `ROOT_WALLET_USER_ID` and `ROOT_WALLET_COIN_ID`, funds it. Called
+/// once the wallet address is saved; wallets already registered are skipped,
+/// so a failed registration is retried by calling it again.
+pub async fn register_deposit_wallet(pool: &PgPool, redis_pool: &Pool, user_id: i64, coin_id: i16) -> Result<(), Error> {
+    if get_registry_program_id(pool, redis_pool).await?.is_none() {
+        return Ok(());
+    }
+    let deposit_wallet = get_wallet_keys_by_user_id(pool, redis_pool, user_id, coin_id).await?;
+    if deposit_wallet.wallet_index.is_none() {
+        log_custom!(Level::Warn, "SOLANA_CLIENT", "Wallet of user {} and coin {} is not derived from the HD seed, it is not registered", user_id, coin_id);
+        return Ok(());
+    }
+    let wallet = get_verified_wallet_keypair(deposit_wallet.wallet_index, deposit_wallet.public_key.as_deref())?;
+    let client = SolanaClient::new(pool, redis_pool).await?;
+    let root_wallet = get_wallet_keys_by_user_id(pool, redis_pool, ROOT_WALLET_USER_ID, ROOT_WALLET_COIN_ID).await?;
+    let root = wallet_signer(pool, redis_pool, root_wallet.wallet_index, root_wallet.public_key.as_deref()).await?;
+    if let Some(signature) = client.register_user(root.as_ref(), &wallet).await? {
+        log_custom!(Level::Info, "SOLANA_CLIENT", "Registered deposit wallet {}: {}", wallet.pubkey(), signature);
+    }
+    Ok(())
+}
 
+/// Returns the signature of a confirmed transfer. A transfer whose status is
+/// still unknown is reported as not confirmed rather than failed, since it
+/// may land later, with its signature and last valid block height so it can
+/// be reconciled.
+fn settle_transfer(outcome: anyhow::Result<TxOutcome>, action: &str) -> Result<String, Error> {
+    match outcome {
+        Ok(TxOutcome::Confirmed { signature, slot }) => {
+            log_custom!(Level::Info, "SOLANA_CLIENT", "{} successful: {} (slot {})", action, signature, slot);
+            Ok(signature.to_string())
+        }
+        Ok(TxOutcome::Unknown { signature, last_valid_block_height }) => {
+            log_custom!(Level::Warn, "SOLANA_CLIENT", "{} {} sent but not confirmed yet, it may still land", action, signature);
+            Err(Error::TransactionNotConfirmed(signature.to_string(), last_valid_block_height))
+        }
+        Ok(TxOutcome::Failed { signature, error }) => {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "{} {} failed: {}", action, signature, error);
+            Err(Error::TechnicalIssue)
+        }
+        Ok(TxOutcome::Expired { signature }) => {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "{} {} expired without being processed", action, signature);
+            Err(Error::TechnicalIssue)
+        }
+        Err(e) => {
+            log_custom!(Level::Error, "SOLANA_CLIENT", "Failed to send {}: {}", action, e);
+            Err(Error::TechnicalIssue)
+        }
+    }
+}
+
+/// Derives the keypair at `m/44'/501'/{account_index}'/0'` from the HD seed
+/// unlocked from the keystore at startup.
+pub fn get_solana_wallet_keypair(account_index: u32) -> Result<(String, String, Keypair), Error> {
+    let seed = hd_seed()?;
     let path = DerivationPath::new_bip44(Some(account_index), Some(0));
-    let keypair = keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(path)).map_err(
+    let keypair = keypair_from_seed_and_derivation_path(seed, Some(path)).map_err(
         |e| {
             log_custom!(Level::Error, "HD_WALLET", "Failed to derive keypair: {}", e);
             Error::TechnicalIssue
@@ -306,4 +536,20 @@ pub async fn get_solana_wallet_keypair(pool: &PgPool, redis_pool: &Pool, account
     
     // store to users wallet in DB
     Ok((keypair.pubkey().to_string(), keypair.secret_bytes().to_hex_string(Case::Upper), keypair))
-}
\ No newline at end of file
+}
+
+/// Derives the keypair of `wallet_index` and checks that it matches the
+/// address stored for the wallet before it is used to sign, so a changed
+/// mnemonic or index fails instead of signing for another account.
+pub fn get_verified_wallet_keypair(wallet_index: Option<i64>, address: Option<&str>) -> Result<Keypair, Error> {
+    let (Some(wallet_index), Some(address)) = (wallet_index.and_then(|index| u32::try_from(index).ok()), address) else {
+        log_custom!(Level::Error, "HD_WALLET", "Wallet has no derivation index or address, it cannot sign");
+        return Err(Error::NotFound("Wallet keys not found".to_string()));
+    };
+    let (derived_address, _, keypair) = get_solana_wallet_keypair(wallet_index)?;
+    if derived_address != address {
+        log_custom!(Level::Error, "HD_WALLET", "Derived address {} for index {} does not match the stored address {}", derived_address, wallet_index, address);
+        return Err(Error::InvalidConfiguration);
+    }
+    Ok(keypair)
+}
@@ -1,12 +1,15 @@
 use crypsol_logger::log;
 use deadpool_redis::Pool;
 use log::Level;
+use serde_json::json;
 use sqlx::{PgPool, query, query_as};
 
 use crate::cache::users::{get_user_from_cache, increment_all_users_cache, set_users_cache};
 use crate::entities::users::Users;
 use crate::responses::error_msgs::Error;
 use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::try_record_audit;
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
 use crate::structs::users::UserCreate;
 use crate::utils::time::TimeHandler;
 
@@ -49,26 +52,31 @@ pub async fn create_user(pool: &PgPool, redis_pool: &Pool, user: UserCreate) ->
     Ok(SuccessMessages::CreatedUser { user_id: new_user.user_id })
 }
 
-pub async fn rollback_user_creation(pool: &PgPool, _redis_pool: &Pool, event_id: i64) -> Result<SuccessMessages, Error> {
-    let result = query!(
+/// Marks the user of `event_id` rolled back, audited in the same transaction.
+pub async fn rollback_user_creation(pool: &PgPool, _redis_pool: &Pool, event_id: i64, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let record = query!(
         r#"
         UPDATE users
         SET event_status = 2
-        WHERE event_id = $1
-        RETURNING user_id
+        FROM (SELECT user_id, event_status FROM users WHERE event_id = $1 FOR UPDATE) AS previous
+        WHERE users.user_id = previous.user_id
+        RETURNING users.user_id, previous.event_status
         "#,
         event_id
     )
-    .fetch_one(pool)
-    .await;
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(db_error)?;
 
-    let _record = match result {
-        Ok(users) => users,
-        Err(e) => {
-            log!(Level::Error, "Database error: {:?}", e);
-            return Err(Error::DatabaseIssue);
-        }
-    };
+    let before = json!({ "event_id": event_id, "event_status": record.event_status });
+    let after = json!({ "event_id": event_id, "event_status": 2 });
+    try_record_audit(&mut *trx, audit, AuditAction::Rollback, AuditEntity::User, &record.user_id.to_string(), Some(before), Some(after)).await?;
+    trx.commit().await.map_err(db_error)?;
 
     Ok(SuccessMessages::UserCreationFailed)
 }
@@ -2,21 +2,28 @@ use crypsol_logger::log;
 use deadpool_redis::Pool;
 use log::Level;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, query, query_as};
+use serde_json::json;
+use sqlx::{PgPool, query, query_as, query_scalar};
+use zeroize::Zeroizing;
 
 use crate::cache::wallets::{
     drop_a_wallet_from_all_coin_id_wallets, drop_a_wallet_from_all_user_id_wallets, drop_a_wallet_from_all_wallets, get_all_wallets_from_cache,
     get_wallet_from_cache_by_id, get_wallets_from_cache_for_coin_id, get_wallets_from_cache_for_user_id, increment_all_wallets_cache, increment_wallet_cache_for_coin_id, increment_wallets_cache_for_user_id, set_wallet_cache_for_id,
 };
+use crate::config::constants::{ROOT_WALLET_COIN_ID, ROOT_WALLET_USER_ID};
 use crate::entities::coins::Coins;
 use crate::entities::users_wallets::UsersWallets;
 use crate::responses::error_msgs::Error;
 use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::{audit_value_with, try_record_audit};
 use crate::services::coins::get_a_coin_from_db_by_id;
+use crate::services::configs::get_bool_config;
 use crate::services::rpc_client::RpcClient;
-use crate::services::solana_client::get_solana_wallet_keypair;
+use crate::services::solana_client::{get_solana_wallet_keypair, get_verified_wallet_keypair, register_deposit_wallet};
 use crate::services::users::get_a_user_from_db;
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
 use crate::structs::wallets::{WalletCreate, WalletQuery, WalletUpdate};
+use crate::utils::encryption::{EncryptedSecret, master_keys};
 use crate::utils::time::TimeHandler;
 
 pub async fn create_wallet(pool: &PgPool, redis_pool: &Pool, wallet: WalletCreate) -> Result<SuccessMessages, Error> {
@@ -266,17 +273,18 @@ pub async fn get_wallet_by_address(pool: &PgPool, _redis_pool: &Pool, address: S
     }
 }
 
+/// Address and HD index of a wallet, all signing needs to find its key.
+/// The stored private key is deliberately left out.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChainWallet {
     pub public_key: Option<String>,
-    pub private_key: Option<String>,
     pub wallet_index: Option<i64>,
 }
 pub async fn get_wallet_keys_by_user_id(pool: &PgPool, _redis_pool: &Pool, user_id: i64, coin_id: i16) -> Result<ChainWallet, Error> {
     let result = query_as!(
         ChainWallet,
         r#"
-        SELECT address as public_key, private_key, wallet_index FROM users_wallets WHERE user_id = $1 AND coin_id = $2
+        SELECT address as public_key, wallet_index FROM users_wallets WHERE user_id = $1 AND coin_id = $2
         "#,
         user_id,
         coin_id
@@ -296,6 +304,143 @@ pub async fn get_wallet_keys_by_user_id(pool: &PgPool, _redis_pool: &Pool, user_
     }
 }
 
+/// Whether private keys of derived wallets are stored (encrypted) next to
+/// their index. Off when `STORE_WALLET_PRIVATE_KEYS` is unset or `false`:
+/// signing re-derives the keypair from `wallet_index` and the mnemonic, and
+/// startup drops the keys already stored.
+pub async fn store_wallet_private_keys(pool: &PgPool, redis_pool: &Pool) -> Result<bool, Error> {
+    Ok(get_bool_config(pool, redis_pool, "STORE_WALLET_PRIVATE_KEYS").await?.unwrap_or(false))
+}
+
+/// Associated data sealing a private key to its wallet row.
+fn wallet_aad(wallet_id: i64) -> Vec<u8> {
+    format!("users_wallets:{wallet_id}").into_bytes()
+}
+
+/// Clears the stored private keys of derived wallets unless
+/// `STORE_WALLET_PRIVATE_KEYS` is `true`. A key is only dropped once the
+/// keypair re-derived from its `wallet_index` matches the wallet address,
+/// other rows keep their key and are reported at `Error` level. This stands
+/// in for a migration nulling the keys: SQL can not check the derived
+/// keypair, so it runs at startup, after the HD seed is unlocked.
+pub async fn drop_derived_wallet_private_keys(pool: &PgPool, redis_pool: &Pool) -> Result<(), Error> {
+    if store_wallet_private_keys(pool, redis_pool).await? {
+        return Ok(());
+    }
+    let rows = query!(
+        r#"
+        SELECT id, wallet_index as "wallet_index!", address FROM users_wallets WHERE private_key IS NOT NULL AND wallet_index IS NOT NULL
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error while loading derived wallet private keys: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    let mut dropped = 0;
+    let mut kept = Vec::new();
+    for row in &rows {
+        if get_verified_wallet_keypair(Some(row.wallet_index), row.address.as_deref()).is_err() {
+            log!(Level::Warn, "Keeping the private key of wallet {}, it does not match the keypair derived from its index", row.id);
+            kept.push(row.id.to_string());
+            continue;
+        }
+        // Only clear the row if its index and address are still the verified ones.
+        query!(
+            r#"
+            UPDATE users_wallets SET private_key = NULL, private_key_data_key = NULL, private_key_key_version = NULL, private_key_bound = FALSE
+            WHERE id = $1 AND wallet_index = $2 AND address IS NOT DISTINCT FROM $3
+            "#,
+            row.id,
+            row.wallet_index,
+            row.address
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            log!(Level::Error, "Database error while dropping wallet private key {}: {:?}", row.id, e);
+            Error::DatabaseIssue
+        })?;
+        dropped += 1;
+    }
+
+    if !rows.is_empty() {
+        log!(Level::Info, "Dropped {} of {} stored private keys of derived wallets", dropped, rows.len());
+    }
+    if !kept.is_empty() {
+        log!(Level::Error, "Private keys of wallets {} are still stored: their address does not match the keypair derived from their index. Check these wallets and clear the keys by hand", kept.join(", "));
+    }
+    Ok(())
+}
+
+fn encrypt_private_key(wallet_id: i64, private_key: Option<&str>) -> Result<Option<EncryptedSecret>, Error> {
+    private_key.map(|private_key| master_keys()?.encrypt(private_key.as_bytes(), &wallet_aad(wallet_id))).transpose()
+}
+
+/// Encrypts plaintext private keys left from before envelope encryption,
+/// seals keys encrypted without their wallet id again, and rewraps data keys
+/// of older master key versions with the current one. Run at startup, after
+/// the master keys are loaded.
+pub async fn encrypt_wallet_private_keys(pool: &PgPool) -> Result<(), Error> {
+    let keys = master_keys()?;
+    let current_version = keys.current_version();
+    let rows = query!(
+        r#"
+        SELECT id, private_key as "private_key!", private_key_data_key, private_key_key_version, private_key_bound FROM users_wallets
+        WHERE private_key IS NOT NULL AND (NOT private_key_bound OR private_key_key_version IS NULL OR private_key_key_version <> $1)
+        "#,
+        current_version
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error while loading wallet private keys: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    for row in &rows {
+        let aad = wallet_aad(row.id);
+        let sealed = match (&row.private_key_data_key, row.private_key_key_version) {
+            (Some(data_key), Some(key_version)) => {
+                let secret = EncryptedSecret { ciphertext: row.private_key.clone(), data_key: data_key.clone(), key_version };
+                if row.private_key_bound {
+                    keys.rewrap(&secret, &aad)?
+                } else {
+                    // Sealed again right away, the plaintext is zeroed once dropped
+                    keys.encrypt(&Zeroizing::new(keys.decrypt(&secret, &[])?), &aad)?
+                }
+            }
+            _ => keys.encrypt(row.private_key.as_bytes(), &aad)?,
+        };
+        // Only update the row if nobody else changed it meanwhile.
+        query!(
+            r#"
+            UPDATE users_wallets SET private_key = $1, private_key_data_key = $2, private_key_key_version = $3, private_key_bound = TRUE
+            WHERE id = $4 AND private_key = $5 AND private_key_key_version IS NOT DISTINCT FROM $6
+            "#,
+            sealed.ciphertext,
+            sealed.data_key,
+            sealed.key_version,
+            row.id,
+            row.private_key,
+            row.private_key_key_version
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            log!(Level::Error, "Database error while encrypting wallet private key {}: {:?}", row.id, e);
+            Error::DatabaseIssue
+        })?;
+    }
+
+    if !rows.is_empty() {
+        log!(Level::Info, "Encrypted {} wallet private keys with master key version {}", rows.len(), current_version);
+    }
+    Ok(())
+}
+
 pub async fn get_all_wallets(pool: &PgPool, redis_pool: &Pool) -> Result<SuccessMessages, Error> {
     if let Some(wallets) = get_all_wallets_from_cache(redis_pool).await {
         return Ok(SuccessMessages::FoundWallet {
@@ -338,43 +483,55 @@ async fn update_all_wallets_cache(pool: &PgPool, redis_pool: &Pool) -> Result<Ve
     }
 }
 
-pub async fn update_wallet(pool: &PgPool, redis_pool: &Pool, wallet_id: i64, wallet: Option<&UsersWallets>, wallet_update: WalletUpdate) -> Result<SuccessMessages, Error> {
+pub async fn update_wallet(pool: &PgPool, redis_pool: &Pool, wallet_id: i64, wallet: Option<&UsersWallets>, wallet_update: WalletUpdate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
     let wallet = if let Some(w) = wallet { w } else { &get_a_wallet_from_db_by_id(pool, wallet_id).await? };
 
     let time_handler = TimeHandler::new();
     let now = time_handler.get_current_time().naive_utc();
+    let private_key = encrypt_private_key(wallet_id, wallet_update.private_key.as_deref())?;
 
-    let update_result = query_as!(
-        UsersWallets,
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let w = query!(
         r#"
         UPDATE users_wallets
         SET coin_id = COALESCE($1, coin_id),
             address = COALESCE($2, address),
             status = COALESCE($3, status),
             private_key = COALESCE($6, private_key),
+            private_key_data_key = COALESCE($8, private_key_data_key),
+            private_key_key_version = COALESCE($9, private_key_key_version),
+            private_key_bound = private_key_bound OR $6 IS NOT NULL,
             wallet_index = COALESCE($7, wallet_index),
             updated_at = $4
-        WHERE id = $5
-        RETURNING id, user_id, coin_id, address, status, created_at, updated_at
+        FROM (SELECT id AS old_id, wallet_index AS old_wallet_index FROM users_wallets WHERE id = $5 FOR UPDATE) AS old
+        WHERE id = old.old_id
+        RETURNING id, user_id, coin_id, address, status, created_at, updated_at, wallet_index, old.old_wallet_index
         "#,
         wallet_update.coin_id,
         wallet_update.address.clone(),
         wallet_update.status,
         now,
         wallet_id,
-        wallet_update.private_key,
-        wallet_update.wallet_index
+        private_key.as_ref().map(|key| key.ciphertext.clone()),
+        wallet_update.wallet_index,
+        private_key.as_ref().map(|key| key.data_key.clone()),
+        private_key.as_ref().map(|key| key.key_version)
     )
-    .fetch_one(pool)
-    .await;
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(db_error)?;
+
+    let updated_wallet = UsersWallets { id: w.id, user_id: w.user_id, coin_id: w.coin_id, address: w.address, status: w.status, created_at: w.created_at, updated_at: w.updated_at };
+    // The key itself is never audited, only whether it was replaced
+    let before = audit_value_with(wallet, json!({ "wallet_index": w.old_wallet_index }));
+    let after = audit_value_with(&updated_wallet, json!({ "wallet_index": w.wallet_index, "private_key_changed": private_key.is_some() }));
+    try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::Wallet, &updated_wallet.id.to_string(), before, after).await?;
+    trx.commit().await.map_err(db_error)?;
 
-    let updated_wallet = match update_result {
-        Ok(w) => w,
-        Err(e) => {
-            log!(Level::Error, "Database error: {:?}", e);
-            return Err(Error::DatabaseIssue);
-        }
-    };
     let _ = set_wallet_cache_for_id(redis_pool, &updated_wallet).await;
     let _ = drop_a_wallet_from_all_user_id_wallets(redis_pool, wallet).await;
     let _ = increment_wallets_cache_for_user_id(redis_pool, &[updated_wallet.clone()]).await;
@@ -386,21 +543,30 @@ pub async fn update_wallet(pool: &PgPool, redis_pool: &Pool, wallet_id: i64, wal
     Ok(SuccessMessages::UpdatedWallet { wallet_id: updated_wallet.id })
 }
 
-pub async fn get_user_address(pool: &PgPool, redis_pool: &Pool, wallet: UsersWallets, coin: Coins) -> Result<SuccessMessages, Error> {
+/// Whether wallets of `coin` are Solana wallets derived from the HD seed.
+pub fn is_solana_coin(coin: &Coins) -> bool {
+    coin.coin_name.to_lowercase().contains("usd") || coin.coin_name.to_lowercase().contains("solana")
+}
+
+pub async fn get_user_address(pool: &PgPool, redis_pool: &Pool, wallet: UsersWallets, coin: Coins, audit: &AuditContext) -> Result<SuccessMessages, Error> {
     let label = format!("user_{}", wallet.user_id);
     // let coin = fetch_a_coin_by_id(pool, redis_pool, wallet.coin_id).await?;
     let mut wallet_index = None;
     let (mut _address, mut secret) = ("".to_string(), None);
-    if coin.coin_name.to_lowercase().contains("usd") || coin.coin_name.to_lowercase().contains("solana") {
+    if is_solana_coin(&coin) {
         wallet_index = Some(get_current_highest_wallet_index(pool, redis_pool).await?+1);
-        let (addr, sec, _) = get_solana_wallet_keypair(pool, redis_pool, wallet_index.unwrap() as u32).await?;
-        (_address, secret) = (addr, Some(sec));
+        let (addr, sec, _keypair) = get_solana_wallet_keypair(wallet_index.unwrap() as u32)?;
+        (_address, secret) = (addr, store_wallet_private_keys(pool, redis_pool).await?.then_some(sec));
     } else {
         let rpc_client = RpcClient::new(pool, redis_pool, &coin.coin_name.to_uppercase()).await?;
         _address = rpc_client.generate_new_address(&label).await?;
     }
 
-    update_wallet(pool, redis_pool, wallet.id, Some(&wallet), WalletUpdate { user_id: wallet.user_id, coin_id: Some(wallet.coin_id), address: Some(_address.clone()), private_key: secret, wallet_index, status: Some(wallet.status) }).await?;
+    update_wallet(pool, redis_pool, wallet.id, Some(&wallet), WalletUpdate { user_id: wallet.user_id, coin_id: Some(wallet.coin_id), address: Some(_address.clone()), private_key: secret, wallet_index, status: Some(wallet.status) }, audit).await?;
+    // Registered only once saved, so a failed registration neither loses nor re-uses the index
+    if wallet_index.is_some() {
+        register_deposit_wallet(pool, redis_pool, wallet.user_id, wallet.coin_id).await?;
+    }
 
     Ok(SuccessMessages::AddressGenerated { coin: wallet.coin_id, address: _address })
 }
@@ -426,16 +592,18 @@ pub async fn get_current_highest_wallet_index(pool: &PgPool, redis_pool: &Pool)
     if index.is_some() {
         return Ok(index.unwrap());
     }
-    let (root_wallet, wallet_exists) = match query!(
+    let (root_wallet, root_wallet_id) = match query!(
         r#"
-        SELECT * FROM users_wallets WHERE user_id = 1 AND coin_id=2
-        "#
+        SELECT * FROM users_wallets WHERE user_id = $1 AND coin_id = $2
+        "#,
+        ROOT_WALLET_USER_ID,
+        ROOT_WALLET_COIN_ID
     )
         .fetch_one(pool)
         .await {
-        Ok(record) => (record.wallet_index, true),
+        Ok(record) => (record.wallet_index, Some(record.id)),
         Err(sqlx::error::Error::RowNotFound) => {
-            (None, false)
+            (None, None)
         }
         Err(e) => {
             log!(Level::Error, "Database error while getting root wallet: {:?}", e);
@@ -447,15 +615,19 @@ pub async fn get_current_highest_wallet_index(pool: &PgPool, redis_pool: &Pool)
         return Ok(root_wallet.unwrap());
     }
     
-    let index = if wallet_exists {
-        let (addr, sec) = generate_root_sol_wallet(pool, redis_pool).await?;
+    let index = if let Some(wallet_id) = root_wallet_id {
+        let (addr, sec) = generate_root_sol_wallet()?;
+        let sec = encrypt_private_key(wallet_id, store_wallet_private_keys(pool, redis_pool).await?.then_some(sec.as_str()))?;
         query!(
             r#"
-            UPDATE users_wallets SET wallet_index=0, address=$1, private_key=$2
-            WHERE user_id =1 AND coin_id=2
+            UPDATE users_wallets SET wallet_index=0, address=$1, private_key=$2, private_key_data_key=$3, private_key_key_version=$4, private_key_bound=TRUE
+            WHERE id = $5
             "#,
             addr,
-            sec
+            sec.as_ref().map(|sec| sec.ciphertext.clone()),
+            sec.as_ref().map(|sec| sec.data_key.clone()),
+            sec.as_ref().map(|sec| sec.key_version),
+            wallet_id
         )
         .execute(pool)
         .await.map_err(|e| {
@@ -464,14 +636,28 @@ pub async fn get_current_highest_wallet_index(pool: &PgPool, redis_pool: &Pool)
         })?;
         0
     } else {
-        let (addr, sec) = generate_root_sol_wallet(pool, redis_pool).await?;
+        let (addr, sec) = generate_root_sol_wallet()?;
+        // The id is reserved first, the private key is sealed to it.
+        let wallet_id = query_scalar!(r#"SELECT nextval(pg_get_serial_sequence('users_wallets', 'id')) AS "id!""#)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                log!(Level::Error, "Database error while reserving the root wallet id: {:?}", e);
+                Error::DatabaseIssue
+            })?;
+        let sec = encrypt_private_key(wallet_id, store_wallet_private_keys(pool, redis_pool).await?.then_some(sec.as_str()))?;
         query!(
             r#"
-            INSERT INTO users_wallets (user_id, coin_id, status, wallet_index, address, private_key)
-            VALUES (1, 2, true, 0, $1, $2)
+            INSERT INTO users_wallets (id, user_id, coin_id, status, wallet_index, address, private_key, private_key_data_key, private_key_key_version, private_key_bound)
+            VALUES ($5, $6, $7, true, 0, $1, $2, $3, $4, TRUE)
             "#,
             addr,
-            sec
+            sec.as_ref().map(|sec| sec.ciphertext.clone()),
+            sec.as_ref().map(|sec| sec.data_key.clone()),
+            sec.as_ref().map(|sec| sec.key_version),
+            wallet_id,
+            ROOT_WALLET_USER_ID,
+            ROOT_WALLET_COIN_ID
         )
             .execute(pool)
             .await.map_err(|e| {
@@ -484,8 +670,8 @@ pub async fn get_current_highest_wallet_index(pool: &PgPool, redis_pool: &Pool)
     Ok(index)
 }
 
-pub async fn generate_root_sol_wallet(pool: &PgPool, redis_pool: &Pool) -> Result<(String, String), Error> {
-    let (address, secret, _) = get_solana_wallet_keypair(pool, redis_pool, 0).await?;
+pub fn generate_root_sol_wallet() -> Result<(String, String), Error> {
+    let (address, secret, _) = get_solana_wallet_keypair(0)?;
     Ok((address, secret))
 }
 
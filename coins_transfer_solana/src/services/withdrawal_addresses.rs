@@ -0,0 +1,266 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use crypsol_logger::log;
+use deadpool_redis::Pool;
+use log::Level;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{PgPool, query, query_as};
+
+use crate::config::constants::{Coin, WITHDRAWAL_ADDRESS_ALLOWLIST_CONFIG, WITHDRAWAL_ADDRESS_DELAY_HOURS_CONFIG, WITHDRAWAL_ADDRESS_DELAY_HOURS_DEFAULT, WITHDRAWAL_ADDRESS_DELAY_HOURS_MAX};
+use crate::entities::withdrawal_addresses::WithdrawalAddresses;
+use crate::responses::error_msgs::Error;
+use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::{audit_value, try_record_audit};
+use crate::services::coins::get_a_coin_from_db_by_id;
+use crate::services::configs::{get_bool_config, get_optional_config};
+use crate::services::users::get_a_user_from_db;
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
+use crate::structs::withdrawal_addresses::{WithdrawalAddressCreate, WithdrawalAddressQuery, WithdrawalAddressUpdate};
+
+/// Hours a newly registered or changed address waits before it can be
+/// withdrawn to, from `WITHDRAWAL_ADDRESS_DELAY_HOURS` or 24 when it is not set.
+async fn address_delay_hours(pool: &PgPool, redis_pool: &Pool) -> Result<i32, Error> {
+    let Some(value) = get_optional_config(pool, redis_pool, WITHDRAWAL_ADDRESS_DELAY_HOURS_CONFIG).await? else {
+        return Ok(WITHDRAWAL_ADDRESS_DELAY_HOURS_DEFAULT as i32);
+    };
+    parse_delay_hours(&value).ok_or_else(|| {
+        log!(Level::Error, "Invalid {} {}, expected a number of hours up to {}", WITHDRAWAL_ADDRESS_DELAY_HOURS_CONFIG, value, WITHDRAWAL_ADDRESS_DELAY_HOURS_MAX);
+        Error::InvalidConfiguration
+    })
+}
+
+fn parse_delay_hours(value: &str) -> Option<i32> {
+    match value.trim().parse::<i64>() {
+        Ok(hours) if (0..=WITHDRAWAL_ADDRESS_DELAY_HOURS_MAX).contains(&hours) => Some(hours as i32),
+        _ => None,
+    }
+}
+
+/// Whether a withdrawal may go to an address registered with `active_at`,
+/// `None` when the user has not registered it for the coin.
+fn address_allowed(active_at: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    active_at.is_some_and(|active_at| active_at <= now)
+}
+
+/// Whether `WITHDRAWAL_ADDRESS_ALLOWLIST_ENFORCED` is `true`.
+async fn allowlist_enforced(pool: &PgPool, redis_pool: &Pool) -> Result<bool, Error> {
+    Ok(get_bool_config(pool, redis_pool, WITHDRAWAL_ADDRESS_ALLOWLIST_CONFIG).await?.unwrap_or(false))
+}
+
+/// Checks that `address` is usable for `coin_id`: Solana coins need a valid
+/// public key, other chains are validated when the withdrawal is sent.
+async fn validate_withdrawal_address(pool: &PgPool, coin_id: i16, address: &str) -> Result<(), Error> {
+    let coin = get_a_coin_from_db_by_id(pool, coin_id).await?;
+    if address.is_empty() || address.len() > 255 {
+        return Err(Error::InvalidAddress);
+    }
+    if !coin.coin_name.to_lowercase().contains(Coin::Litecoin.name()) && Pubkey::from_str(address).is_err() {
+        log!(Level::Error, "Invalid withdrawal address {} for coin {}", address, coin.coin_name);
+        return Err(Error::InvalidAddress);
+    }
+    Ok(())
+}
+
+fn map_write_error(e: sqlx::Error, action: &str) -> Error {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            log!(Level::Error, "Duplicate Entry: withdrawal address already registered");
+            Error::DuplicateEntry
+        }
+        sqlx::Error::RowNotFound => Error::NotFound("Withdrawal address not found".to_string()),
+        e => {
+            log!(Level::Error, "Database error on {} withdrawal address: {:?}", action, e);
+            Error::DatabaseIssue
+        }
+    }
+}
+
+pub async fn create_withdrawal_address(pool: &PgPool, redis_pool: &Pool, create: WithdrawalAddressCreate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let _user = get_a_user_from_db(pool, create.user_id).await?;
+    let address = create.address.trim().to_string();
+    validate_withdrawal_address(pool, create.coin_id, &address).await?;
+    let delay_hours = address_delay_hours(pool, redis_pool).await?;
+
+    let mut trx = pool.begin().await.map_err(|e| map_write_error(e, "inserting"))?;
+    let withdrawal_address = query_as!(
+        WithdrawalAddresses,
+        r#"
+        INSERT INTO withdrawal_addresses (user_id, coin_id, address, label, active_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(hours => $5), NOW(), NOW())
+        RETURNING id, user_id, coin_id, address, label, active_at, created_at, updated_at
+        "#,
+        create.user_id,
+        create.coin_id,
+        address,
+        create.label,
+        delay_hours
+    )
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(|e| map_write_error(e, "inserting"))?;
+    try_record_audit(&mut *trx, audit, AuditAction::Create, AuditEntity::WithdrawalAddress, &withdrawal_address.id.to_string(), None, audit_value(&withdrawal_address)).await?;
+    trx.commit().await.map_err(|e| map_write_error(e, "inserting"))?;
+
+    log!(Level::Info, "Withdrawal address {} registered for user {}, active at {}", withdrawal_address.id, withdrawal_address.user_id, withdrawal_address.active_at);
+    Ok(SuccessMessages::CreatedWithdrawalAddress { withdrawal_address })
+}
+
+pub async fn get_withdrawal_addresses(pool: &PgPool, query: WithdrawalAddressQuery) -> Result<SuccessMessages, Error> {
+    let addresses_list = query_as!(
+        WithdrawalAddresses,
+        r#"
+        SELECT id, user_id, coin_id, address, label, active_at, created_at, updated_at FROM withdrawal_addresses
+        WHERE user_id = $1 AND ($2::SMALLINT IS NULL OR coin_id = $2)
+        ORDER BY id
+        "#,
+        query.user_id,
+        query.coin_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on listing withdrawal addresses: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    Ok(SuccessMessages::FoundWithdrawalAddresses { user_id: query.user_id, addresses_list })
+}
+
+pub async fn get_withdrawal_address_from_db(pool: &PgPool, address_id: i64) -> Result<WithdrawalAddresses, Error> {
+    query_as!(
+        WithdrawalAddresses,
+        r#"
+        SELECT id, user_id, coin_id, address, label, active_at, created_at, updated_at FROM withdrawal_addresses WHERE id = $1
+        "#,
+        address_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| map_write_error(e, "getting"))
+}
+
+/// Updates the label and address of a registered address. A new address
+/// starts a new cool-down, so an account takeover can not swap it in.
+pub async fn update_withdrawal_address(pool: &PgPool, redis_pool: &Pool, address_id: i64, update: WithdrawalAddressUpdate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let existing = get_withdrawal_address_from_db(pool, address_id).await?;
+    let address = update.address.map(|address| address.trim().to_string()).filter(|address| *address != existing.address);
+    if let Some(address) = &address {
+        validate_withdrawal_address(pool, existing.coin_id, address).await?;
+    }
+    let delay_hours = address_delay_hours(pool, redis_pool).await?;
+
+    let mut trx = pool.begin().await.map_err(|e| map_write_error(e, "updating"))?;
+    let withdrawal_address = query_as!(
+        WithdrawalAddresses,
+        r#"
+        UPDATE withdrawal_addresses
+        SET address = COALESCE($1, address),
+            label = COALESCE($2, label),
+            active_at = CASE WHEN $1::VARCHAR IS NULL THEN active_at ELSE NOW() + make_interval(hours => $3) END,
+            updated_at = NOW()
+        WHERE id = $4
+        RETURNING id, user_id, coin_id, address, label, active_at, created_at, updated_at
+        "#,
+        address,
+        update.label,
+        delay_hours,
+        address_id
+    )
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(|e| map_write_error(e, "updating"))?;
+    try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::WithdrawalAddress, &address_id.to_string(), audit_value(&existing), audit_value(&withdrawal_address)).await?;
+    trx.commit().await.map_err(|e| map_write_error(e, "updating"))?;
+
+    Ok(SuccessMessages::UpdatedWithdrawalAddress { withdrawal_address })
+}
+
+pub async fn delete_withdrawal_address(pool: &PgPool, address_id: i64, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let mut trx = pool.begin().await.map_err(|e| map_write_error(e, "deleting"))?;
+    let deleted = query_as!(
+        WithdrawalAddresses,
+        r#"
+        DELETE FROM withdrawal_addresses WHERE id = $1
+        RETURNING id, user_id, coin_id, address, label, active_at, created_at, updated_at
+        "#,
+        address_id
+    )
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(|e| map_write_error(e, "deleting"))?;
+    try_record_audit(&mut *trx, audit, AuditAction::Delete, AuditEntity::WithdrawalAddress, &address_id.to_string(), audit_value(&deleted), None).await?;
+    trx.commit().await.map_err(|e| map_write_error(e, "deleting"))?;
+
+    Ok(SuccessMessages::DeletedWithdrawalAddress { address_id })
+}
+
+/// With `WITHDRAWAL_ADDRESS_ALLOWLIST_ENFORCED` set, rejects withdrawals to
+/// addresses the user has not registered for the coin, or whose cool-down
+/// has not ended yet.
+pub async fn check_withdrawal_address_allowed(pool: &PgPool, redis_pool: &Pool, user_id: i64, coin_id: i16, address: &str) -> Result<(), Error> {
+    if !allowlist_enforced(pool, redis_pool).await? {
+        return Ok(());
+    }
+
+    let registered = query!(
+        r#"
+        SELECT (
+            SELECT active_at FROM withdrawal_addresses WHERE user_id = $1 AND coin_id = $2 AND address = $3
+        ) AS active_at, NOW()::TIMESTAMP AS "now!"
+        "#,
+        user_id,
+        coin_id,
+        address.trim()
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on checking the withdrawal address allowlist: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    if !address_allowed(registered.active_at, registered.now) {
+        log!(Level::Warn, "Rejected withdrawal for user {} to {}: address not allowlisted or still cooling down", user_id, address);
+        return Err(Error::WithdrawalAddressNotAllowed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+
+    fn now() -> NaiveDateTime {
+        DateTime::from_timestamp(1_750_000_000, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn only_registered_addresses_are_allowed() {
+        assert!(!address_allowed(None, now()));
+        assert!(address_allowed(Some(now() - Duration::days(30)), now()));
+    }
+
+    #[test]
+    fn addresses_are_allowed_once_their_cool_down_ends() {
+        let delay = Duration::hours(parse_delay_hours(" 24 ").unwrap() as i64);
+        let registered_at = now();
+        let active_at = registered_at + delay;
+        assert!(!address_allowed(Some(active_at), registered_at));
+        assert!(!address_allowed(Some(active_at), active_at - Duration::seconds(1)));
+        assert!(address_allowed(Some(active_at), active_at));
+        assert!(address_allowed(Some(active_at), active_at + Duration::hours(1)));
+    }
+
+    #[test]
+    fn cool_down_must_be_a_number_of_hours_up_to_a_year() {
+        assert_eq!(parse_delay_hours("0"), Some(0));
+        assert_eq!(parse_delay_hours("8760"), Some(8760));
+        assert_eq!(parse_delay_hours("8761"), None);
+        assert_eq!(parse_delay_hours("99999999999"), None);
+        assert_eq!(parse_delay_hours("-1"), None);
+        assert_eq!(parse_delay_hours("1.5"), None);
+        assert_eq!(parse_delay_hours(""), None);
+    }
+}
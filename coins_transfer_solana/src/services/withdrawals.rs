@@ -3,30 +3,36 @@ use crate::entities::coins::Coins;
 use crate::entities::withdrawals::Withdrawals;
 use crate::responses::error_msgs::Error;
 use crate::responses::success_msgs::SuccessMessages;
+use crate::services::audit::{audit_value, audit_value_with, record_audit, try_record_audit};
 use crate::services::coins::get_a_coin_from_db_by_id;
 use crate::services::configs::get_a_config;
 use crate::services::conversion_rates::get_rate_by_coin_id;
 use crate::services::rpc_client::RpcClient;
 use crate::services::users::get_a_user_from_db;
+use crate::structs::audit::{AuditAction, AuditContext, AuditEntity};
 use crate::structs::history::{WithdrawalsHistoryRequest, WithdrawalsHistoryResult};
 use crate::structs::withdrawals::{WithdrawalCreate, WithdrawalUpdate};
 use crate::utils::decimal_functions::truncate_decimal;
 use crate::utils::time::TimeHandler;
 use crate::utils::transactions_lock::TransactionsLocks;
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
 use crypsol_logger::log;
 use deadpool_redis::Pool;
 use log::Level;
-use sqlx::{PgPool, Postgres, Transaction, query, query_as};
+use serde_json::{Value, json};
+use sqlx::{PgPool, Postgres, Transaction, query, query_as, query_scalar};
 use std::cmp::max;
 use std::str::FromStr;
 use solana_sdk::pubkey::Pubkey;
 use crate::config::constants::Coin;
-use crate::services::solana_client::{get_solana_wallet_keypair, SolanaClient};
+use crate::services::signers::wallet_signer;
+use crate::services::solana_client::{SignatureState, SolanaClient};
 use crate::services::wallets::get_wallet_keys_by_user_id;
+use crate::services::withdrawal_addresses::check_withdrawal_address_allowed;
 
-pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: WithdrawalCreate) -> Result<SuccessMessages, Error> {
+pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: WithdrawalCreate, audit: &AuditContext) -> Result<SuccessMessages, Error> {
     let _user = get_a_user_from_db(pool, withdrawal.user_id).await?;
+    check_withdrawal_address_allowed(pool, redis_pool, withdrawal.user_id, withdrawal.coin_id, &withdrawal.address).await?;
 
     let minimum_withdrawal = get_a_config(pool, redis_pool, "WITHDRAWAL_MINIMUM".to_string()).await?;
     let minimum_withdrawal = minimum_withdrawal.parse::<BigDecimal>().unwrap();
@@ -41,6 +47,19 @@ pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: Wit
 
     TransactionsLocks::add_lock(withdrawal.user_id);
 
+    // A withdrawal whose transfer may still land keeps the user locked until
+    // it is reconciled, even across restarts. Settling an earlier withdrawal
+    // is the service's doing, not this request's, so it is audited as such.
+    reconcile_pending_withdrawals(pool, redis_pool, Some(withdrawal.user_id), &AuditContext::system()).await;
+    let has_pending = has_pending_withdrawal(pool, withdrawal.user_id).await.inspect_err(|_e| {
+        TransactionsLocks::remove_lock(withdrawal.user_id);
+    })?;
+    if has_pending {
+        TransactionsLocks::remove_lock(withdrawal.user_id);
+        log!(Level::Error, "User {} has a withdrawal pending confirmation", withdrawal.user_id);
+        return Err(Error::TransactionInProgress);
+    }
+
     let coin = get_a_coin_from_db_by_id(pool, withdrawal.coin_id).await.inspect_err(|_e| {
         TransactionsLocks::remove_lock(withdrawal.user_id);
     })?;
@@ -69,11 +88,57 @@ pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: Wit
     //     }
     // }
 
+    // The withdrawal is audited under a reserved id before its transfer is
+    // sent, and again with the outcome, so a transfer is never left unaudited.
+    let withdrawal_id = reserve_withdrawal_id(pool).await.inspect_err(|_e| {
+        TransactionsLocks::remove_lock(withdrawal.user_id);
+    })?;
+    let intent = json!({
+        "user_id": withdrawal.user_id,
+        "coin_id": coin.id,
+        "usd_amount": withdrawal.usd_amount,
+        "coin_amount": coin_amount,
+        "address": withdrawal.address,
+        "event_id": withdrawal.event_id,
+        "chain_status": "sending"
+    });
+    try_record_audit(pool, audit, AuditAction::Create, AuditEntity::Withdrawal, &withdrawal_id.to_string(), None, Some(intent.clone())).await.inspect_err(|_e| {
+        TransactionsLocks::remove_lock(withdrawal.user_id);
+    })?;
+
     let (tx_id, network_fee) = match process_chain_withdrawal(pool, redis_pool, coin.clone(), coin_amount.clone(), withdrawal.user_id, &withdrawal.address).await {
         Ok((tx_id, network_fee)) => (tx_id, network_fee),
+        Err(Error::TransactionNotConfirmed(signature, last_valid_block_height)) => {
+            log!(Level::Warn, "Withdrawal {} for user {} was sent but not confirmed yet, recording it as pending", signature, withdrawal.user_id);
+            let recorded = async {
+                let mut trx = pool.begin().await.map_err(|e| {
+                    log!(Level::Error, "Error beginning transaction for pending withdrawal: {:?}", e);
+                    Error::DatabaseIssue
+                })?;
+                let pending = record_pending_withdrawal(&mut trx, withdrawal_id, &withdrawal, &coin, coin_amount, &signature, last_valid_block_height).await?;
+                try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::Withdrawal, &withdrawal_id.to_string(), Some(intent), audit_value(&pending)).await?;
+                trx.commit().await.map_err(|e| {
+                    log!(Level::Error, "Error commiting pending withdrawal: {:?}", e);
+                    Error::DatabaseIssue
+                })?;
+                Ok::<_, Error>(pending)
+            };
+            match recorded.await {
+                Ok(pending) => {
+                    TransactionsLocks::remove_lock(withdrawal.user_id);
+                    let _ = set_withdrawal_cache_by_id(redis_pool, &pending).await;
+                    let _ = update_withdrawals_cache(pool, redis_pool, withdrawal.user_id).await;
+                }
+                // The transfer may land, so the user stays locked rather than risk paying twice
+                Err(e) => log!(Level::Error, "Failed to record pending withdrawal {} for user {}, keeping the user locked: {:?}", signature, withdrawal.user_id, e),
+            }
+            return Err(Error::TransactionNotConfirmed(signature, last_valid_block_height));
+        }
         Err(e) => {
             TransactionsLocks::remove_lock(withdrawal.user_id);
             log!(Level::Error, "Error processing withdrawal: {:?}", e);
+            let failed = failed_intent(&intent, &e);
+            record_audit(pool, audit, AuditAction::Update, AuditEntity::Withdrawal, &withdrawal_id.to_string(), Some(intent), Some(failed)).await;
             return Err(Error::TechnicalIssue);
         }
     };
@@ -81,24 +146,28 @@ pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: Wit
     let network_fee_usd = network_fee.clone() * rate.clone();
     let network_fee_usd = truncate_decimal(&network_fee_usd, 8);
 
+    // From here on the coins have left the wallet, so a failure to record the
+    // withdrawal keeps the user locked rather than allow paying twice.
     let mut trx = pool.begin().await.map_err(|e| {
-        log!(Level::Error, "Error beginning transaction for withdrawal request: {:?}", e);
-        TransactionsLocks::remove_lock(withdrawal.user_id);
+        log!(Level::Error, "Error beginning transaction for withdrawal {} of user {}, keeping the user locked: {:?}", tx_id, withdrawal.user_id, e);
         Error::DatabaseIssue
     })?;
 
-    let new_withdrawal = match process_withdrawal(&mut trx, redis_pool, withdrawal.clone(), coin, coin_amount, tx_id, network_fee, network_fee_usd).await {
+    let recorded = match process_withdrawal(&mut trx, redis_pool, withdrawal.clone(), coin, coin_amount, tx_id.clone(), network_fee, network_fee_usd, Some(withdrawal_id)).await {
+        Ok(w) => try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::Withdrawal, &w.id.to_string(), Some(intent), audit_value(&w)).await.map(|_| w),
+        Err(e) => Err(e),
+    };
+    let new_withdrawal = match recorded {
         Ok(w) => {
-            TransactionsLocks::remove_lock(withdrawal.user_id);
             trx.commit().await.map_err(|e| {
-                log!(Level::Error, "Error commiting transaction for withdrawal request: {:?}", e);
+                log!(Level::Error, "Error commiting withdrawal {} of user {}, keeping the user locked: {:?}", tx_id, withdrawal.user_id, e);
                 Error::DatabaseIssue
             })?;
+            TransactionsLocks::remove_lock(withdrawal.user_id);
             w
         }
         Err(e) => {
-            TransactionsLocks::remove_lock(withdrawal.user_id);
-            log!(Level::Error, "Error in process adding withdrawal transaction: {:?}", e);
+            log!(Level::Error, "Error recording withdrawal {} of user {}, keeping the user locked: {:?}", tx_id, withdrawal.user_id, e);
             trx.rollback().await.map_err(|e| {
                 log!(Level::Error, "Error rollingback transaction for withdrawal request: {:?}", e);
                 Error::DatabaseIssue
@@ -111,7 +180,7 @@ pub async fn create_withdrawal(pool: &PgPool, redis_pool: &Pool, withdrawal: Wit
 }
 
 pub async fn process_chain_withdrawal(pool: &PgPool, redis_pool: &Pool, coin:Coins, coin_amount: BigDecimal, from_user_id: i64, address: &str) -> Result<(String, BigDecimal), Error> {
-    if coin.coin_name.to_lowercase().contains(Coin::Litecoin.to_string()) {
+    if coin.coin_name.to_lowercase().contains(Coin::Litecoin.name()) {
         let rpc_client = RpcClient::new(pool, redis_pool, &coin.coin_name.to_uppercase()).await?;
         let tx_id = rpc_client.send_to_address(&address, coin_amount.to_f64().unwrap()).await?;
         let network_fee = get_user_withdrawal_details(rpc_client, &tx_id, coin_amount.clone()).await?;
@@ -125,23 +194,32 @@ pub async fn process_chain_withdrawal(pool: &PgPool, redis_pool: &Pool, coin:Coi
                 Error::InvalidAddress
             }
         )?;
-        let (_,_, sender_keypair) = get_solana_wallet_keypair(pool, redis_pool, wallet.wallet_index.unwrap() as u32).await?;
-        let tx_id = if coin.coin_name.to_lowercase().contains(Coin::Solana.to_string()) {
-            rpc_client.transfer_sol(&sender_keypair, &to_address, coin_amount.to_f64().unwrap()).await?
+        let signer = wallet_signer(pool, redis_pool, wallet.wallet_index, wallet.public_key.as_deref()).await?;
+        let tx_id = if coin.coin_name.to_lowercase().contains(Coin::Solana.name()) {
+            rpc_client.transfer_sol(signer.as_ref(), &to_address, coin_amount.to_f64().unwrap()).await?
         } else {
-            rpc_client.transfer_token(&sender_keypair, &to_address, Coin::from_str(&coin.coin_name.to_uppercase()).unwrap(), coin_amount.to_f64().unwrap()).await?
+            rpc_client.transfer_token(signer.as_ref(), &to_address, Coin::from_name(&coin.coin_name).unwrap(), coin_amount.to_f64().unwrap()).await?
         };
-        let tx_info = rpc_client.get_transaction_info(&tx_id).await?;
-        Ok((tx_id, BigDecimal::from_f64(tx_info.network_fee).unwrap()))
+        // The transfer is confirmed, a missing fee must not fail the withdrawal
+        let network_fee = match rpc_client.get_transaction_info(&tx_id).await {
+            Ok(tx_info) => BigDecimal::from_f64(tx_info.network_fee).unwrap_or_default(),
+            Err(e) => {
+                log!(Level::Warn, "Failed to get the network fee of withdrawal {}, recording it as 0: {:?}", tx_id, e);
+                BigDecimal::from(0)
+            }
+        };
+        Ok((tx_id, network_fee))
     }
 }
 
-pub async fn process_withdrawal(trx: &mut Transaction<'_, Postgres>, redis_pool: &Pool, withdrawal: WithdrawalCreate, coin: Coins, coin_amount: BigDecimal, tx_id: String, fee_coin: BigDecimal, fee_usd: BigDecimal) -> Result<Withdrawals, Error> {
+/// Inserts a completed withdrawal, under `withdrawal_id` when one was
+/// reserved with [`reserve_withdrawal_id`].
+pub async fn process_withdrawal(trx: &mut Transaction<'_, Postgres>, redis_pool: &Pool, withdrawal: WithdrawalCreate, coin: Coins, coin_amount: BigDecimal, tx_id: String, fee_coin: BigDecimal, fee_usd: BigDecimal, withdrawal_id: Option<i64>) -> Result<Withdrawals, Error> {
     let usd_amount = truncate_decimal(&withdrawal.usd_amount, 8);
     let result = query!(
         r#"
-        INSERT INTO withdrawals (user_id, coin_id, usd_amount, coin_amount, transaction_hash, address, status, created_at, updated_at, event_id, event_status, fee_usd_amount, fee_coin_amount)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), NOW(), $8, 1, $9, $10)
+        INSERT INTO withdrawals (id, user_id, coin_id, usd_amount, coin_amount, transaction_hash, address, status, created_at, updated_at, event_id, event_status, fee_usd_amount, fee_coin_amount)
+        VALUES (COALESCE($11, nextval(pg_get_serial_sequence('withdrawals', 'id'))), $1, $2, $3, $4, $5, $6, $7, NOW(), NOW(), $8, 1, $9, $10)
         RETURNING id, user_id, coin_id, usd_amount, coin_amount, fee_usd_amount, fee_coin_amount, transaction_hash, address, status, created_at, updated_at
         "#,
         withdrawal.user_id,
@@ -153,7 +231,8 @@ pub async fn process_withdrawal(trx: &mut Transaction<'_, Postgres>, redis_pool:
         withdrawal.status,
         withdrawal.event_id,
         fee_usd,
-        fee_coin
+        fee_coin,
+        withdrawal_id
     )
     .fetch_one(&mut **trx)
     .await;
@@ -184,27 +263,232 @@ pub async fn process_withdrawal(trx: &mut Transaction<'_, Postgres>, redis_pool:
     Ok(new_withdrawal)
 }
 
-pub async fn rollback_withdrawal_request(pool: &PgPool, _redis_pool: &Pool, event_id: i64) -> Result<SuccessMessages, Error> {
-    let result = query!(
+/// Reserves the id of a withdrawal before its transfer is sent, so it can be
+/// audited under that id whatever the outcome.
+async fn reserve_withdrawal_id(pool: &PgPool) -> Result<i64, Error> {
+    query_scalar!(r#"SELECT nextval(pg_get_serial_sequence('withdrawals', 'id')) AS "id!""#).fetch_one(pool).await.map_err(|e| {
+        log!(Level::Error, "Database error on reserving a withdrawal id: {:?}", e);
+        Error::DatabaseIssue
+    })
+}
+
+/// Records a withdrawal whose transfer was sent but not confirmed. It stays
+/// `pending`, and its user locked, until [`reconcile_pending_withdrawals`]
+/// finds out whether it landed.
+async fn record_pending_withdrawal(trx: &mut Transaction<'_, Postgres>, withdrawal_id: i64, withdrawal: &WithdrawalCreate, coin: &Coins, coin_amount: BigDecimal, signature: &str, last_valid_block_height: Option<u64>) -> Result<Withdrawals, Error> {
+    let usd_amount = truncate_decimal(&withdrawal.usd_amount, 8);
+    let last_valid_block_height = last_valid_block_height.and_then(|height| i64::try_from(height).ok());
+    let pending = query_as!(
+        Withdrawals,
         r#"
-        UPDATE withdrawals
-        SET event_status = 2
-        WHERE event_id = $1
-        RETURNING id
+        INSERT INTO withdrawals (id, user_id, coin_id, usd_amount, coin_amount, transaction_hash, address, status, created_at, updated_at, event_id, event_status, fee_usd_amount, fee_coin_amount, chain_status, last_valid_block_height)
+        VALUES ($9, $1, $2, $3, $4, $5, $6, FALSE, NOW(), NOW(), $7, 1, 0, 0, 'pending', $8)
+        RETURNING id, user_id, coin_id, usd_amount, coin_amount, fee_usd_amount, fee_coin_amount, transaction_hash, address, status, created_at, updated_at
         "#,
-        event_id
+        withdrawal.user_id,
+        coin.id,
+        usd_amount,
+        coin_amount,
+        signature,
+        withdrawal.address.clone(),
+        withdrawal.event_id,
+        last_valid_block_height,
+        withdrawal_id
+    )
+    .fetch_one(&mut **trx)
+    .await
+    .map_err(|e| {
+        log!(Level::Error, "Database error on insert of pending withdrawal: {:?}", e);
+        Error::DatabaseIssue
+    })?;
+
+    Ok(pending)
+}
+
+async fn has_pending_withdrawal(pool: &PgPool, user_id: i64) -> Result<bool, Error> {
+    query!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM withdrawals WHERE user_id = $1 AND chain_status = 'pending') AS "pending!"
+        "#,
+        user_id
     )
     .fetch_one(pool)
+    .await
+    .map(|record| record.pending)
+    .map_err(|e| {
+        log!(Level::Error, "Database error on checking pending withdrawals: {:?}", e);
+        Error::DatabaseIssue
+    })
+}
+
+/// Looks up the transfers of `pending` withdrawals, of `user_id` or of every
+/// user, by signature. A confirmed transfer completes its withdrawal with the
+/// network fee; a failed one, or one whose blockhash expired without it
+/// landing, marks it `failed`. Anything else stays pending for the next run,
+/// errors are logged and leave the withdrawal pending as well. Each settlement
+/// is audited under `audit` in the same transaction.
+pub async fn reconcile_pending_withdrawals(pool: &PgPool, redis_pool: &Pool, user_id: Option<i64>, audit: &AuditContext) {
+    let pending = query!(
+        r#"
+        SELECT id, user_id, usd_amount, coin_amount, transaction_hash, last_valid_block_height FROM withdrawals
+        WHERE chain_status = 'pending' AND ($1::BIGINT IS NULL OR user_id = $1)
+        ORDER BY id
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
     .await;
+    let pending = match pending {
+        Ok(pending) if pending.is_empty() => return,
+        Ok(pending) => pending,
+        Err(e) => {
+            log!(Level::Error, "Database error on listing pending withdrawals: {:?}", e);
+            return;
+        }
+    };
 
-    let _record = match result {
-        Ok(users) => users,
+    let client = match SolanaClient::new(pool, redis_pool).await {
+        Ok(client) => client,
         Err(e) => {
-            log!(Level::Error, "Database error on rollback of withdrawal request: {:?}", e);
-            return Err(Error::DatabaseIssue);
+            log!(Level::Error, "Failed to reconcile pending withdrawals: {:?}", e);
+            return;
+        }
+    };
+    for withdrawal in pending {
+        let Some(signature) = withdrawal.transaction_hash else {
+            log!(Level::Error, "Pending withdrawal {} has no signature, it must be resolved manually", withdrawal.id);
+            continue;
+        };
+        let state = match client.get_signature_state(&signature).await {
+            Ok(state) => state,
+            Err(e) => {
+                log!(Level::Error, "Failed to get the status of pending withdrawal {} ({}): {:?}", withdrawal.id, signature, e);
+                continue;
+            }
+        };
+        let settled = match state {
+            SignatureState::Confirmed => {
+                let fee_coin = match client.get_transaction_info(&signature).await {
+                    Ok(info) => truncate_decimal(&BigDecimal::from_f64(info.network_fee).unwrap_or_default(), 8),
+                    Err(e) => {
+                        log!(Level::Warn, "Failed to get the network fee of withdrawal {}, recording it as 0: {:?}", signature, e);
+                        BigDecimal::from(0)
+                    }
+                };
+                // The rate the withdrawal was made at
+                let fee_usd = if withdrawal.coin_amount.is_zero() { BigDecimal::from(0) } else { truncate_decimal(&(fee_coin.clone() * withdrawal.usd_amount / withdrawal.coin_amount), 8) };
+                settle_pending_withdrawal(pool, audit, withdrawal.id, "confirmed", fee_usd, fee_coin).await
+            }
+            SignatureState::Failed(error) => {
+                log!(Level::Warn, "Pending withdrawal {} ({}) failed on chain: {}", withdrawal.id, signature, error);
+                settle_pending_withdrawal(pool, audit, withdrawal.id, "failed", BigDecimal::from(0), BigDecimal::from(0)).await
+            }
+            SignatureState::NotFound { block_height } if withdrawal.last_valid_block_height.is_some_and(|last_valid| i64::try_from(block_height).is_ok_and(|height| height > last_valid)) => {
+                log!(Level::Warn, "Pending withdrawal {} ({}) expired without landing", withdrawal.id, signature);
+                settle_pending_withdrawal(pool, audit, withdrawal.id, "failed", BigDecimal::from(0), BigDecimal::from(0)).await
+            }
+            SignatureState::NotFound { .. } | SignatureState::Processing => continue,
+        };
+        match settled {
+            Ok(()) => {
+                log!(Level::Info, "Reconciled pending withdrawal {} ({})", withdrawal.id, signature);
+                let _ = update_withdrawals_cache(pool, redis_pool, withdrawal.user_id).await;
+            }
+            Err(e) => log!(Level::Error, "Failed to reconcile pending withdrawal {} ({}): {:?}", withdrawal.id, signature, e),
         }
+    }
+}
+
+/// Records the outcome of a pending withdrawal and its audit entry in one
+/// transaction. A withdrawal already settled by another run is left alone.
+async fn settle_pending_withdrawal(pool: &PgPool, audit: &AuditContext, withdrawal_id: i64, chain_status: &str, fee_usd: BigDecimal, fee_coin: BigDecimal) -> Result<(), Error> {
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error on settling pending withdrawal {}: {:?}", withdrawal_id, e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let before = query_as!(
+        Withdrawals,
+        r#"
+        SELECT id, user_id, coin_id, usd_amount, coin_amount, fee_usd_amount, fee_coin_amount, transaction_hash, address, status, created_at, updated_at FROM withdrawals
+        WHERE id = $1 AND chain_status = 'pending'
+        FOR UPDATE
+        "#,
+        withdrawal_id
+    )
+    .fetch_optional(&mut *trx)
+    .await
+    .map_err(db_error)?;
+    let Some(before) = before else {
+        return Ok(());
     };
 
+    let after = query_as!(
+        Withdrawals,
+        r#"
+        UPDATE withdrawals
+        SET chain_status = $1::VARCHAR,
+            status = $1::VARCHAR = 'confirmed',
+            fee_usd_amount = $2,
+            fee_coin_amount = $3,
+            updated_at = NOW()
+        WHERE id = $4
+        RETURNING id, user_id, coin_id, usd_amount, coin_amount, fee_usd_amount, fee_coin_amount, transaction_hash, address, status, created_at, updated_at
+        "#,
+        chain_status,
+        fee_usd,
+        fee_coin,
+        withdrawal_id
+    )
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(db_error)?;
+
+    let (before, after) = settlement_audit(&before, &after, chain_status);
+    try_record_audit(&mut *trx, audit, AuditAction::Update, AuditEntity::Withdrawal, &withdrawal_id.to_string(), before, after).await?;
+    trx.commit().await.map_err(db_error)
+}
+
+/// Audit values of a pending withdrawal settled as `chain_status`, which the
+/// `Withdrawals` record does not serialize.
+fn settlement_audit(before: &Withdrawals, after: &Withdrawals, chain_status: &str) -> (Option<Value>, Option<Value>) {
+    (audit_value_with(before, json!({ "chain_status": "pending" })), audit_value_with(after, json!({ "chain_status": chain_status })))
+}
+
+/// The intent of a withdrawal whose transfer failed to be sent, with the error.
+fn failed_intent(intent: &Value, error: &Error) -> Value {
+    let mut failed = intent.clone();
+    failed["chain_status"] = json!("failed");
+    failed["error"] = json!(error.to_string());
+    failed
+}
+
+/// Marks the withdrawal of `event_id` rolled back, audited in the same transaction.
+pub async fn rollback_withdrawal_request(pool: &PgPool, _redis_pool: &Pool, event_id: i64, audit: &AuditContext) -> Result<SuccessMessages, Error> {
+    let db_error = |e: sqlx::Error| {
+        log!(Level::Error, "Database error on rollback of withdrawal request: {:?}", e);
+        Error::DatabaseIssue
+    };
+    let mut trx = pool.begin().await.map_err(db_error)?;
+    let record = query!(
+        r#"
+        UPDATE withdrawals
+        SET event_status = 2
+        FROM (SELECT id, event_status FROM withdrawals WHERE event_id = $1 FOR UPDATE) AS previous
+        WHERE withdrawals.id = previous.id
+        RETURNING withdrawals.id, previous.event_status
+        "#,
+        event_id
+    )
+    .fetch_one(&mut *trx)
+    .await
+    .map_err(db_error)?;
+
+    let before = json!({ "event_id": event_id, "event_status": record.event_status });
+    let after = json!({ "event_id": event_id, "event_status": 2 });
+    try_record_audit(&mut *trx, audit, AuditAction::Rollback, AuditEntity::Withdrawal, &record.id.to_string(), Some(before), Some(after)).await?;
+    trx.commit().await.map_err(db_error)?;
+
     Ok(SuccessMessages::WithdrawalFailed)
 }
 
@@ -454,3 +738,47 @@ pub async fn get_user_withdrawal_details(rpc_client: RpcClient, tx_id: &str, use
     }
     Ok(BigDecimal::from(0))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn withdrawal(status: bool, fee: i32) -> Withdrawals {
+        let now = TimeHandler::new().get_current_time().naive_utc();
+        Withdrawals {
+            id: 7,
+            user_id: 42,
+            coin_id: 2,
+            usd_amount: BigDecimal::from(10),
+            coin_amount: BigDecimal::from(1),
+            fee_usd_amount: BigDecimal::from(fee),
+            fee_coin_amount: BigDecimal::from(fee),
+            transaction_hash: Some("signature".to_string()),
+            address: Some("address".to_string()),
+            status,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn settlements_audit_the_chain_status_change() {
+        let (before, after) = settlement_audit(&withdrawal(false, 0), &withdrawal(true, 1), "confirmed");
+        let (before, after) = (before.unwrap(), after.unwrap());
+        assert_eq!(before["chain_status"], "pending");
+        assert_eq!(before["status"], false);
+        assert_eq!(after["chain_status"], "confirmed");
+        assert_eq!(after["status"], true);
+        assert_eq!(after["transaction_hash"], "signature");
+    }
+
+    #[test]
+    fn failed_sends_audit_the_intent_with_the_error() {
+        let intent = json!({ "user_id": 42, "chain_status": "sending" });
+        let failed = failed_intent(&intent, &Error::RpcIssue);
+        assert_eq!(failed["user_id"], 42);
+        assert_eq!(failed["chain_status"], "failed");
+        assert_eq!(failed["error"], Error::RpcIssue.to_string());
+        assert_eq!(intent["chain_status"], "sending");
+    }
+}
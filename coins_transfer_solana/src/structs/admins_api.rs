@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
 
 #[derive(Serialize, Deserialize)]
 pub struct AdminApiCreate {
@@ -13,3 +14,24 @@ pub struct AdminApiUpdate {
     pub api_secret: Option<String>,
     pub status: Option<bool>,
 }
+
+/// Identity of the admin whose API key signed the request, stored in the
+/// request extensions by the authentication middleware.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuthenticatedAdmin {
+    pub id: i32,
+    pub name: String,
+    pub role: Role,
+}
+
+/// Role stored in `admins.role`. Every role may call read-only routes;
+/// mutations are limited to the role owning the scope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Role {
+    ReadOnly,
+    DepositsOperator,
+    WithdrawalsOperator,
+    ConfigAdmin,
+}
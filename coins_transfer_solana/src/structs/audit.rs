@@ -0,0 +1,88 @@
+use std::future::{Ready, ready};
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use uuid::Uuid;
+
+use crate::middlewares::ip_allowlist::client_ip;
+use crate::structs::admins_api::AuthenticatedAdmin;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const REQUEST_ID_MAX_LEN: usize = 128;
+
+/// Who made a request and from where, recorded with every audit entry.
+/// Extracted from the authenticated admin, the `X-Request-Id` header (an id
+/// is generated when it is missing) and the client address resolved by the
+/// IP allowlist.
+#[derive(Clone, Debug)]
+pub struct AuditContext {
+    pub actor: Option<AuthenticatedAdmin>,
+    pub request_id: String,
+    pub client_ip: Option<String>,
+}
+
+impl AuditContext {
+    /// Context of changes made by a background task rather than a request,
+    /// recorded without an actor or client address.
+    pub fn system() -> Self {
+        AuditContext { actor: None, request_id: Uuid::new_v4().to_string(), client_ip: None }
+    }
+}
+
+impl FromRequest for AuditContext {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && value.len() <= REQUEST_ID_MAX_LEN)
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let client_ip = client_ip(req).map(|ip| ip.to_string());
+
+        ready(Ok(AuditContext { actor: req.extensions().get::<AuthenticatedAdmin>().cloned(), request_id, client_ip }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Rollback,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditEntity {
+    Config,
+    Coin,
+    Wallet,
+    Withdrawal,
+    WithdrawalAddress,
+    User,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditLogQuery {
+    pub actor_id: Option<i32>,
+    pub action: Option<AuditAction>,
+    pub entity: Option<AuditEntity>,
+    pub entity_id: Option<String>,
+    pub request_id: Option<String>,
+    pub client_ip: Option<String>,
+    pub from_date: Option<NaiveDate>,
+    pub to_date: Option<NaiveDate>,
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+}
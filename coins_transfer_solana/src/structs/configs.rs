@@ -5,10 +5,13 @@ use sqlx::FromRow;
 pub struct ConfigCreate {
     pub name: String,
     pub value: String,
+    pub is_secret: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ConfigUpdate {
     pub name: Option<String>,
     pub value: Option<String>,
+    /// Marks the config as secret. A secret config can not be made public again.
+    pub is_secret: Option<bool>,
 }
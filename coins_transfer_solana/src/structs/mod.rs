@@ -1,4 +1,5 @@
 pub mod admins_api;
+pub mod audit;
 pub mod coin_clients;
 pub mod coins;
 pub mod configs;
@@ -10,4 +11,5 @@ pub mod notify_payment;
 pub mod request_validation;
 pub mod users;
 pub mod wallets;
+pub mod withdrawal_addresses;
 pub mod withdrawals;
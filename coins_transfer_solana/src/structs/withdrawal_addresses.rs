@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WithdrawalAddressCreate {
+    pub user_id: i64,
+    pub coin_id: i16,
+    pub address: String,
+    pub label: Option<String>,
+}
+
+/// Changing `address` restarts the cool-down.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WithdrawalAddressUpdate {
+    pub address: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WithdrawalAddressQuery {
+    pub user_id: i64,
+    pub coin_id: Option<i16>,
+}
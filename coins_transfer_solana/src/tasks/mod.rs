@@ -1,2 +1,3 @@
 pub mod rate_updater;
 pub mod task_manager;
+pub mod withdrawal_reconciler;
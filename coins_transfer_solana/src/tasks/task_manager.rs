@@ -1,4 +1,5 @@
 use crate::tasks::rate_updater::start_rate_updater;
+use crate::tasks::withdrawal_reconciler::start_withdrawal_reconciler;
 use crypsol_logger::log;
 use deadpool_redis::Pool;
 use log::Level;
@@ -28,6 +29,11 @@ impl TaskManager {
             self.start_task("update_conversion_rate", |pg_pool, redis_pool| async move {
                 Self::update_conversion_rate(pg_pool, redis_pool).await;
             });
+            self.start_task("reconcile_pending_withdrawals", |pg_pool, redis_pool| async move {
+                if let Err(e) = start_withdrawal_reconciler(pg_pool, redis_pool).await {
+                    log!(Level::Error, "Withdrawal reconciler not started: {}", e);
+                }
+            });
 
             log!(Level::Info, "🌐 Task Manager Started ✅");
         });
@@ -0,0 +1,56 @@
+use crate::config::constants::{WITHDRAWAL_RECONCILE_INTERVAL_CONFIG, WITHDRAWAL_RECONCILE_INTERVAL_SECS_DEFAULT};
+use crate::responses::error_msgs::Error;
+use crate::services::configs::get_optional_config;
+use crate::services::withdrawals::reconcile_pending_withdrawals;
+use crate::structs::audit::AuditContext;
+use crypsol_logger::log;
+use deadpool_redis::Pool;
+use log::Level;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Periodically settles withdrawals left pending because their transfer was
+/// not confirmed when it was sent.
+pub async fn start_withdrawal_reconciler(pool: PgPool, redis_pool: Pool) -> Result<(), Error> {
+    log!(Level::Info, "Starting withdrawal reconciler task");
+    let sleep_duration = reconcile_interval_secs(&pool, &redis_pool).await?;
+
+    tokio::spawn(async move {
+        loop {
+            reconcile_pending_withdrawals(&pool, &redis_pool, None, &AuditContext::system()).await;
+            sleep(Duration::from_secs(sleep_duration)).await;
+        }
+    });
+    Ok(())
+}
+
+/// Seconds between two reconciler runs, from `WITHDRAWAL_RECONCILE_INTERVAL_IN_SECONDS`
+/// or 60 when it is not set. Also read at startup, so a bad value stops the
+/// service before the reconciler would.
+pub async fn reconcile_interval_secs(pool: &PgPool, redis_pool: &Pool) -> Result<u64, Error> {
+    let Some(value) = get_optional_config(pool, redis_pool, WITHDRAWAL_RECONCILE_INTERVAL_CONFIG).await? else {
+        return Ok(WITHDRAWAL_RECONCILE_INTERVAL_SECS_DEFAULT);
+    };
+    parse_interval_secs(&value).ok_or_else(|| {
+        log!(Level::Error, "Invalid {} {}, expected a positive number of seconds", WITHDRAWAL_RECONCILE_INTERVAL_CONFIG, value);
+        Error::InvalidConfiguration
+    })
+}
+
+fn parse_interval_secs(value: &str) -> Option<u64> {
+    value.trim().parse::<u64>().ok().filter(|secs| *secs > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_are_positive_seconds() {
+        assert_eq!(parse_interval_secs(" 30 "), Some(30));
+        for value in ["0", "-5", "1m", ""] {
+            assert_eq!(parse_interval_secs(value), None);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use crypsol_logger::log;
+use log::Level;
+
+use crate::responses::error_msgs::Error;
+
+pub const MASTER_KEYS_ENV: &str = "WALLET_MASTER_KEYS";
+pub const MASTER_KEYS_FILE_ENV: &str = "WALLET_MASTER_KEYS_FILE";
+
+const NONCE_LEN: usize = 12;
+
+static MASTER_KEYS: OnceLock<MasterKeys> = OnceLock::new();
+
+/// A secret sealed with envelope encryption: the secret is encrypted with a
+/// random data key, and the data key with the master key `key_version`.
+/// Both fields are hex of `nonce || ciphertext` (AES-256-GCM), authenticated
+/// with the associated data of their owner so they can't be moved to another.
+#[derive(Clone, Debug)]
+pub struct EncryptedSecret {
+    pub ciphertext: String,
+    pub data_key: String,
+    pub key_version: i16,
+}
+
+/// Versioned AES-256 master keys. New secrets are wrapped with the highest
+/// version; older versions are kept to unwrap existing data keys until they
+/// are rewrapped.
+pub struct MasterKeys {
+    keys: BTreeMap<i16, Key<Aes256Gcm>>,
+    current_version: i16,
+}
+
+impl MasterKeys {
+    /// Builds the key set, which needs at least one key to encrypt with.
+    fn new(keys: BTreeMap<i16, Key<Aes256Gcm>>) -> Result<Self, Error> {
+        let Some(&current_version) = keys.keys().next_back() else {
+            log!(Level::Error, "No wallet master key configured");
+            return Err(Error::InvalidConfiguration);
+        };
+        Ok(MasterKeys { keys, current_version })
+    }
+
+    /// Parses `version:hex_key` entries separated by commas or newlines.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut keys = BTreeMap::new();
+        for entry in value.split([',', '\n']).map(str::trim).filter(|entry| !entry.is_empty()) {
+            let parsed = entry.split_once(':').and_then(|(version, key)| Some((version.trim().parse::<i16>().ok()?, hex::decode(key.trim()).ok()?)));
+            match parsed {
+                Some((version, key)) if key.len() == 32 => {
+                    keys.insert(version, *Key::<Aes256Gcm>::from_slice(&key));
+                }
+                _ => {
+                    log!(Level::Error, "Invalid wallet master key entry, expected <version>:<64 hex characters>");
+                    return Err(Error::InvalidConfiguration);
+                }
+            }
+        }
+        Self::new(keys)
+    }
+
+    /// Loads the keys from the file named by `WALLET_MASTER_KEYS_FILE`, or
+    /// from `WALLET_MASTER_KEYS` when no file is configured.
+    pub fn load() -> Result<Self, Error> {
+        if let Ok(path) = env::var(MASTER_KEYS_FILE_ENV) {
+            let value = fs::read_to_string(&path).map_err(|e| {
+                log!(Level::Error, "Failed to read wallet master keys from {}: {}", path, e);
+                Error::InvalidConfiguration
+            })?;
+            return Self::parse(&value);
+        }
+        let value = env::var(MASTER_KEYS_ENV).map_err(|_| {
+            log!(Level::Error, "{} or {} is not set in .env file", MASTER_KEYS_ENV, MASTER_KEYS_FILE_ENV);
+            Error::EnvVarMissing(MASTER_KEYS_ENV.to_string())
+        })?;
+        Self::parse(&value)
+    }
+
+    pub fn current_version(&self) -> i16 {
+        self.current_version
+    }
+
+    /// Encrypts `plaintext` with a new data key wrapped by the current master
+    /// key, both bound to `aad`.
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<EncryptedSecret, Error> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let version = self.current_version();
+        Ok(EncryptedSecret { ciphertext: seal(&data_key, plaintext, aad)?, data_key: seal(&self.keys[&version], &data_key, aad)?, key_version: version })
+    }
+
+    /// Decrypts `secret`, failing unless it was encrypted with the same `aad`.
+    pub fn decrypt(&self, secret: &EncryptedSecret, aad: &[u8]) -> Result<Vec<u8>, Error> {
+        let data_key = self.unwrap_data_key(secret, aad)?;
+        open(&data_key, &secret.ciphertext, aad)
+    }
+
+    /// Wraps the data key of `secret` with the current master key, leaving
+    /// the ciphertext untouched. Used to rotate master keys.
+    pub fn rewrap(&self, secret: &EncryptedSecret, aad: &[u8]) -> Result<EncryptedSecret, Error> {
+        let data_key = self.unwrap_data_key(secret, aad)?;
+        let version = self.current_version();
+        Ok(EncryptedSecret { ciphertext: secret.ciphertext.clone(), data_key: seal(&self.keys[&version], &data_key, aad)?, key_version: version })
+    }
+
+    fn unwrap_data_key(&self, secret: &EncryptedSecret, aad: &[u8]) -> Result<Key<Aes256Gcm>, Error> {
+        let Some(master_key) = self.keys.get(&secret.key_version) else {
+            log!(Level::Error, "Wallet master key version {} is not configured", secret.key_version);
+            return Err(Error::InvalidConfiguration);
+        };
+        let data_key = open(master_key, &secret.data_key, aad)?;
+        if data_key.len() != 32 {
+            log!(Level::Error, "Unwrapped data key has an invalid length");
+            return Err(Error::TechnicalIssue);
+        }
+        Ok(*Key::<Aes256Gcm>::from_slice(&data_key))
+    }
+}
+
+fn seal(key: &Key<Aes256Gcm>, plaintext: &[u8], aad: &[u8]) -> Result<String, Error> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(key).encrypt(&nonce, Payload { msg: plaintext, aad }).map_err(|_| {
+        log!(Level::Error, "Failed to encrypt secret");
+        Error::TechnicalIssue
+    })?;
+    Ok(hex::encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn open(key: &Key<Aes256Gcm>, sealed: &str, aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let sealed = hex::decode(sealed).ok().filter(|sealed| sealed.len() > NONCE_LEN).ok_or_else(|| {
+        log!(Level::Error, "Encrypted secret is malformed");
+        Error::TechnicalIssue
+    })?;
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    Aes256Gcm::new(key).decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).map_err(|_| {
+        log!(Level::Error, "Failed to decrypt secret, wrong master key or tampered data");
+        Error::TechnicalIssue
+    })
+}
+
+/// Loads the master keys once at startup, see [`MasterKeys::load`].
+pub fn init_master_keys() -> Result<&'static MasterKeys, Error> {
+    if let Some(keys) = MASTER_KEYS.get() {
+        return Ok(keys);
+    }
+    let keys = MasterKeys::load()?;
+    Ok(MASTER_KEYS.get_or_init(|| keys))
+}
+
+pub fn master_keys() -> Result<&'static MasterKeys, Error> {
+    MASTER_KEYS.get().ok_or_else(|| {
+        log!(Level::Error, "Wallet master keys are not initialized");
+        Error::InvalidConfiguration
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_1: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+    const KEY_2: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+
+    fn keys(value: &str) -> MasterKeys {
+        MasterKeys::parse(value).unwrap()
+    }
+
+    #[test]
+    fn parses_versioned_keys() {
+        assert_eq!(keys(&format!("1:{KEY_1},\n 2:{KEY_2}\n")).current_version(), 2);
+        assert!(matches!(MasterKeys::parse(""), Err(Error::InvalidConfiguration)));
+        assert!(matches!(MasterKeys::parse(" ,\n"), Err(Error::InvalidConfiguration)));
+        assert!(matches!(MasterKeys::parse(&format!("x:{KEY_1}")), Err(Error::InvalidConfiguration)));
+        assert!(matches!(MasterKeys::parse("1:abcd"), Err(Error::InvalidConfiguration)));
+    }
+
+    #[test]
+    fn decrypts_what_it_encrypts() {
+        let keys = keys(&format!("1:{KEY_1}"));
+        let secret = keys.encrypt(b"private key", b"wallet 1").unwrap();
+        assert_eq!(secret.key_version, 1);
+        assert_eq!(keys.decrypt(&secret, b"wallet 1").unwrap(), b"private key");
+    }
+
+    #[test]
+    fn rewraps_with_the_current_version() {
+        let secret = keys(&format!("1:{KEY_1}")).encrypt(b"private key", b"wallet 1").unwrap();
+        let keys = keys(&format!("1:{KEY_1},2:{KEY_2}"));
+
+        let rewrapped = keys.rewrap(&secret, b"wallet 1").unwrap();
+        assert_eq!(rewrapped.key_version, 2);
+        assert_eq!(rewrapped.ciphertext, secret.ciphertext);
+        assert_eq!(keys.decrypt(&rewrapped, b"wallet 1").unwrap(), b"private key");
+    }
+
+    #[test]
+    fn rejects_unknown_or_wrong_versions() {
+        let secret = keys(&format!("2:{KEY_2}")).encrypt(b"private key", b"wallet 1").unwrap();
+        assert!(matches!(keys(&format!("1:{KEY_1}")).decrypt(&secret, b"wallet 1"), Err(Error::InvalidConfiguration)));
+
+        let mislabelled = EncryptedSecret { key_version: 1, ..secret };
+        assert!(matches!(keys(&format!("1:{KEY_1},2:{KEY_2}")).decrypt(&mislabelled, b"wallet 1"), Err(Error::TechnicalIssue)));
+    }
+
+    #[test]
+    fn rejects_tampered_or_moved_secrets() {
+        let keys = keys(&format!("1:{KEY_1}"));
+        let secret = keys.encrypt(b"private key", b"wallet 1").unwrap();
+        assert!(matches!(keys.decrypt(&secret, b"wallet 2"), Err(Error::TechnicalIssue)));
+        assert!(matches!(keys.rewrap(&secret, b"wallet 2"), Err(Error::TechnicalIssue)));
+
+        let mut ciphertext = hex::decode(&secret.ciphertext).unwrap();
+        *ciphertext.last_mut().unwrap() ^= 1;
+        let tampered = EncryptedSecret { ciphertext: hex::encode(ciphertext), ..secret.clone() };
+        assert!(matches!(keys.decrypt(&tampered, b"wallet 1"), Err(Error::TechnicalIssue)));
+
+        let truncated = EncryptedSecret { data_key: secret.data_key[..NONCE_LEN * 2].to_string(), ..secret };
+        assert!(matches!(keys.decrypt(&truncated, b"wallet 1"), Err(Error::TechnicalIssue)));
+    }
+}
@@ -0,0 +1,333 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use bip39::Mnemonic;
+use crypsol_logger::log;
+use log::Level;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer, read_keypair_file};
+use zeroize::Zeroizing;
+
+use crate::responses::error_msgs::Error;
+
+pub const KEYSTORE_FILE_ENV: &str = "MNEMONIC_KEYSTORE_FILE";
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "MNEMONIC_KEYSTORE_PASSPHRASE";
+pub const MNEMONIC_ENV: &str = "MNEMONIC_PHRASE";
+pub const ALLOW_UNSEALED_MNEMONIC_ENV: &str = "ALLOW_UNSEALED_MNEMONIC";
+pub const SIGNER_KEYSTORE_PASSPHRASE_ENV: &str = "SIGNER_KEYSTORE_PASSPHRASE";
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+
+static HD_SEED: OnceLock<Zeroizing<[u8; 64]>> = OnceLock::new();
+
+/// Sealed secret as written to `MNEMONIC_KEYSTORE_FILE` or a signer keystore
+/// directory: the secret is encrypted with AES-256-GCM under a key derived
+/// from the passphrase with Argon2id. Binary fields are hex.
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u8,
+    kdf: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Zeroizing<[u8; 32]>, Error> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| {
+        log!(Level::Error, "Invalid keystore KDF parameters: {}", e);
+        Error::InvalidConfiguration
+    })?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(passphrase, salt, key.as_mut()).map_err(|e| {
+        log!(Level::Error, "Failed to derive the keystore key: {}", e);
+        Error::TechnicalIssue
+    })?;
+    Ok(key)
+}
+
+fn read_passphrase(env_name: &str, prompt: &str) -> Result<Zeroizing<String>, Error> {
+    if let Ok(passphrase) = env::var(env_name) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    rpassword::prompt_password(prompt).map(Zeroizing::new).map_err(|e| {
+        log!(Level::Error, "{} is not set and no passphrase could be read from the terminal: {}", env_name, e);
+        Error::EnvVarMissing(env_name.to_string())
+    })
+}
+
+/// Reads the passphrase of a new keystore from `env_name`, or from the
+/// terminal where it is typed twice.
+fn read_new_passphrase(env_name: &str) -> Result<Zeroizing<String>, Error> {
+    let passphrase = read_passphrase(env_name, "New keystore passphrase: ")?;
+    if env::var(env_name).is_err() && *read_passphrase(env_name, "Repeat the passphrase: ")? != *passphrase {
+        log!(Level::Error, "Passphrases do not match");
+        return Err(Error::InvalidConfiguration);
+    }
+    if passphrase.is_empty() {
+        log!(Level::Error, "The keystore passphrase can not be empty");
+        return Err(Error::InvalidConfiguration);
+    }
+    Ok(passphrase)
+}
+
+fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, Error> {
+    Mnemonic::from_str(phrase.trim()).map_err(|e| {
+        log!(Level::Error, "Invalid HD mnemonic: {}", e);
+        Error::InvalidConfiguration
+    })
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(value).map_err(|_| {
+        log!(Level::Error, "Keystore field {} is not hex", field);
+        Error::InvalidConfiguration
+    })
+}
+
+/// Decrypts the keystore at `path` and returns the sealed secret.
+pub fn open_keystore(path: &str, passphrase: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        log!(Level::Error, "Failed to read the keystore {}: {}", path, e);
+        Error::InvalidConfiguration
+    })?;
+    let keystore: KeystoreFile = serde_json::from_str(&content).map_err(|e| {
+        log!(Level::Error, "Keystore {} is malformed: {}", path, e);
+        Error::InvalidConfiguration
+    })?;
+    if keystore.version != KEYSTORE_VERSION || keystore.kdf != "argon2id" {
+        log!(Level::Error, "Unsupported keystore version {} with kdf {}", keystore.version, keystore.kdf);
+        return Err(Error::InvalidConfiguration);
+    }
+
+    let nonce = decode_hex("nonce", &keystore.nonce)?;
+    if nonce.len() != 12 {
+        log!(Level::Error, "Keystore nonce has an invalid length");
+        return Err(Error::InvalidConfiguration);
+    }
+    let key = derive_key(passphrase, &decode_hex("salt", &keystore.salt)?, keystore.m_cost, keystore.t_cost, keystore.p_cost)?;
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref()))
+        .decrypt(Nonce::from_slice(&nonce), decode_hex("ciphertext", &keystore.ciphertext)?.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            log!(Level::Error, "Failed to unlock the keystore {}, wrong passphrase or tampered file", path);
+            Error::InvalidConfiguration
+        })
+}
+
+/// Encrypts `secret` into a new keystore at `path`, which must not exist yet.
+pub fn seal_keystore(path: &str, passphrase: &[u8], secret: &[u8]) -> Result<(), Error> {
+    let params = Params::default();
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, params.m_cost(), params.t_cost(), params.p_cost())?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref())).encrypt(&nonce, secret).map_err(|_| {
+        log!(Level::Error, "Failed to encrypt the keystore secret");
+        Error::TechnicalIssue
+    })?;
+
+    let keystore = KeystoreFile {
+        version: KEYSTORE_VERSION,
+        kdf: "argon2id".to_string(),
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    };
+    let content = serde_json::to_string_pretty(&keystore).map_err(|_| Error::SerializationIssue)?;
+    write_private_file(path, content.as_bytes()).map_err(|e| {
+        log!(Level::Error, "Failed to write the keystore {}: {}", path, e);
+        Error::TechnicalIssue
+    })
+}
+
+/// Unlocks the HD seed at startup. With `MNEMONIC_KEYSTORE_FILE` set, the
+/// keystore is decrypted with `MNEMONIC_KEYSTORE_PASSPHRASE`, or a
+/// passphrase typed on the terminal. The unsealed `MNEMONIC_PHRASE`
+/// environment variable is only used with `ALLOW_UNSEALED_MNEMONIC=true`,
+/// otherwise startup is refused. Without either Solana wallets cannot be
+/// derived.
+pub fn unlock_hd_seed() -> Result<(), Error> {
+    let phrase = if let Ok(path) = env::var(KEYSTORE_FILE_ENV) {
+        let passphrase = read_passphrase(KEYSTORE_PASSPHRASE_ENV, "Keystore passphrase: ")?;
+        let phrase = open_keystore(&path, passphrase.as_bytes())?;
+        String::from_utf8(phrase.to_vec()).map(Zeroizing::new).map_err(|_| Error::InvalidConfiguration)?
+    } else if let Ok(phrase) = env::var(MNEMONIC_ENV) {
+        let phrase = Zeroizing::new(phrase);
+        if !unsealed_mnemonic_allowed(env::var(ALLOW_UNSEALED_MNEMONIC_ENV).ok().as_deref())? {
+            log!(Level::Error, "{} is set but unsealed, seal it into {} or set {}=true for local development", MNEMONIC_ENV, KEYSTORE_FILE_ENV, ALLOW_UNSEALED_MNEMONIC_ENV);
+            return Err(Error::InvalidConfiguration);
+        }
+        log!(Level::Warn, "Using the unsealed {} environment variable, prefer a keystore in {}", MNEMONIC_ENV, KEYSTORE_FILE_ENV);
+        phrase
+    } else {
+        log!(Level::Warn, "No HD mnemonic configured, Solana wallets can not be derived");
+        return Ok(());
+    };
+
+    let seed = Zeroizing::new(parse_mnemonic(&phrase)?.to_seed(""));
+    let _ = HD_SEED.set(seed);
+    log!(Level::Info, "HD seed unlocked");
+    Ok(())
+}
+
+/// Whether `ALLOW_UNSEALED_MNEMONIC`, `value` when set, opts in to the
+/// unsealed `MNEMONIC_PHRASE`. Only `true` or `false` are accepted.
+fn unsealed_mnemonic_allowed(value: Option<&str>) -> Result<bool, Error> {
+    match value.map(|value| value.trim().to_lowercase()).as_deref() {
+        None | Some("false") => Ok(false),
+        Some("true") => Ok(true),
+        Some(_) => {
+            log!(Level::Error, "Invalid {} {:?}, expected true or false", ALLOW_UNSEALED_MNEMONIC_ENV, value);
+            Err(Error::InvalidConfiguration)
+        }
+    }
+}
+
+/// Seed of the HD wallet unlocked by [`unlock_hd_seed`].
+pub fn hd_seed() -> Result<&'static [u8; 64], Error> {
+    HD_SEED.get().map(|seed| &**seed).ok_or_else(|| {
+        log!(Level::Error, "HD seed is not unlocked, set {} or {}", KEYSTORE_FILE_ENV, MNEMONIC_ENV);
+        Error::InvalidConfiguration
+    })
+}
+
+/// Seals a mnemonic typed on the terminal into a new keystore at `path`,
+/// protected by a passphrase typed twice (or `MNEMONIC_KEYSTORE_PASSPHRASE`).
+pub fn seal_mnemonic(path: &str) -> Result<(), Error> {
+    let phrase = rpassword::prompt_password("Mnemonic phrase: ").map(Zeroizing::new).map_err(|e| {
+        log!(Level::Error, "Failed to read the mnemonic: {}", e);
+        Error::TechnicalIssue
+    })?;
+    parse_mnemonic(&phrase)?;
+
+    let passphrase = read_new_passphrase(KEYSTORE_PASSPHRASE_ENV)?;
+    seal_keystore(path, passphrase.as_bytes(), phrase.trim().as_bytes())?;
+    log!(Level::Info, "Mnemonic sealed into {}", path);
+    Ok(())
+}
+
+/// Path of the keystore holding the keypair of `address` in `dir`. Taking a
+/// parsed `Pubkey` keeps addresses read from the database, such as
+/// `../../x`, from naming a file outside `dir`.
+fn keypair_keystore_path(dir: &str, address: &Pubkey) -> String {
+    format!("{}/{}.json", dir.trim_end_matches('/'), address)
+}
+
+/// Seals the Solana keypair file at `keypair_path` into `dir`, named after
+/// its address, with a passphrase typed twice (or `SIGNER_KEYSTORE_PASSPHRASE`).
+pub fn seal_keypair(keypair_path: &str, dir: &str) -> Result<(), Error> {
+    let keypair = read_keypair_file(keypair_path).map_err(|e| {
+        log!(Level::Error, "Failed to read the keypair {}: {}", keypair_path, e);
+        Error::InvalidConfiguration
+    })?;
+    let passphrase = read_new_passphrase(SIGNER_KEYSTORE_PASSPHRASE_ENV)?;
+    let path = keypair_keystore_path(dir, &keypair.pubkey());
+    seal_keystore(&path, passphrase.as_bytes(), Zeroizing::new(keypair.to_bytes()).as_ref())?;
+    log!(Level::Info, "Keypair {} sealed into {}", keypair.pubkey(), path);
+    Ok(())
+}
+
+/// Opens the keypair of `address` sealed in `dir` by [`seal_keypair`], with
+/// the passphrase in `SIGNER_KEYSTORE_PASSPHRASE`.
+pub fn open_keypair(dir: &str, address: &Pubkey) -> Result<Keypair, Error> {
+    let passphrase = env::var(SIGNER_KEYSTORE_PASSPHRASE_ENV).map(Zeroizing::new).map_err(|_| {
+        log!(Level::Error, "{} is not set in .env file", SIGNER_KEYSTORE_PASSPHRASE_ENV);
+        Error::EnvVarMissing(SIGNER_KEYSTORE_PASSPHRASE_ENV.to_string())
+    })?;
+    open_keypair_with(dir, address, passphrase.as_bytes())
+}
+
+fn open_keypair_with(dir: &str, address: &Pubkey, passphrase: &[u8]) -> Result<Keypair, Error> {
+    let secret = open_keystore(&keypair_keystore_path(dir, address), passphrase)?;
+    let keypair = Keypair::try_from(secret.as_slice()).map_err(|e| {
+        log!(Level::Error, "Keystore of {} does not hold a keypair: {}", address, e);
+        Error::InvalidConfiguration
+    })?;
+    if keypair.pubkey() != *address {
+        log!(Level::Error, "Keystore of {} holds the keypair of {}", address, keypair.pubkey());
+        return Err(Error::InvalidConfiguration);
+    }
+    Ok(keypair)
+}
+
+fn write_private_file(path: &str, content: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory for the keystores of one test.
+    fn temp_dir(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("keystore_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn opens_what_it_seals() {
+        let path = format!("{}/seed.json", temp_dir("round_trip"));
+        seal_keystore(&path, b"passphrase", b"mnemonic words").unwrap();
+        assert_eq!(open_keystore(&path, b"passphrase").unwrap().as_slice(), b"mnemonic words");
+        // An existing keystore is never overwritten
+        assert!(matches!(seal_keystore(&path, b"passphrase", b"other"), Err(Error::TechnicalIssue)));
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase_or_a_tampered_file() {
+        let path = format!("{}/seed.json", temp_dir("tampered"));
+        seal_keystore(&path, b"passphrase", b"mnemonic words").unwrap();
+        assert!(matches!(open_keystore(&path, b"wrong"), Err(Error::InvalidConfiguration)));
+
+        let mut keystore: KeystoreFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let mut ciphertext = hex::decode(&keystore.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        keystore.ciphertext = hex::encode(ciphertext);
+        fs::write(&path, serde_json::to_string(&keystore).unwrap()).unwrap();
+        assert!(matches!(open_keystore(&path, b"passphrase"), Err(Error::InvalidConfiguration)));
+    }
+
+    #[test]
+    fn opens_the_keypair_of_its_address_only() {
+        let dir = temp_dir("keypair");
+        let keypair = Keypair::new();
+        let address = keypair.pubkey();
+        seal_keystore(&keypair_keystore_path(&dir, &address), b"passphrase", &keypair.to_bytes()).unwrap();
+        assert_eq!(open_keypair_with(&dir, &address, b"passphrase").unwrap().pubkey(), keypair.pubkey());
+        assert!(matches!(open_keypair_with(&dir, &address, b"wrong"), Err(Error::InvalidConfiguration)));
+
+        // A keystore renamed after another address is refused
+        let other = Keypair::new().pubkey();
+        fs::copy(keypair_keystore_path(&dir, &address), keypair_keystore_path(&dir, &other)).unwrap();
+        assert!(matches!(open_keypair_with(&dir, &other, b"passphrase"), Err(Error::InvalidConfiguration)));
+    }
+
+    #[test]
+    fn unsealed_mnemonic_needs_an_explicit_opt_in() {
+        assert!(!unsealed_mnemonic_allowed(None).unwrap());
+        assert!(!unsealed_mnemonic_allowed(Some("false")).unwrap());
+        assert!(unsealed_mnemonic_allowed(Some(" TRUE ")).unwrap());
+        assert!(matches!(unsealed_mnemonic_allowed(Some("1")), Err(Error::InvalidConfiguration)));
+    }
+}
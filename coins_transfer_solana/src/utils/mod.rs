@@ -1,6 +1,8 @@
 pub mod decimal_functions;
+pub mod encryption;
+pub mod keystore;
 pub mod request_validation;
 pub mod rpc;
 pub mod struct_validation;
 pub mod time;
-pub mod transactions_lock;
\ No newline at end of file
+pub mod transactions_lock;
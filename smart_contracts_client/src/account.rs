@@ -0,0 +1,101 @@
+use anyhow::{Context, Result, bail};
+use borsh::BorshDeserialize;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::RpcFilterType;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::state::Mint;
+
+use smart_contracts_types::state::UserAccount;
+
+use crate::pda::find_user_address;
+
+/// Decodes the raw data of a registry PDA into a `UserAccount`.
+pub fn decode_user_account(data: &[u8]) -> Result<UserAccount> {
+    if data.len() < UserAccount::LEN {
+        bail!("account data is {} bytes, expected {}", data.len(), UserAccount::LEN);
+    }
+    UserAccount::try_from_slice(&data[..UserAccount::LEN]).context("failed to decode UserAccount")
+}
+
+/// Fetches and decodes the `UserAccount` stored at `address`.
+/// Returns `None` if the account does not exist.
+pub fn fetch_user_account_at(rpc: &RpcClient, program_id: &Pubkey, address: &Pubkey) -> Result<Option<UserAccount>> {
+    let Some(account) = rpc.get_account_with_commitment(address, rpc.commitment())?.value else {
+        return Ok(None);
+    };
+    if account.owner != *program_id {
+        bail!("account {} is owned by {}, not the registry program", address, account.owner);
+    }
+    decode_user_account(&account.data).map(Some)
+}
+
+/// Fetches the `UserAccount` registered for `owner`.
+pub fn fetch_user_account(rpc: &RpcClient, program_id: &Pubkey, owner: &Pubkey) -> Result<Option<UserAccount>> {
+    let (user_pda, _) = find_user_address(program_id, owner);
+    fetch_user_account_at(rpc, program_id, &user_pda)
+}
+
+/// Fetches every `UserAccount` owned by the registry program, keyed by PDA.
+pub fn fetch_all_user_accounts(rpc: &RpcClient, program_id: &Pubkey) -> Result<Vec<(Pubkey, UserAccount)>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::DataSize(UserAccount::LEN as u64)]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(rpc.commitment()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    rpc.get_program_accounts_with_config(program_id, config)?
+        .into_iter()
+        .map(|(address, account)| decode_user_account(&account.data).with_context(|| format!("failed to decode user account {}", address)).map(|user| (address, user)))
+        .collect()
+}
+
+/// Reads the number of decimals configured on an SPL token `mint`.
+pub fn fetch_mint_decimals(rpc: &RpcClient, mint: &Pubkey) -> Result<u8> {
+    let account = rpc.get_account(mint).with_context(|| format!("failed to fetch mint {}", mint))?;
+    let mint_state = Mint::unpack(&account.data).with_context(|| format!("account {} is not an SPL token mint", mint))?;
+    Ok(mint_state.decimals)
+}
+
+/// Reads the balance `ValidateTxn` compares: the token amount of an SPL token
+/// account, the lamports of any other account (0 when it does not exist).
+pub fn fetch_validated_balance(rpc: &RpcClient, address: &Pubkey) -> Result<u64> {
+    let Some(account) = rpc.get_account_with_commitment(address, rpc.commitment())?.value else {
+        return Ok(0);
+    };
+    if account.owner == spl_token::id() {
+        let token_account = spl_token::state::Account::unpack(&account.data).with_context(|| format!("account {} is not an SPL token account", address))?;
+        return Ok(token_account.amount);
+    }
+    Ok(account.lamports)
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::to_vec;
+
+    use super::*;
+
+    #[test]
+    fn decodes_user_accounts_and_ignores_trailing_bytes() {
+        let owner = Pubkey::new_unique();
+        let mut data = to_vec(&UserAccount { is_initialized: true, owner, created_at: 1_700_000_000 }).unwrap();
+        assert_eq!(data.len(), UserAccount::LEN);
+        data.extend_from_slice(&[0; 8]);
+        let user = decode_user_account(&data).unwrap();
+        assert!(user.is_initialized);
+        assert_eq!(user.owner, owner);
+        assert_eq!(user.created_at, 1_700_000_000);
+    }
+
+    #[test]
+    fn rejects_short_account_data() {
+        let err = decode_user_account(&[1; 40]).unwrap_err();
+        assert_eq!(err.to_string(), "account data is 40 bytes, expected 41");
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result, bail};
+
+/// Decimals of native SOL (1 SOL = 10^9 lamports).
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Converts a UI amount such as `"0.001"` into base units of a token with
+/// `decimals` decimals. Parsed from the string to avoid float rounding.
+pub fn ui_amount_to_base_units(amount: &str, decimals: u8) -> Result<u64> {
+    let amount = amount.trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        bail!("invalid amount `{}`", amount);
+    }
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        bail!("invalid amount `{}`", amount);
+    }
+    if fraction.len() > decimals as usize {
+        bail!("amount `{}` has more than {} decimal places", amount, decimals);
+    }
+
+    let scale = 10u64.checked_pow(decimals as u32).context("too many decimals")?;
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().with_context(|| format!("amount `{}` is too large", amount))? };
+    let fraction: u64 = if fraction.is_empty() { 0 } else { format!("{:0<width$}", fraction, width = decimals as usize).parse()? };
+
+    whole
+        .checked_mul(scale)
+        .and_then(|units| units.checked_add(fraction))
+        .with_context(|| format!("amount `{}` is too large", amount))
+}
+
+/// Formats base units of a token with `decimals` decimals as a UI amount.
+pub fn base_units_to_ui_amount(units: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return units.to_string();
+    }
+    let scale = 10u128.pow(decimals as u32);
+    let whole = units as u128 / scale;
+    let fraction = format!("{:0>width$}", units as u128 % scale, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() { whole.to_string() } else { format!("{}.{}", whole, fraction) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whole_and_fractional_amounts() {
+        assert_eq!(ui_amount_to_base_units("1", SOL_DECIMALS).unwrap(), 1_000_000_000);
+        assert_eq!(ui_amount_to_base_units("0.001", SOL_DECIMALS).unwrap(), 1_000_000);
+        assert_eq!(ui_amount_to_base_units(".5", 6).unwrap(), 500_000);
+        assert_eq!(ui_amount_to_base_units("2.", 6).unwrap(), 2_000_000);
+        assert_eq!(ui_amount_to_base_units(" 7 ", 0).unwrap(), 7);
+    }
+
+    #[test]
+    fn rejects_empty_negative_and_malformed_amounts() {
+        for amount in ["", " ", ".", "-1", "-0.5", "+1", "1e3", "1.2.3", "abc"] {
+            assert!(ui_amount_to_base_units(amount, SOL_DECIMALS).is_err(), "accepted `{}`", amount);
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_decimals() {
+        assert!(ui_amount_to_base_units("0.0000000001", SOL_DECIMALS).is_err());
+        assert!(ui_amount_to_base_units("1.5", 0).is_err());
+        assert!(ui_amount_to_base_units("1", 20).is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(ui_amount_to_base_units("18446744073709551615", 0).unwrap(), u64::MAX);
+        assert!(ui_amount_to_base_units("18446744073709551616", 0).is_err());
+        assert!(ui_amount_to_base_units("18446744074", SOL_DECIMALS).is_err());
+        assert!(ui_amount_to_base_units("18446744073.709551616", SOL_DECIMALS).is_err());
+    }
+
+    #[test]
+    fn formats_base_units() {
+        assert_eq!(base_units_to_ui_amount(1_000_000, SOL_DECIMALS), "0.001");
+        assert_eq!(base_units_to_ui_amount(2_000_000_000, SOL_DECIMALS), "2");
+        assert_eq!(base_units_to_ui_amount(u64::MAX, 0), u64::MAX.to_string());
+        assert_eq!(base_units_to_ui_amount(u64::MAX, 19), "1.8446744073709551615");
+        for units in [0, 1, 999_999, 123_456_789_012] {
+            assert_eq!(ui_amount_to_base_units(&base_units_to_ui_amount(units, 6), 6).unwrap(), units);
+        }
+    }
+}
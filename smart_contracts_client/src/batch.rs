@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{Context as _, Result, bail};
+use serde::{Deserialize, Serialize};
+use solana_packet::PACKET_DATA_SIZE;
+use solana_sdk::hash::{Hash, hash};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+
+use smart_contracts_client::account::fetch_mint_decimals;
+use smart_contracts_client::amount::{SOL_DECIMALS, ui_amount_to_base_units};
+use smart_contracts_client::fees::compute_budget_ixs;
+use smart_contracts_client::instruction::{transfer_sol_ix, transfer_spl_ix};
+
+use crate::cli::OutputFormat;
+use crate::context::{Context, UnconfirmedTx};
+use crate::output::{AlreadyReported, Report};
+
+/// Compute units budgeted per transfer when packing, kept above what the
+/// registry program uses so a full transaction stays under the limit.
+const SOL_TRANSFER_UNITS: u32 = 10_000;
+const SPL_TRANSFER_UNITS: u32 = 30_000;
+/// Most compute units a single transaction may request, unless lowered
+/// with `--compute-unit-limit`.
+const MAX_TX_UNITS: u32 = 1_400_000;
+/// First line of a checkpoint file, followed by the hash of its input.
+const CHECKPOINT_HEADER: &str = "# input";
+
+pub struct BatchOptions {
+    pub input: PathBuf,
+    pub results: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub max_per_tx: usize,
+    pub concurrency: usize,
+}
+
+/// One line of the input CSV. `mint` is left empty for SOL transfers.
+#[derive(Debug, Deserialize)]
+struct InputRow {
+    kind: String,
+    recipient: String,
+    #[serde(default)]
+    mint: String,
+    amount: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ResultRow<'a> {
+    row: usize,
+    kind: &'a str,
+    recipient: &'a str,
+    mint: &'a str,
+    amount: &'a str,
+    status: &'static str,
+    signature: String,
+    error: String,
+}
+
+/// What happened to the transaction carrying a row.
+enum RowOutcome {
+    Sent { signature: String },
+    /// Broadcast but not confirmed; it may still land, so the row is not retried.
+    Unknown { signature: String, error: String },
+    /// Failed or expired without landing, safe to send again.
+    Failed { error: String },
+}
+
+/// A transfer ready to be packed; `row` is its 1-based position in the input.
+struct Transfer {
+    row: usize,
+    input: InputRow,
+    ix: Instruction,
+    units: u32,
+}
+
+/// Output files shared by the worker threads.
+struct Sink {
+    results: csv::Writer<File>,
+    checkpoint: File,
+    sent: usize,
+    unknown: usize,
+    failed: usize,
+}
+
+impl Sink {
+    fn record(&mut self, row: usize, input: &InputRow, outcome: &RowOutcome) -> Result<()> {
+        let (status, signature, error) = match outcome {
+            RowOutcome::Sent { signature } => ("ok", signature.clone(), String::new()),
+            RowOutcome::Unknown { signature, error } => ("unknown", signature.clone(), error.clone()),
+            RowOutcome::Failed { error } => ("failed", String::new(), error.clone()),
+        };
+        self.results.serialize(ResultRow {
+            row,
+            kind: &input.kind,
+            recipient: &input.recipient,
+            mint: &input.mint,
+            amount: &input.amount,
+            status,
+            signature,
+            error,
+        })?;
+        self.results.flush()?;
+        match outcome {
+            RowOutcome::Sent { .. } => self.sent += 1,
+            RowOutcome::Unknown { .. } => self.unknown += 1,
+            RowOutcome::Failed { .. } => {
+                self.failed += 1;
+                return Ok(());
+            }
+        }
+        writeln!(self.checkpoint, "{}", row)?;
+        self.checkpoint.flush()?;
+        Ok(())
+    }
+}
+
+/// Sends the transfers listed in a CSV file, several per transaction and
+/// several transactions at a time. Rows already listed in the checkpoint
+/// file are skipped, so rerunning the same command resumes the batch. Rows
+/// whose transaction was sent but not confirmed are checkpointed too, since
+/// sending them again could pay twice.
+pub fn run(ctx: &Context, options: BatchOptions) -> Result<()> {
+    if ctx.sign_only || ctx.simulate || ctx.nonce.is_some() {
+        bail!("batch sends its transactions directly and cannot be combined with --sign-only, --simulate or --nonce");
+    }
+    if options.max_per_tx == 0 || options.concurrency == 0 {
+        bail!("--max-per-tx and --concurrency must be at least 1");
+    }
+    let program_id = ctx.program_id()?;
+    let results_path = options.results.unwrap_or_else(|| options.input.with_extension("results.csv"));
+    let checkpoint_path = options.checkpoint.unwrap_or_else(|| options.input.with_extension("checkpoint"));
+
+    let input_hash = hash(&fs::read(&options.input).with_context(|| format!("failed to read {}", options.input.display()))?);
+    let done = read_checkpoint(&checkpoint_path, &input_hash)?;
+    let mut sink = Sink {
+        results: open_results(&results_path, &done)?,
+        checkpoint: open_checkpoint(&checkpoint_path, &input_hash)?,
+        sent: 0,
+        unknown: 0,
+        failed: 0,
+    };
+
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(&options.input).with_context(|| format!("failed to read {}", options.input.display()))?;
+    let mut decimals = HashMap::new();
+    let mut transfers = Vec::new();
+    let mut skipped = 0;
+    for (index, record) in reader.deserialize::<InputRow>().enumerate() {
+        let row = index + 1;
+        if done.contains(&row) {
+            skipped += 1;
+            continue;
+        }
+        let input = record.with_context(|| format!("invalid CSV row {}", row))?;
+        match build_transfer(ctx, &program_id, &input, &mut decimals) {
+            Ok((ix, units)) => transfers.push(Transfer { row, input, ix, units }),
+            Err(err) => sink.record(row, &input, &RowOutcome::Failed { error: format!("{:#}", err) })?,
+        }
+    }
+
+    let max_units = ctx.compute_unit_limit.map_or(MAX_TX_UNITS, |limit| limit.min(MAX_TX_UNITS));
+    let batches = pack(&ctx.fee_payer_pubkey()?, transfers, options.max_per_tx, max_units);
+    let transactions = batches.len();
+    eprintln!("Sending {} transaction(s), {} row(s) already done", transactions, skipped);
+
+    let queue = Mutex::new(batches.into_iter());
+    let sink = Mutex::new(sink);
+    thread::scope(|scope| -> Result<()> {
+        let workers: Vec<_> = (0..options.concurrency)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some(batch) = queue.lock().unwrap().next() {
+                        send_batch(ctx, &batch, &sink)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        workers.into_iter().try_for_each(|worker| worker.join().unwrap())
+    })?;
+
+    let sink = sink.into_inner().unwrap();
+    Report::new()
+        .field("transactions", transactions)
+        .field("sent", sink.sent)
+        .field("unknown", sink.unknown)
+        .field("failed", sink.failed)
+        .field("skipped", skipped)
+        .field("results", results_path.display().to_string())
+        .print(ctx.output);
+    if sink.failed > 0 || sink.unknown > 0 {
+        if let OutputFormat::Json = ctx.output {
+            return Err(AlreadyReported.into());
+        }
+        let mut problems = Vec::new();
+        if sink.unknown > 0 {
+            problems.push(format!(
+                "{} row(s) were sent but not confirmed, check their signatures in {} before sending them again, a rerun skips them",
+                sink.unknown,
+                results_path.display()
+            ));
+        }
+        if sink.failed > 0 {
+            problems.push(format!("{} row(s) failed, rerun the same command to retry them", sink.failed));
+        }
+        bail!("{}", problems.join("; "));
+    }
+    Ok(())
+}
+
+fn send_batch(ctx: &Context, batch: &[Transfer], sink: &Mutex<Sink>) -> Result<()> {
+    let rows = batch.iter().map(|transfer| transfer.row.to_string()).collect::<Vec<_>>().join(", ");
+    let outcome = match ctx.send_tx(batch.iter().map(|transfer| transfer.ix.clone()).collect()) {
+        Ok(sent) => {
+            let signature = sent.map(|sent| sent.signature.to_string()).unwrap_or_default();
+            eprintln!("Rows {}: {}", rows, signature);
+            RowOutcome::Sent { signature }
+        }
+        Err(err) => match err.downcast_ref::<UnconfirmedTx>() {
+            Some(unconfirmed) => {
+                eprintln!("Rows {} unconfirmed: {:#}", rows, err);
+                RowOutcome::Unknown { signature: unconfirmed.signature.to_string(), error: format!("{:#}", err) }
+            }
+            None => {
+                eprintln!("Rows {} failed: {:#}", rows, err);
+                RowOutcome::Failed { error: format!("{:#}", err) }
+            }
+        },
+    };
+    let mut sink = sink.lock().unwrap();
+    for transfer in batch {
+        sink.record(transfer.row, &transfer.input, &outcome)?;
+    }
+    Ok(())
+}
+
+/// Builds the registry instruction for one row and its compute unit estimate.
+fn build_transfer(ctx: &Context, program_id: &Pubkey, input: &InputRow, decimals: &mut HashMap<Pubkey, u8>) -> Result<(Instruction, u32)> {
+    let recipient = Pubkey::from_str(&input.recipient).context("invalid recipient")?;
+    match input.kind.to_ascii_lowercase().as_str() {
+        "sol" => {
+            let lamports = ui_amount_to_base_units(&input.amount, SOL_DECIMALS)?;
+            Ok((transfer_sol_ix(program_id, &ctx.payer_pubkey()?, &recipient, lamports), SOL_TRANSFER_UNITS))
+        }
+        "spl" => {
+            let mint = Pubkey::from_str(&input.mint).context("invalid mint")?;
+            let mint_decimals = match decimals.get(&mint) {
+                Some(mint_decimals) => *mint_decimals,
+                None => {
+                    let mint_decimals = fetch_mint_decimals(&ctx.rpc, &mint)?;
+                    decimals.insert(mint, mint_decimals);
+                    mint_decimals
+                }
+            };
+            let units = ui_amount_to_base_units(&input.amount, mint_decimals)?;
+            Ok((transfer_spl_ix(program_id, &ctx.payer_pubkey()?, &recipient, &mint, units), SPL_TRANSFER_UNITS))
+        }
+        kind => bail!("unknown kind `{}`, expected `sol` or `spl`", kind),
+    }
+}
+
+/// Groups transfers in input order into transactions that stay under the
+/// packet size, `max_units` compute units and `max_per_tx` instructions.
+fn pack(fee_payer: &Pubkey, transfers: Vec<Transfer>, max_per_tx: usize, max_units: u32) -> Vec<Vec<Transfer>> {
+    let mut batches: Vec<Vec<Transfer>> = Vec::new();
+    let mut current: Vec<Transfer> = Vec::new();
+    let mut units = 0;
+    for transfer in transfers {
+        if !current.is_empty() {
+            let mut ixs: Vec<Instruction> = current.iter().map(|t| t.ix.clone()).collect();
+            ixs.push(transfer.ix.clone());
+            if current.len() >= max_per_tx || units + transfer.units > max_units || !fits_in_packet(fee_payer, &ixs) {
+                batches.push(std::mem::take(&mut current));
+                units = 0;
+            }
+        }
+        units += transfer.units;
+        current.push(transfer);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// Whether a signed transaction with `instructions`, plus room for the
+/// compute budget instructions, fits in one packet.
+fn fits_in_packet(fee_payer: &Pubkey, instructions: &[Instruction]) -> bool {
+    let mut ixs = compute_budget_ixs(Some(0), Some(0));
+    ixs.extend_from_slice(instructions);
+    let tx = Transaction::new_unsigned(Message::new(&ixs, Some(fee_payer)));
+    bincode::serialized_size(&tx).is_ok_and(|size| size as usize <= PACKET_DATA_SIZE)
+}
+
+/// Rows listed in the checkpoint at `path`. Row numbers only identify
+/// transfers of the file they were written for, so a checkpoint written for
+/// different input contents is rejected.
+fn read_checkpoint(path: &Path, input_hash: &Hash) -> Result<BTreeSet<usize>> {
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let contents = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut lines = contents.lines().map(str::trim).filter(|line| !line.is_empty());
+    match lines.next().map(|line| line.strip_prefix(CHECKPOINT_HEADER).map(str::trim)) {
+        None => return Ok(BTreeSet::new()),
+        Some(Some(hash)) if hash == input_hash.to_string() => {}
+        Some(_) => bail!(
+            "checkpoint {} was written for different input contents, rows may already have been paid; restore the original input or pass a new --checkpoint",
+            path.display()
+        ),
+    }
+    lines.map(|line| line.parse().with_context(|| format!("invalid row `{}` in {}", line, path.display()))).collect()
+}
+
+/// Opens the checkpoint for appending, writing the input hash to a new file.
+fn open_checkpoint(path: &Path, input_hash: &Hash) -> Result<File> {
+    let is_new = fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+    let mut file = OpenOptions::new().create(true).append(true).open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    if is_new {
+        writeln!(file, "{} {}", CHECKPOINT_HEADER, input_hash)?;
+    }
+    Ok(file)
+}
+
+/// Opens the results CSV for appending, writing the header only to a new
+/// file. Rows of an earlier run that are not in the checkpoint `done` are
+/// sent again, so the file is first rewritten with only the last result of
+/// each checkpointed row.
+fn open_results(path: &Path, done: &BTreeSet<usize>) -> Result<csv::Writer<File>> {
+    let is_new = fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+    if !is_new {
+        let mut reader = csv::Reader::from_path(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let mut kept = BTreeMap::new();
+        for record in reader.records() {
+            let record = record.with_context(|| format!("failed to read {}", path.display()))?;
+            if let Some(row) = record.get(0).and_then(|row| row.parse::<usize>().ok()).filter(|row| done.contains(row)) {
+                kept.insert(row, record);
+            }
+        }
+        let rewritten = path.with_extension("csv.tmp");
+        let mut writer = csv::Writer::from_path(&rewritten).with_context(|| format!("failed to write {}", rewritten.display()))?;
+        writer.write_record(reader.headers()?)?;
+        for record in kept.values() {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        fs::rename(&rewritten, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(csv::WriterBuilder::new().has_headers(is_new).from_writer(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfers(count: usize) -> Vec<Transfer> {
+        (1..=count)
+            .map(|row| Transfer {
+                row,
+                input: InputRow { kind: "sol".to_string(), recipient: String::new(), mint: String::new(), amount: "1".to_string() },
+                ix: transfer_sol_ix(&Pubkey::new_unique(), &Pubkey::new_unique(), &Pubkey::new_unique(), 1),
+                units: SOL_TRANSFER_UNITS,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packing_respects_the_compute_unit_limit() {
+        let fee_payer = Pubkey::new_unique();
+        let sizes = |batches: Vec<Vec<Transfer>>| batches.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes(pack(&fee_payer, transfers(5), 2, MAX_TX_UNITS)), vec![2, 2, 1]);
+        assert_eq!(sizes(pack(&fee_payer, transfers(5), 10, 3 * SOL_TRANSFER_UNITS)), vec![3, 2]);
+    }
+
+    #[test]
+    fn resuming_keeps_one_result_per_checkpointed_row() {
+        let path = std::env::temp_dir().join(format!("batch-{}.results.csv", std::process::id()));
+        fs::write(&path, "row,kind,recipient,mint,amount,status,signature,error\n1,sol,a,,1,ok,sig1,\n2,sol,b,,1,failed,,boom\n3,sol,c,,1,unknown,sig3,timeout\n").unwrap();
+
+        let mut writer = open_results(&path, &BTreeSet::from([1, 3])).unwrap();
+        writer.write_record(["2", "sol", "b", "", "1", "ok", "sig2", ""]).unwrap();
+        writer.flush().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents, "row,kind,recipient,mint,amount,status,signature,error\n1,sol,a,,1,ok,sig1,\n3,sol,c,,1,unknown,sig3,timeout\n2,sol,b,,1,ok,sig2,\n");
+    }
+}
@@ -0,0 +1,292 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use solana_commitment_config::CommitmentLevel;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+
+use smart_contracts_client::fees::DEFAULT_PRIORITY_FEE_PERCENTILE;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Command-line client for the user registry program")]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    /// Simulate the transaction and print compute units, balance changes and program logs instead of sending it
+    #[arg(long, global = true)]
+    pub simulate: bool,
+
+    /// Print results as a table or as JSON
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+
+    #[command(flatten)]
+    pub signing: SigningArgs,
+
+    #[command(flatten)]
+    pub fees: FeeArgs,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Solana CLI configuration file [default: ~/.config/solana/cli/config.yml]
+    #[arg(short = 'C', long = "config", global = true, value_name = "PATH")]
+    pub config_file: Option<String>,
+
+    /// RPC URL or moniker (mainnet-beta, testnet, devnet, localhost), overrides the config file
+    #[arg(short = 'u', long = "url", global = true, value_name = "URL_OR_MONIKER")]
+    pub json_rpc_url: Option<String>,
+
+    /// Payer keypair file, or its pubkey when it signs elsewhere, overrides the config file
+    #[arg(short = 'k', long, env = "KEYPAIR", global = true, value_name = "KEYPAIR_OR_PUBKEY")]
+    pub keypair: Option<String>,
+
+    /// Registry program id
+    #[arg(long, env = "PROGRAM_ID", global = true)]
+    pub program_id: Option<Pubkey>,
+
+    /// Commitment used for queries and confirmation, overrides the config file [default: confirmed]
+    #[arg(long, global = true, value_enum)]
+    pub commitment: Option<Commitment>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<Commitment> for CommitmentLevel {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => CommitmentLevel::Processed,
+            Commitment::Confirmed => CommitmentLevel::Confirmed,
+            Commitment::Finalized => CommitmentLevel::Finalized,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ConfirmArgs {
+    /// Seconds between status checks, rebroadcasting the transaction at each
+    #[arg(long, global = true, value_name = "SECS", default_value_t = 2)]
+    pub rebroadcast_interval: u64,
+
+    /// Times to re-sign with a fresh blockhash when the transaction expires without landing
+    #[arg(long, global = true, value_name = "N", default_value_t = 3)]
+    pub max_resigns: usize,
+
+    /// Seconds to wait for durable nonce transactions, and for a landed transaction to reach the commitment
+    #[arg(long, global = true, value_name = "SECS", default_value_t = 60)]
+    pub confirm_timeout: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct SigningArgs {
+    /// Sign the transaction and print it instead of sending it, for offline signing (needs --blockhash)
+    #[arg(long, global = true)]
+    pub sign_only: bool,
+
+    /// Recent blockhash to sign with instead of fetching one from the cluster
+    #[arg(long, global = true)]
+    pub blockhash: Option<Hash>,
+
+    /// Fee payer keypair file, or a pubkey whose signature is added later with `submit` [default: the payer keypair]
+    #[arg(long, global = true, value_name = "KEYPAIR_OR_PUBKEY")]
+    pub fee_payer: Option<String>,
+
+    /// Additional keypair file to sign with, may be repeated
+    #[arg(long = "signer", global = true, value_name = "PATH")]
+    pub signers: Vec<String>,
+
+    /// Durable nonce account to use instead of a recent blockhash
+    #[arg(long, global = true, value_name = "PUBKEY")]
+    pub nonce: Option<Pubkey>,
+
+    /// Nonce authority keypair file, or its pubkey when it signs elsewhere [default: the payer keypair]
+    #[arg(long, global = true, value_name = "KEYPAIR_OR_PUBKEY")]
+    pub nonce_authority: Option<String>,
+
+    /// Encoding of transactions written by --sign-only
+    #[arg(long, global = true, value_enum, default_value_t = TxFormat::Base64)]
+    pub tx_format: TxFormat,
+
+    /// Write the --sign-only transaction to a file instead of stdout
+    #[arg(long, global = true, value_name = "PATH")]
+    pub out_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct FeeArgs {
+    /// Compute unit limit requested for the transaction
+    #[arg(long, global = true, value_name = "UNITS")]
+    pub compute_unit_limit: Option<u32>,
+
+    /// Priority fee in micro-lamports per compute unit
+    #[arg(long, global = true, value_name = "MICRO_LAMPORTS", conflicts_with = "auto_priority_fee")]
+    pub compute_unit_price: Option<u64>,
+
+    /// Set the compute unit price from recent prioritization fees paid for the writable accounts of the transaction
+    #[arg(long, global = true)]
+    pub auto_priority_fee: bool,
+
+    /// Percentile of the recent fees used by --auto-priority-fee
+    #[arg(long, global = true, value_name = "0-100", default_value_t = DEFAULT_PRIORITY_FEE_PERCENTILE, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub priority_fee_percentile: u8,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TxFormat {
+    Base64,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Derive the user PDA of the payer and send `RegisterUser`
+    RegisterUser,
+
+    /// Send `TransferSol` from the payer to a recipient
+    TransferSol {
+        /// Destination account pubkey
+        #[arg(long)]
+        recipient: Pubkey,
+        /// Amount in SOL, e.g. 0.001
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Send `TransferSpl` between the associated token accounts of the payer and a recipient
+    TransferSpl {
+        /// SPL token mint address
+        #[arg(long)]
+        mint: Pubkey,
+        /// Recipient's wallet pubkey
+        #[arg(long)]
+        recipient: Pubkey,
+        /// Amount in token units, converted using the mint decimals
+        #[arg(long)]
+        amount: String,
+        /// Decimals of the mint, skips fetching the mint (required with --sign-only)
+        #[arg(long)]
+        mint_decimals: Option<u8>,
+    },
+
+    /// Fetch the current balance of an account and send `ValidateTxn` against it
+    ValidateTxn {
+        /// Pubkey of the account to check
+        #[arg(long)]
+        account: Pubkey,
+        /// Balance to compare against, in lamports or token base units for token accounts; skips fetching it (required with --sign-only)
+        #[arg(long)]
+        pre_balance: Option<u64>,
+        /// Fail unless the balance is at least this much below the pre-balance
+        #[arg(long, default_value_t = 0)]
+        expected_decrease: u64,
+    },
+
+    /// Send the SOL and SPL transfers listed in a CSV file, several per transaction
+    Batch {
+        /// CSV with a `kind,recipient,mint,amount` header, `kind` being `sol` or `spl`
+        input: PathBuf,
+        /// CSV the status, signature and error of every row are appended to [default: <INPUT>.results.csv]
+        #[arg(long, value_name = "PATH")]
+        results: Option<PathBuf>,
+        /// File listing the rows already sent or left unconfirmed, used to resume an interrupted batch [default: <INPUT>.checkpoint]
+        #[arg(long, value_name = "PATH")]
+        checkpoint: Option<PathBuf>,
+        /// Most transfers packed into one transaction
+        #[arg(long, default_value_t = 8)]
+        max_per_tx: usize,
+        /// Transactions sent at the same time
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+
+    /// Show the registry account of a wallet
+    ShowUser {
+        /// Wallet pubkey the user PDA was registered for
+        owner: Pubkey,
+    },
+
+    /// List every user registered with the program
+    ListUsers,
+
+    /// Stream registry activity as it happens
+    Watch {
+        /// Poll getSignaturesForAddress instead of opening a logsSubscribe WebSocket
+        #[arg(long)]
+        poll: bool,
+        /// Seconds between polls
+        #[arg(long, value_name = "SECS", default_value_t = 5)]
+        poll_interval: u64,
+        /// WebSocket URL [default: derived from the RPC URL]
+        #[arg(long, value_name = "URL")]
+        ws_url: Option<String>,
+    },
+
+    /// Manage durable nonce accounts
+    #[command(subcommand)]
+    Nonce(NonceCommand),
+
+    /// Add signatures to a transaction written by --sign-only and broadcast it
+    Submit {
+        /// File holding the base64 or JSON transaction, `-` for stdin
+        #[arg(default_value = "-")]
+        transaction: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NonceCommand {
+    /// Create and initialize a nonce account funded by the payer
+    Create {
+        /// Keypair file of the new nonce account
+        #[arg(long, value_name = "PATH")]
+        nonce_keypair: PathBuf,
+        /// Amount in SOL to fund it with [default: the rent-exempt minimum]
+        #[arg(long)]
+        amount: Option<String>,
+        /// Authority of the new nonce account [default: --nonce-authority, else the payer]
+        #[arg(long)]
+        authority: Option<Pubkey>,
+    },
+
+    /// Advance the stored nonce value, signed by --nonce-authority
+    Advance {
+        /// Nonce account pubkey
+        nonce_account: Pubkey,
+    },
+
+    /// Show the authority, stored nonce and balance of a nonce account
+    Show {
+        /// Nonce account pubkey
+        nonce_account: Pubkey,
+    },
+
+    /// Withdraw SOL from a nonce account, signed by --nonce-authority
+    Withdraw {
+        /// Nonce account pubkey
+        nonce_account: Pubkey,
+        /// Account receiving the SOL
+        #[arg(long)]
+        destination: Pubkey,
+        /// Amount in SOL
+        #[arg(long)]
+        amount: String,
+    },
+}
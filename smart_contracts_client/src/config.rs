@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, read_keypair_file};
+
+use crate::cli::ConfigArgs;
+
+const DEFAULT_JSON_RPC_URL: &str = "http://127.0.0.1:8899";
+const DEFAULT_KEYPAIR_PATH: &str = "~/.config/solana/id.json";
+const DEFAULT_CONFIG_FILE: &str = "~/.config/solana/cli/config.yml";
+
+/// Subset of the Solana CLI `config.yml` the client understands.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SolanaCliConfig {
+    json_rpc_url: String,
+    keypair_path: String,
+    commitment: String,
+}
+
+/// Settings resolved from flags, then env, then the Solana CLI config, then defaults.
+#[derive(Debug)]
+pub struct ClientConfig {
+    pub json_rpc_url: String,
+    pub keypair_path: PathBuf,
+    pub commitment: CommitmentConfig,
+    pub program_id: Option<Pubkey>,
+}
+
+impl ClientConfig {
+    pub fn load(args: &ConfigArgs) -> Result<Self> {
+        let file = match &args.config_file {
+            Some(path) => load_cli_config(&expand_tilde(path))?.with_context(|| format!("config file {} not found", path))?,
+            None => load_cli_config(&expand_tilde(DEFAULT_CONFIG_FILE))?.unwrap_or_default(),
+        };
+
+        let json_rpc_url = args
+            .json_rpc_url
+            .as_deref()
+            .or_else(|| non_empty(&file.json_rpc_url))
+            .map(normalize_to_url_if_moniker)
+            .unwrap_or_else(|| DEFAULT_JSON_RPC_URL.to_string());
+
+        let keypair_path = args
+            .keypair
+            .as_deref()
+            .or_else(|| non_empty(&file.keypair_path))
+            .unwrap_or(DEFAULT_KEYPAIR_PATH);
+
+        let commitment = match (args.commitment, non_empty(&file.commitment)) {
+            (Some(level), _) => CommitmentConfig { commitment: level.into() },
+            (None, Some(level)) => CommitmentConfig::from_str(level).map_err(|_| anyhow!("invalid commitment `{}` in Solana CLI config", level))?,
+            (None, None) => CommitmentConfig::confirmed(),
+        };
+
+        Ok(Self {
+            json_rpc_url,
+            keypair_path: expand_tilde(keypair_path),
+            commitment,
+            program_id: args.program_id,
+        })
+    }
+}
+
+/// Reads a keypair file, expanding a leading `~/`.
+pub fn read_keypair_path(path: impl AsRef<Path>) -> Result<Keypair> {
+    let path = path.as_ref();
+    let path = path.to_str().map_or_else(|| path.to_path_buf(), expand_tilde);
+    read_keypair_file(&path).map_err(|e| anyhow!("failed to read keypair {}: {}", path.display(), e))
+}
+
+/// Maps the cluster monikers accepted by the Solana CLI to their RPC URLs.
+pub fn normalize_to_url_if_moniker(url_or_moniker: &str) -> String {
+    match url_or_moniker {
+        "m" | "mainnet-beta" | "mainnet" => "https://api.mainnet-beta.solana.com",
+        "t" | "testnet" => "https://api.testnet.solana.com",
+        "d" | "devnet" => "https://api.devnet.solana.com",
+        "l" | "localhost" => "http://localhost:8899",
+        url => url,
+    }
+    .to_string()
+}
+
+fn load_cli_config(path: &Path) -> Result<Option<SolanaCliConfig>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let config = serde_yaml::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(config))
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs_next::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    if value.is_empty() { None } else { Some(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_commitment_config::CommitmentLevel;
+
+    use super::*;
+    use crate::cli::Commitment;
+
+    /// Config file in the temp dir, removed when dropped.
+    struct TempConfig(PathBuf);
+
+    impl TempConfig {
+        fn path(&self) -> Option<String> {
+            Some(self.0.to_str().unwrap().to_string())
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_config(name: &str, contents: &str) -> TempConfig {
+        let path = std::env::temp_dir().join(format!("registry-client-{}-{}.yml", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        TempConfig(path)
+    }
+
+    fn args(config_file: Option<String>) -> ConfigArgs {
+        ConfigArgs { config_file, json_rpc_url: None, keypair: None, program_id: None, commitment: None }
+    }
+
+    #[test]
+    fn reads_the_solana_cli_config() {
+        let file = write_config("file", "json_rpc_url: devnet\nkeypair_path: /keys/payer.json\ncommitment: finalized\n");
+        let config = ClientConfig::load(&args(file.path())).unwrap();
+        assert_eq!(config.json_rpc_url, "https://api.devnet.solana.com");
+        assert_eq!(config.keypair_path, PathBuf::from("/keys/payer.json"));
+        assert_eq!(config.commitment.commitment, CommitmentLevel::Finalized);
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let file = write_config("flags", "json_rpc_url: devnet\nkeypair_path: /keys/payer.json\ncommitment: finalized\n");
+        let args = ConfigArgs { json_rpc_url: Some("http://rpc.example:8899".to_string()), keypair: Some("/keys/other.json".to_string()), commitment: Some(Commitment::Processed), ..args(file.path()) };
+        let config = ClientConfig::load(&args).unwrap();
+        assert_eq!(config.json_rpc_url, "http://rpc.example:8899");
+        assert_eq!(config.keypair_path, PathBuf::from("/keys/other.json"));
+        assert_eq!(config.commitment.commitment, CommitmentLevel::Processed);
+    }
+
+    #[test]
+    fn rejects_a_missing_file_or_an_invalid_commitment() {
+        assert!(ClientConfig::load(&args(Some("/nonexistent/config.yml".to_string()))).is_err());
+        let file = write_config("commitment", "commitment: eventually\n");
+        assert!(ClientConfig::load(&args(file.path())).is_err());
+    }
+
+    #[test]
+    fn normalizes_cluster_monikers() {
+        assert_eq!(normalize_to_url_if_moniker("m"), "https://api.mainnet-beta.solana.com");
+        assert_eq!(normalize_to_url_if_moniker("localhost"), "http://localhost:8899");
+        assert_eq!(normalize_to_url_if_moniker("http://custom:1234"), "http://custom:1234");
+    }
+}
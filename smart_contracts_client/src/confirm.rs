@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+/// How transactions are broadcast and confirmed.
+#[derive(Clone, Debug)]
+pub struct ConfirmConfig {
+    /// Commitment a transaction must reach to count as confirmed.
+    pub commitment: CommitmentConfig,
+    /// Delay between status checks; the transaction is rebroadcast at each one.
+    pub rebroadcast_interval: Duration,
+    /// How many times a transaction is re-signed with a fresh blockhash after
+    /// its blockhash expired or was not found.
+    pub max_resigns: usize,
+    /// How long to wait when the blockhash cannot expire (durable nonces), and
+    /// for a transaction seen by the cluster to reach `commitment`.
+    pub timeout: Duration,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::confirmed(),
+            rebroadcast_interval: Duration::from_secs(2),
+            max_resigns: 3,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Final state of a broadcast transaction.
+#[derive(Clone, Debug)]
+pub enum TxOutcome {
+    /// Reached the configured commitment.
+    Confirmed { signature: Signature, slot: u64 },
+    /// Executed and failed, or was rejected by preflight.
+    Failed { signature: Signature, error: TransactionError },
+    /// Its blockhash expired or was unknown and the cluster has no record of
+    /// it, so it can no longer land and is safe to send again.
+    Expired { signature: Signature },
+    /// Still unconfirmed when we stopped waiting. It may land later and must
+    /// not be treated as failed. Once the block height passes
+    /// `last_valid_block_height` without the signature being found, it can
+    /// no longer land; without a height only a lookup by signature tells.
+    Unknown { signature: Signature, last_valid_block_height: Option<u64> },
+}
+
+impl TxOutcome {
+    pub fn signature(&self) -> &Signature {
+        match self {
+            TxOutcome::Confirmed { signature, .. } | TxOutcome::Failed { signature, .. } | TxOutcome::Expired { signature } | TxOutcome::Unknown { signature, .. } => signature,
+        }
+    }
+}
+
+/// Fetches a blockhash, signs with `sign` and sends until confirmed. When the
+/// transaction expires without landing, it is signed again with a fresh
+/// blockhash, up to `config.max_resigns` times.
+pub async fn send_and_confirm_with_resign<F>(rpc: &RpcClient, config: &ConfirmConfig, mut sign: F) -> Result<TxOutcome>
+where
+    F: FnMut(Hash) -> Result<Transaction>,
+{
+    send_and_confirm_with_async_resign(rpc, config, |blockhash| std::future::ready(sign(blockhash))).await
+}
+
+/// Same as [`send_and_confirm_with_resign`] for signers that have to await,
+/// such as a remote signing service.
+pub async fn send_and_confirm_with_async_resign<F, Fut>(rpc: &RpcClient, config: &ConfirmConfig, mut sign: F) -> Result<TxOutcome>
+where
+    F: FnMut(Hash) -> Fut,
+    Fut: Future<Output = Result<Transaction>>,
+{
+    let mut attempt = 0;
+    loop {
+        let (blockhash, last_valid_block_height) = rpc.get_latest_blockhash_with_commitment(config.commitment).await?;
+        let tx = sign(blockhash).await?;
+        let outcome = send_and_confirm(rpc, &tx, Some(last_valid_block_height), config).await?;
+        match outcome {
+            TxOutcome::Expired { .. } if attempt < config.max_resigns => attempt += 1,
+            outcome => return Ok(outcome),
+        }
+    }
+}
+
+/// Sends an already signed `tx` and rebroadcasts it until it reaches the
+/// configured commitment, fails, or its blockhash passes
+/// `last_valid_block_height`. Without a block height (durable nonce or an
+/// unknown blockhash age) it waits up to `config.timeout`. The signature is
+/// looked up once more before anything other than `Confirmed` or `Failed`
+/// is returned.
+pub async fn send_and_confirm(rpc: &RpcClient, tx: &Transaction, last_valid_block_height: Option<u64>, config: &ConfirmConfig) -> Result<TxOutcome> {
+    let signature = tx.signatures[0];
+    let preflight = RpcSendTransactionConfig {
+        preflight_commitment: Some(config.commitment.commitment),
+        ..RpcSendTransactionConfig::default()
+    };
+    if let Err(err) = rpc.send_transaction_with_config(tx, preflight).await {
+        return match err.get_transaction_error() {
+            Some(TransactionError::BlockhashNotFound) => Ok(TxOutcome::Expired { signature }),
+            Some(TransactionError::AlreadyProcessed) => wait_for_commitment(rpc, &signature, last_valid_block_height, config).await,
+            Some(error) => Ok(TxOutcome::Failed { signature, error }),
+            None if may_have_been_sent(&err) => Ok(TxOutcome::Unknown { signature, last_valid_block_height }),
+            None => Err(err.into()),
+        };
+    }
+
+    let rebroadcast = RpcSendTransactionConfig {
+        skip_preflight: true,
+        ..RpcSendTransactionConfig::default()
+    };
+    let deadline = Instant::now() + config.timeout;
+    loop {
+        tokio::time::sleep(config.rebroadcast_interval).await;
+
+        let status = rpc.get_signature_statuses(&[signature]).await.ok().and_then(|response| response.value.into_iter().next().flatten());
+        if let Some(status) = status {
+            if let Some(error) = status.err {
+                return Ok(TxOutcome::Failed { signature, error });
+            }
+            if status.satisfies_commitment(config.commitment) {
+                return Ok(TxOutcome::Confirmed { signature, slot: status.slot });
+            }
+            // Landed in a block, so it no longer depends on the blockhash.
+            return wait_for_commitment(rpc, &signature, last_valid_block_height, config).await;
+        }
+
+        let expired = match last_valid_block_height {
+            Some(last_valid) => rpc.get_block_height_with_commitment(config.commitment).await.is_ok_and(|height| height > last_valid),
+            None => Instant::now() >= deadline,
+        };
+        if expired {
+            break;
+        }
+        // Failed rebroadcasts are retried on the next tick.
+        let _ = rpc.send_transaction_with_config(tx, rebroadcast).await;
+    }
+
+    match rpc.get_signature_statuses_with_history(&[signature]).await {
+        Ok(response) => match response.value.into_iter().next().flatten() {
+            Some(status) => match status.err {
+                Some(error) => Ok(TxOutcome::Failed { signature, error }),
+                None => wait_for_commitment(rpc, &signature, last_valid_block_height, config).await,
+            },
+            None if last_valid_block_height.is_some() => Ok(TxOutcome::Expired { signature }),
+            None => Ok(TxOutcome::Unknown { signature, last_valid_block_height }),
+        },
+        Err(_) => Ok(TxOutcome::Unknown { signature, last_valid_block_height }),
+    }
+}
+
+/// Polls a signature the cluster has already seen until it reaches the
+/// configured commitment, for at most `config.timeout`.
+async fn wait_for_commitment(rpc: &RpcClient, signature: &Signature, last_valid_block_height: Option<u64>, config: &ConfirmConfig) -> Result<TxOutcome> {
+    let deadline = Instant::now() + config.timeout;
+    while Instant::now() < deadline {
+        let status = rpc.get_signature_statuses_with_history(&[*signature]).await.ok().and_then(|response| response.value.into_iter().next().flatten());
+        if let Some(status) = status {
+            if let Some(error) = status.err {
+                return Ok(TxOutcome::Failed { signature: *signature, error });
+            }
+            if status.satisfies_commitment(config.commitment) {
+                return Ok(TxOutcome::Confirmed { signature: *signature, slot: status.slot });
+            }
+        }
+        tokio::time::sleep(config.rebroadcast_interval).await;
+    }
+    Ok(TxOutcome::Unknown { signature: *signature, last_valid_block_height })
+}
+
+/// Whether a failed `sendTransaction` call may still have reached the
+/// cluster: the request went out but no usable answer came back. A refused
+/// connection or an error returned by the node means it was not sent.
+fn may_have_been_sent(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Reqwest(err) => !err.is_connect() && !err.is_builder(),
+        ClientErrorKind::RpcError(_) | ClientErrorKind::SigningError(_) | ClientErrorKind::TransactionError(_) => false,
+        _ => true,
+    }
+}
@@ -0,0 +1,440 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use solana_client::nonce_utils;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+
+use smart_contracts_client::confirm::{ConfirmConfig, TxOutcome, send_and_confirm, send_and_confirm_with_resign};
+use smart_contracts_client::error::describe_transaction_error;
+use smart_contracts_client::fees::{compute_budget_ixs, recent_priority_fee, writable_accounts};
+
+use crate::cli::{Cli, OutputFormat, TxFormat};
+use crate::config::{ClientConfig, read_keypair_path};
+use crate::offline::{missing_signers, write_transaction};
+use crate::simulate::simulate_tx;
+
+/// A signer given on the command line: a local keypair, or only a pubkey
+/// when its signature is collected on another machine.
+pub enum SignerArg {
+    Keypair(Keypair),
+    Pubkey(Pubkey),
+}
+
+impl SignerArg {
+    fn parse(value: &str) -> Result<Self> {
+        match Pubkey::from_str(value) {
+            Ok(pubkey) => Ok(SignerArg::Pubkey(pubkey)),
+            Err(_) => read_keypair_path(value).map(SignerArg::Keypair),
+        }
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            SignerArg::Keypair(keypair) => keypair.pubkey(),
+            SignerArg::Pubkey(pubkey) => *pubkey,
+        }
+    }
+
+    fn keypair(&self) -> Option<&Keypair> {
+        match self {
+            SignerArg::Keypair(keypair) => Some(keypair),
+            SignerArg::Pubkey(_) => None,
+        }
+    }
+}
+
+/// A confirmed transaction. `fee` is `None` when the cluster could not
+/// return the transaction right after confirming it.
+pub struct SentTx {
+    pub signature: Signature,
+    pub slot: u64,
+    pub fee: Option<u64>,
+}
+
+/// Error for a transaction that was broadcast but not confirmed before we
+/// stopped waiting. It may still land, so it must not be sent again before
+/// its status is checked.
+#[derive(Debug)]
+pub struct UnconfirmedTx {
+    pub signature: Signature,
+}
+
+impl std::fmt::Display for UnconfirmedTx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transaction {} was sent but is not confirmed yet, it may still land, check its status before sending again", self.signature)
+    }
+}
+
+impl std::error::Error for UnconfirmedTx {}
+
+/// Everything a subcommand needs to build and submit a transaction.
+pub struct Context {
+    pub rpc: RpcClient,
+    /// Read from `keypair_path` on first use, so flows that never need the
+    /// payer, such as `submit` or `--sign-only` with `--fee-payer`, work on
+    /// machines without its keypair file.
+    payer: OnceLock<SignerArg>,
+    keypair_path: PathBuf,
+    pub program_id: Option<Pubkey>,
+    pub fee_payer: Option<SignerArg>,
+    pub signers: Vec<Keypair>,
+    pub nonce: Option<Pubkey>,
+    pub nonce_authority: Option<SignerArg>,
+    pub simulate: bool,
+    pub output: OutputFormat,
+    pub sign_only: bool,
+    pub blockhash: Option<Hash>,
+    pub tx_format: TxFormat,
+    pub out_file: Option<PathBuf>,
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price: Option<u64>,
+    /// Percentile to sample recent prioritization fees at, set by `--auto-priority-fee`.
+    pub priority_fee_percentile: Option<u8>,
+    pub confirm: ConfirmConfig,
+}
+
+impl Context {
+    pub fn new(cli: &Cli) -> Result<Self> {
+        let config = ClientConfig::load(&cli.config)?;
+        let signing = &cli.signing;
+        Ok(Self {
+            rpc: RpcClient::new_with_commitment(config.json_rpc_url.clone(), config.commitment),
+            payer: OnceLock::new(),
+            keypair_path: config.keypair_path.clone(),
+            program_id: config.program_id,
+            fee_payer: signing.fee_payer.as_deref().map(SignerArg::parse).transpose()?,
+            signers: signing.signers.iter().map(read_keypair_path).collect::<Result<_>>()?,
+            nonce: signing.nonce,
+            nonce_authority: signing.nonce_authority.as_deref().map(SignerArg::parse).transpose()?,
+            simulate: cli.simulate,
+            output: cli.output,
+            sign_only: signing.sign_only,
+            blockhash: signing.blockhash,
+            tx_format: signing.tx_format,
+            out_file: signing.out_file.clone(),
+            compute_unit_limit: cli.fees.compute_unit_limit,
+            compute_unit_price: cli.fees.compute_unit_price,
+            priority_fee_percentile: cli.fees.auto_priority_fee.then_some(cli.fees.priority_fee_percentile),
+            confirm: ConfirmConfig {
+                commitment: config.commitment,
+                rebroadcast_interval: Duration::from_secs(cli.confirm.rebroadcast_interval),
+                max_resigns: cli.confirm.max_resigns,
+                timeout: Duration::from_secs(cli.confirm.confirm_timeout),
+            },
+        })
+    }
+
+    pub fn program_id(&self) -> Result<Pubkey> {
+        self.program_id.context("program id not set, pass --program-id or set PROGRAM_ID")
+    }
+
+    /// The payer from `--keypair` or the Solana CLI config: a keypair file, or
+    /// a pubkey whose signature is added later with `submit`.
+    pub fn payer(&self) -> Result<&SignerArg> {
+        if let Some(payer) = self.payer.get() {
+            return Ok(payer);
+        }
+        let path = self.keypair_path.to_string_lossy();
+        let payer = SignerArg::parse(&path).with_context(|| format!("the payer keypair is needed, pass --keypair or set it in the Solana CLI config (tried {})", path))?;
+        Ok(self.payer.get_or_init(|| payer))
+    }
+
+    pub fn payer_pubkey(&self) -> Result<Pubkey> {
+        self.payer().map(SignerArg::pubkey)
+    }
+
+    pub fn fee_payer_pubkey(&self) -> Result<Pubkey> {
+        self.fee_payer.as_ref().map_or_else(|| self.payer_pubkey(), |fee_payer| Ok(fee_payer.pubkey()))
+    }
+
+    pub fn nonce_authority_pubkey(&self) -> Result<Pubkey> {
+        self.nonce_authority.as_ref().map_or_else(|| self.payer_pubkey(), |authority| Ok(authority.pubkey()))
+    }
+
+    pub fn send_tx(&self, instructions: Vec<Instruction>) -> Result<Option<SentTx>> {
+        self.send_tx_with_signers(instructions, &[])
+    }
+
+    /// Builds a transaction from `instructions`, signs it with every local
+    /// keypair it needs plus `extra_signers` and hands it to `finish_tx`.
+    /// With `--nonce` the transaction advances that nonce and uses its value
+    /// as the blockhash. Compute budget instructions go in front.
+    pub fn send_tx_with_signers(&self, instructions: Vec<Instruction>, extra_signers: &[&Keypair]) -> Result<Option<SentTx>> {
+        let fee_payer = self.fee_payer_pubkey()?;
+        let instructions = self.with_compute_budget(instructions, &fee_payer)?;
+        let (message, blockhash) = self.build_message(instructions, &fee_payer)?;
+        match blockhash {
+            Some(blockhash) => {
+                let mut tx = Transaction::new_unsigned(message);
+                self.sign_available(&mut tx, blockhash, extra_signers)?;
+                self.finish_tx(tx)
+            }
+            None => self.send_with_fresh_blockhash(&message, extra_signers),
+        }
+    }
+
+    /// The message for `instructions` and the blockhash to sign it with, or
+    /// `None` when a fresh one is fetched at send time. With `--nonce` the
+    /// message advances the nonce first and the blockhash is its stored value.
+    fn build_message(&self, instructions: Vec<Instruction>, fee_payer: &Pubkey) -> Result<(Message, Option<Hash>)> {
+        match self.nonce {
+            Some(nonce) => {
+                let blockhash = match self.blockhash {
+                    Some(blockhash) => blockhash,
+                    None if self.sign_only => bail!("--sign-only with --nonce needs --blockhash set to the nonce value"),
+                    None => self.nonce_blockhash(&nonce)?,
+                };
+                Ok((Message::new_with_nonce(instructions, Some(fee_payer), &nonce, &self.nonce_authority_pubkey()?), Some(blockhash)))
+            }
+            None => {
+                let blockhash = match self.blockhash {
+                    Some(blockhash) => Some(blockhash),
+                    None if self.sign_only => bail!("--sign-only needs --blockhash"),
+                    None => None,
+                };
+                Ok((Message::new(&instructions, Some(fee_payer)), blockhash))
+            }
+        }
+    }
+
+    /// Adds local signatures to a previously built transaction and hands it to `finish_tx`.
+    pub fn submit_tx(&self, mut tx: Transaction) -> Result<Option<SentTx>> {
+        let blockhash = tx.message.recent_blockhash;
+        self.sign_available(&mut tx, blockhash, &[])?;
+        self.finish_tx(tx)
+    }
+
+    /// Prepends the `ComputeBudget` instructions asked for on the command line,
+    /// sampling the unit price from the cluster with `--auto-priority-fee`.
+    fn with_compute_budget(&self, instructions: Vec<Instruction>, fee_payer: &Pubkey) -> Result<Vec<Instruction>> {
+        let unit_price = match self.priority_fee_percentile {
+            Some(_) if self.sign_only => bail!("--auto-priority-fee needs the cluster, pass --compute-unit-price with --sign-only"),
+            Some(percentile) => {
+                let price = recent_priority_fee(&self.rpc, &writable_accounts(&instructions, fee_payer), percentile)?;
+                eprintln!("Priority fee: {} micro-lamports per compute unit (p{} of recent fees)", price, percentile);
+                Some(price)
+            }
+            None => self.compute_unit_price,
+        };
+        Ok(compute_budget_ixs(self.compute_unit_limit, unit_price).into_iter().chain(instructions).collect())
+    }
+
+    /// Current value stored in the durable nonce account `nonce`.
+    pub fn nonce_blockhash(&self, nonce: &Pubkey) -> Result<Hash> {
+        let account = nonce_utils::get_account_with_commitment(&self.rpc, nonce, self.rpc.commitment())?;
+        let data = nonce_utils::data_from_account(&account)?;
+        Ok(data.blockhash())
+    }
+
+    /// Writes the transaction out with `--sign-only`, simulates it with
+    /// `--simulate`, and otherwise sends it. Returns the confirmed transaction if sent.
+    fn finish_tx(&self, tx: Transaction) -> Result<Option<SentTx>> {
+        if self.sign_only {
+            write_transaction(&tx, self.tx_format, self.out_file.as_deref(), self.output)?;
+            return Ok(None);
+        }
+        check_signed(&tx)?;
+
+        if self.simulate {
+            simulate_tx(&self.rpc, self.program_id.as_ref(), &tx, self.output)?;
+            return Ok(None);
+        }
+        // The blockhash was chosen elsewhere, so its expiry height is unknown
+        // and the transaction cannot be re-signed.
+        let outcome = self.rpc.runtime().block_on(send_and_confirm(self.rpc.get_inner_client(), &tx, None, &self.confirm))?;
+        self.settle(outcome, &tx.message).map(Some)
+    }
+
+    /// Signs `message` with a blockhash fetched at send time and sends it,
+    /// re-signing with a newer blockhash if it expires without landing.
+    fn send_with_fresh_blockhash(&self, message: &Message, extra_signers: &[&Keypair]) -> Result<Option<SentTx>> {
+        let sign = |blockhash: Hash| -> Result<Transaction> {
+            let mut tx = Transaction::new_unsigned(message.clone());
+            self.sign_available(&mut tx, blockhash, extra_signers)?;
+            check_signed(&tx)?;
+            Ok(tx)
+        };
+        if self.simulate {
+            let tx = sign(self.rpc.get_latest_blockhash()?)?;
+            simulate_tx(&self.rpc, self.program_id.as_ref(), &tx, self.output)?;
+            return Ok(None);
+        }
+        let outcome = self.rpc.runtime().block_on(send_and_confirm_with_resign(self.rpc.get_inner_client(), &self.confirm, sign))?;
+        self.settle(outcome, message).map(Some)
+    }
+
+    /// Turns the outcome of a send into a `SentTx`, or an error that tells a
+    /// failed transaction apart from one whose status is unknown.
+    fn settle(&self, outcome: TxOutcome, message: &Message) -> Result<SentTx> {
+        match outcome {
+            TxOutcome::Confirmed { signature, slot } => Ok(SentTx { signature, slot, fee: self.fee_paid(&signature) }),
+            TxOutcome::Failed { signature, error } => bail!("transaction {} failed: {}", signature, self.describe_error(&error, message)),
+            TxOutcome::Expired { signature } => bail!("transaction {} expired without being processed, it is safe to send again", signature),
+            TxOutcome::Unknown { signature, .. } => Err(UnconfirmedTx { signature }.into()),
+        }
+    }
+
+    /// Looks up the fee paid by a confirmed transaction.
+    fn fee_paid(&self, signature: &Signature) -> Option<u64> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(self.rpc.commitment()),
+            max_supported_transaction_version: Some(0),
+        };
+        let confirmed = self.rpc.get_transaction_with_config(signature, config).ok()?;
+        confirmed.transaction.meta.map(|meta| meta.fee)
+    }
+
+    /// Names the registry error behind a failed transaction when possible.
+    fn describe_error(&self, error: &TransactionError, message: &Message) -> String {
+        match &self.program_id {
+            Some(program_id) => describe_transaction_error(error, message, program_id),
+            None => error.to_string(),
+        }
+    }
+
+    /// Signs `tx` with the local keypairs that are required signers of it.
+    /// A payer keypair that can not be read is skipped, its signature is then
+    /// reported missing when the transaction is sent.
+    fn sign_available(&self, tx: &mut Transaction, blockhash: Hash, extra_signers: &[&Keypair]) -> Result<()> {
+        let required = &tx.message.account_keys[..tx.message.header.num_required_signatures as usize];
+        let payer = self.payer().ok().and_then(SignerArg::keypair);
+        let fee_payer = self.fee_payer.as_ref().and_then(SignerArg::keypair);
+        let nonce_authority = self.nonce_authority.as_ref().and_then(SignerArg::keypair);
+        let mut keypairs: Vec<&Keypair> = Vec::new();
+        for keypair in payer.into_iter().chain(fee_payer).chain(nonce_authority).chain(&self.signers).chain(extra_signers.iter().copied()) {
+            let pubkey = keypair.pubkey();
+            if required.contains(&pubkey) && !keypairs.iter().any(|k| k.pubkey() == pubkey) {
+                keypairs.push(keypair);
+            }
+        }
+        tx.try_partial_sign(&keypairs, blockhash)?;
+        Ok(())
+    }
+}
+
+fn check_signed(tx: &Transaction) -> Result<()> {
+    let missing = missing_signers(tx);
+    if !missing.is_empty() {
+        bail!("transaction is missing signatures from: {}", missing.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_system_interface::instruction::SystemInstruction;
+
+    use super::*;
+
+    fn context(payer: Pubkey) -> Context {
+        Context {
+            rpc: RpcClient::new("http://127.0.0.1:8899".to_string()),
+            payer: OnceLock::from(SignerArg::Pubkey(payer)),
+            keypair_path: PathBuf::new(),
+            program_id: None,
+            fee_payer: None,
+            signers: Vec::new(),
+            nonce: None,
+            nonce_authority: None,
+            simulate: false,
+            output: OutputFormat::Table,
+            sign_only: true,
+            blockhash: None,
+            tx_format: TxFormat::Base64,
+            out_file: None,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            priority_fee_percentile: None,
+            confirm: ConfirmConfig {
+                commitment: CommitmentConfig::confirmed(),
+                rebroadcast_interval: Duration::from_secs(2),
+                max_resigns: 0,
+                timeout: Duration::from_secs(60),
+            },
+        }
+    }
+
+    fn transfer(from: &Pubkey) -> Instruction {
+        solana_system_interface::instruction::transfer(from, &Pubkey::new_unique(), 1)
+    }
+
+    #[test]
+    fn nonce_transactions_advance_the_nonce_first() {
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let nonce = Pubkey::new_unique();
+        let nonce_value = Hash::new_unique();
+        let mut ctx = context(payer);
+        ctx.nonce = Some(nonce);
+        ctx.nonce_authority = Some(SignerArg::Pubkey(authority));
+        ctx.blockhash = Some(nonce_value);
+
+        let (message, blockhash) = ctx.build_message(vec![transfer(&payer)], &payer).unwrap();
+        assert_eq!(blockhash, Some(nonce_value));
+        assert_eq!(message.instructions.len(), 2);
+        let advance = &message.instructions[0];
+        assert_eq!(message.account_keys[advance.program_id_index as usize], solana_system_interface::program::ID);
+        assert!(matches!(bincode::deserialize(&advance.data).unwrap(), SystemInstruction::AdvanceNonceAccount));
+        assert_eq!(message.account_keys[advance.accounts[0] as usize], nonce);
+        assert_eq!(message.account_keys[advance.accounts[2] as usize], authority);
+        assert!(message.is_signer(message.account_keys.iter().position(|key| *key == authority).unwrap()));
+    }
+
+    #[test]
+    fn nonce_authority_defaults_to_the_payer() {
+        let payer = Pubkey::new_unique();
+        let mut ctx = context(payer);
+        ctx.nonce = Some(Pubkey::new_unique());
+        ctx.blockhash = Some(Hash::new_unique());
+
+        let (message, _) = ctx.build_message(vec![transfer(&payer)], &payer).unwrap();
+        let advance = &message.instructions[0];
+        assert_eq!(message.account_keys[advance.accounts[2] as usize], payer);
+    }
+
+    #[test]
+    fn sign_only_needs_the_blockhash_or_nonce_value() {
+        let payer = Pubkey::new_unique();
+        let mut ctx = context(payer);
+        let err = ctx.build_message(vec![transfer(&payer)], &payer).unwrap_err();
+        assert!(err.to_string().contains("--sign-only needs --blockhash"));
+
+        ctx.nonce = Some(Pubkey::new_unique());
+        let err = ctx.build_message(vec![transfer(&payer)], &payer).unwrap_err();
+        assert!(err.to_string().contains("--nonce needs --blockhash"));
+    }
+
+    #[test]
+    fn compute_budget_instructions_go_first() {
+        let payer = Pubkey::new_unique();
+        let mut ctx = context(payer);
+        ctx.compute_unit_limit = Some(300_000);
+        ctx.compute_unit_price = Some(10);
+        let ixs = ctx.with_compute_budget(vec![transfer(&payer)], &payer).unwrap();
+        assert_eq!(ixs.len(), 3);
+        assert_eq!(ixs[..2], compute_budget_ixs(Some(300_000), Some(10))[..]);
+        assert_eq!(ixs[2].program_id, solana_system_interface::program::ID);
+    }
+
+    #[test]
+    fn auto_priority_fee_is_refused_offline() {
+        let payer = Pubkey::new_unique();
+        let mut ctx = context(payer);
+        ctx.priority_fee_percentile = Some(75);
+        let err = ctx.with_compute_budget(vec![transfer(&payer)], &payer).unwrap_err();
+        assert!(err.to_string().contains("--auto-priority-fee needs the cluster"));
+    }
+}
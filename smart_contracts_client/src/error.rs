@@ -0,0 +1,22 @@
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+use smart_contracts_types::error::RegistryError;
+
+/// Renders a transaction error, naming the `RegistryError` when a custom
+/// error was returned by a registry instruction.
+pub fn describe_transaction_error(err: &TransactionError, message: &Message, program_id: &Pubkey) -> String {
+    if let TransactionError::InstructionError(index, InstructionError::Custom(code)) = err {
+        let is_registry_ix = message
+            .instructions
+            .get(*index as usize)
+            .and_then(|ix| message.account_keys.get(ix.program_id_index as usize))
+            .is_some_and(|key| key == program_id);
+        if let (true, Some(registry_err)) = (is_registry_ix, RegistryError::from_code(*code)) {
+            return format!("instruction {}: {} (custom program error 0x{:x})", index, registry_err, code);
+        }
+    }
+    err.to_string()
+}
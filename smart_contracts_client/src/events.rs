@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use smart_contracts_types::error::RegistryError;
+use smart_contracts_types::event::RegistryEvent as LogEvent;
+
+/// Registry activity recovered from the `msg!` lines the program logs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum RegistryEvent {
+    #[serde(rename_all = "camelCase")]
+    UserRegistered { owner: String },
+    #[serde(rename_all = "camelCase")]
+    SolTransferred { lamports: u64 },
+    #[serde(rename_all = "camelCase")]
+    TokensTransferred { amount: u64 },
+    #[serde(rename_all = "camelCase")]
+    BalanceValidated { pre_balance: u64, balance: u64, decreased: bool },
+    /// A `RegistryError` message logged right before the program failed.
+    #[serde(rename_all = "camelCase")]
+    Error { message: String },
+}
+
+impl From<LogEvent> for RegistryEvent {
+    fn from(event: LogEvent) -> Self {
+        match event {
+            LogEvent::UserRegistered { owner } => RegistryEvent::UserRegistered { owner: owner.to_string() },
+            LogEvent::SolTransferred { lamports } => RegistryEvent::SolTransferred { lamports },
+            LogEvent::TokensTransferred { amount } => RegistryEvent::TokensTransferred { amount },
+            LogEvent::BalanceValidated { pre_balance, balance, decreased } => RegistryEvent::BalanceValidated { pre_balance, balance, decreased },
+        }
+    }
+}
+
+impl std::fmt::Display for RegistryEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryEvent::UserRegistered { owner } => write!(f, "user registered: {}", owner),
+            RegistryEvent::SolTransferred { lamports } => write!(f, "SOL transferred: {} lamports", lamports),
+            RegistryEvent::TokensTransferred { amount } => write!(f, "tokens transferred: {} base units", amount),
+            RegistryEvent::BalanceValidated { pre_balance, balance, .. } => write!(f, "balance validated: {} -> {}", pre_balance, balance),
+            RegistryEvent::Error { message } => write!(f, "error: {}", message),
+        }
+    }
+}
+
+/// Extracts the registry events from a transaction's log messages. Only
+/// lines logged while `program_id` is the executing program are considered,
+/// so CPIs into other programs do not produce events.
+pub fn parse_registry_logs(program_id: &Pubkey, logs: &[String]) -> Vec<RegistryEvent> {
+    let mut stack: Vec<Pubkey> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(message) = rest.strip_prefix("log: ") {
+            if stack.last() == Some(program_id) {
+                events.extend(parse_message(message));
+            }
+            continue;
+        }
+        let mut words = rest.split_whitespace();
+        let (Some(program), Some(action)) = (words.next().and_then(|key| Pubkey::from_str(key).ok()), words.next()) else {
+            continue;
+        };
+        match action {
+            "invoke" => stack.push(program),
+            "success" | "failed:" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+fn parse_message(message: &str) -> Option<RegistryEvent> {
+    if let Some(event) = LogEvent::parse(message) {
+        return Some(event.into());
+    }
+    (0..)
+        .map_while(RegistryError::from_code)
+        .any(|err| err.to_string() == message)
+        .then(|| RegistryEvent::Error { message: message.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn follows_nested_invokes() {
+        let registry = Pubkey::new_unique();
+        let system = Pubkey::new_unique();
+        let logs = logs(&[
+            &format!("Program {registry} invoke [1]"),
+            &format!("Program {system} invoke [2]"),
+            &format!("Program {system} success"),
+            &format!("Program log: {}", LogEvent::SolTransferred { lamports: 5 }),
+            &format!("Program {registry} invoke [2]"),
+            &format!("Program log: {}", LogEvent::TokensTransferred { amount: 7 }),
+            &format!("Program {registry} success"),
+            &format!("Program log: {}", LogEvent::BalanceValidated { pre_balance: 10, balance: 5, decreased: true }),
+            &format!("Program {registry} consumed 1200 of 200000 compute units"),
+            &format!("Program {registry} success"),
+        ]);
+        assert_eq!(
+            parse_registry_logs(&registry, &logs),
+            vec![
+                RegistryEvent::SolTransferred { lamports: 5 },
+                RegistryEvent::TokensTransferred { amount: 7 },
+                RegistryEvent::BalanceValidated { pre_balance: 10, balance: 5, decreased: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_logs_of_other_programs() {
+        let registry = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let event = format!("Program log: {}", LogEvent::SolTransferred { lamports: 5 });
+        let logs = logs(&[
+            &event,
+            &format!("Program {other} invoke [1]"),
+            &event,
+            &format!("Program {registry} invoke [2]"),
+            &format!("Program {registry} success"),
+            &event,
+            &format!("Program {other} success"),
+            &event,
+        ]);
+        assert!(parse_registry_logs(&registry, &logs).is_empty());
+    }
+
+    #[test]
+    fn reports_custom_errors() {
+        let registry = Pubkey::new_unique();
+        let logs = logs(&[
+            &format!("Program {registry} invoke [1]"),
+            &format!("Program log: {}", RegistryError::AlreadyRegistered),
+            &format!("Program {registry} failed: custom program error: 0x1"),
+            &format!("Program log: {}", RegistryError::MathOverflow),
+        ]);
+        assert_eq!(parse_registry_logs(&registry, &logs), vec![RegistryEvent::Error { message: RegistryError::AlreadyRegistered.to_string() }]);
+    }
+
+    #[test]
+    fn skips_unknown_messages() {
+        let registry = Pubkey::new_unique();
+        let logs = logs(&[&format!("Program {registry} invoke [1]"), "Program log: Instruction: Transfer", "Program log: Transferred many lamports", "Program return: abc"]);
+        assert!(parse_registry_logs(&registry, &logs).is_empty());
+    }
+}
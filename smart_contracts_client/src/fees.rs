@@ -0,0 +1,76 @@
+use anyhow::Result;
+use solana_client::rpc_client::RpcClient;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+/// Percentile of recent prioritization fees used when none is given.
+pub const DEFAULT_PRIORITY_FEE_PERCENTILE: u8 = 75;
+
+/// Most accounts `getRecentPrioritizationFees` accepts in one request.
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+/// `ComputeBudget` instructions setting the compute unit limit and price
+/// (in micro-lamports per compute unit), for prepending to a transaction.
+pub fn compute_budget_ixs(unit_limit: Option<u32>, unit_price: Option<u64>) -> Vec<Instruction> {
+    let mut ixs = Vec::new();
+    if let Some(units) = unit_limit {
+        ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+    }
+    if let Some(price) = unit_price {
+        ixs.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    ixs
+}
+
+/// Accounts written by `instructions`, fee payer first. These are the
+/// accounts whose write locks set the priority fee a transaction competes with.
+pub fn writable_accounts(instructions: &[Instruction], fee_payer: &Pubkey) -> Vec<Pubkey> {
+    let mut accounts = vec![*fee_payer];
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts
+}
+
+/// Samples `getRecentPrioritizationFees` for `accounts` and returns the fee
+/// at `percentile` (0-100) in micro-lamports per compute unit, 0 if the
+/// cluster has no samples.
+pub fn recent_priority_fee(rpc: &RpcClient, accounts: &[Pubkey], percentile: u8) -> Result<u64> {
+    let accounts = &accounts[..accounts.len().min(MAX_FEE_ACCOUNTS)];
+    let mut fees: Vec<u64> = rpc.get_recent_prioritization_fees(accounts)?.into_iter().map(|fee| fee.prioritization_fee).collect();
+    if fees.is_empty() {
+        return Ok(0);
+    }
+    fees.sort_unstable();
+    let index = (fees.len() - 1) * usize::from(percentile.min(100)) / 100;
+    Ok(fees[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn compute_budget_sets_the_limit_before_the_price() {
+        assert!(compute_budget_ixs(None, None).is_empty());
+        let ixs = compute_budget_ixs(Some(200_000), Some(5_000));
+        assert_eq!(ixs, vec![ComputeBudgetInstruction::set_compute_unit_limit(200_000), ComputeBudgetInstruction::set_compute_unit_price(5_000)]);
+        assert_eq!(compute_budget_ixs(None, Some(1)), vec![ComputeBudgetInstruction::set_compute_unit_price(1)]);
+    }
+
+    #[test]
+    fn writable_accounts_start_with_the_fee_payer_and_skip_duplicates() {
+        let fee_payer = Pubkey::new_unique();
+        let written = Pubkey::new_unique();
+        let read = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let ix = Instruction::new_with_bytes(program, &[], vec![AccountMeta::new(fee_payer, true), AccountMeta::new(written, false), AccountMeta::new_readonly(read, false)]);
+        let again = Instruction::new_with_bytes(program, &[], vec![AccountMeta::new(written, false)]);
+        assert_eq!(writable_accounts(&[ix, again], &fee_payer), vec![fee_payer, written]);
+    }
+}
@@ -0,0 +1,105 @@
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use solana_system_interface::program as system_program;
+use spl_token::id as token_program_id;
+
+use smart_contracts_types::instruction::RegistryInstruction;
+
+use crate::pda::find_user_address;
+
+/// Builds `RegisterUser` for `payer`, deriving the user PDA and its bump.
+pub fn register_user_ix(program_id: &Pubkey, payer: &Pubkey) -> Instruction {
+    let (user_pda, bump) = find_user_address(program_id, payer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &RegistryInstruction::RegisterUser { bump },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(user_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Builds `TransferSol` moving `lamports` from `from` to `to`.
+pub fn transfer_sol_ix(program_id: &Pubkey, from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &RegistryInstruction::TransferSol { amount: lamports },
+        vec![
+            AccountMeta::new(*from, true),
+            AccountMeta::new(*to, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Builds `TransferSpl` moving `amount` base units of `mint` between the
+/// associated token accounts of `authority` and `recipient`.
+pub fn transfer_spl_ix(program_id: &Pubkey, authority: &Pubkey, recipient: &Pubkey, mint: &Pubkey, amount: u64) -> Instruction {
+    let from_ata = get_associated_token_address(authority, mint);
+    let to_ata = get_associated_token_address(recipient, mint);
+    Instruction::new_with_borsh(
+        *program_id,
+        &RegistryInstruction::TransferSpl { amount },
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(token_program_id(), false),
+            AccountMeta::new(from_ata, false),
+            AccountMeta::new(to_ata, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+    )
+}
+
+/// Builds `ValidateTxn`, which fails unless the balance of `account` (token
+/// amount for token accounts, lamports otherwise) is at least
+/// `expected_decrease` below `pre_balance`.
+pub fn validate_txn_ix(program_id: &Pubkey, account: &Pubkey, pre_balance: u64, expected_decrease: u64) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &RegistryInstruction::ValidateTxn { pre_balance, expected_decrease },
+        vec![AccountMeta::new_readonly(*account, false)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_user_targets_the_payer_pda() {
+        let (program_id, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (user_pda, bump) = find_user_address(&program_id, &payer);
+
+        let ix = register_user_ix(&program_id, &payer);
+        assert_eq!(ix.program_id, program_id);
+        assert!(matches!(RegistryInstruction::unpack(&ix.data), Ok(RegistryInstruction::RegisterUser { bump: b }) if b == bump));
+        assert_eq!(ix.accounts, vec![AccountMeta::new(payer, true), AccountMeta::new(user_pda, false), AccountMeta::new_readonly(system_program::id(), false)]);
+    }
+
+    #[test]
+    fn transfers_encode_their_amounts() {
+        let (program_id, from, to, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let sol = transfer_sol_ix(&program_id, &from, &to, 42);
+        assert!(matches!(RegistryInstruction::unpack(&sol.data), Ok(RegistryInstruction::TransferSol { amount: 42 })));
+        assert_eq!(sol.accounts[0], AccountMeta::new(from, true));
+        assert_eq!(sol.accounts[1], AccountMeta::new(to, false));
+
+        let spl = transfer_spl_ix(&program_id, &from, &to, &mint, 7);
+        assert!(matches!(RegistryInstruction::unpack(&spl.data), Ok(RegistryInstruction::TransferSpl { amount: 7 })));
+        assert_eq!(spl.accounts[2].pubkey, get_associated_token_address(&from, &mint));
+        assert_eq!(spl.accounts[3].pubkey, get_associated_token_address(&to, &mint));
+        assert_eq!(spl.accounts[4], AccountMeta::new_readonly(mint, false));
+    }
+
+    #[test]
+    fn validate_txn_only_reads_the_account() {
+        let (program_id, account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = validate_txn_ix(&program_id, &account, 100, 40);
+        assert!(matches!(RegistryInstruction::unpack(&ix.data), Ok(RegistryInstruction::ValidateTxn { pre_balance: 100, expected_decrease: 40 })));
+        assert_eq!(ix.accounts, vec![AccountMeta::new_readonly(account, false)]);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod account;
+pub mod amount;
+pub mod confirm;
+pub mod error;
+pub mod events;
+pub mod fees;
+pub mod instruction;
+pub mod pda;
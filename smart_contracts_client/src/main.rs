@@ -1,109 +1,120 @@
-use std::str::FromStr;
-use solana_sdk::instruction::AccountMeta;
-use anyhow::Result;
-use solana_client::rpc_client::RpcClient;
-use solana_commitment_config::CommitmentConfig;
-use solana_sdk::{
-    instruction::Instruction,
-    pubkey::Pubkey,
-    signature::{Keypair, Signer},
-    transaction::Transaction,
-};
-use spl_associated_token_account::get_associated_token_address;
-use spl_associated_token_account::solana_program::system_program;
-use spl_token::id as token_program_id;
+use anyhow::{Result, bail};
+use clap::Parser;
+use solana_sdk::pubkey::Pubkey;
 
-// Import your on-chain program's instruction enum and program ID
-use smart_contracts_solana::instruction::RegistryInstruction;
+use smart_contracts_client::account::{fetch_mint_decimals, fetch_validated_balance};
+use smart_contracts_client::amount::{SOL_DECIMALS, ui_amount_to_base_units};
+use smart_contracts_client::instruction::{register_user_ix, transfer_sol_ix, transfer_spl_ix, validate_txn_ix};
+use smart_contracts_client::pda::find_user_address;
 
-fn main() -> Result<()> {
-    let program_id = Pubkey::from_str("YourProgramID111111111111111111111111111111111")?;
+use crate::batch::BatchOptions;
+use crate::cli::{Cli, Command, OutputFormat};
+use crate::context::Context;
+use crate::offline::read_transaction;
+use crate::output::{AlreadyReported, Report, print_error};
+use crate::watch::WatchOptions;
 
-    // 1) RPC client & payer
-    let rpc = RpcClient::new_with_commitment(
-        "http://127.0.0.1:8899", // to Replace with RPC URL
-        CommitmentConfig::confirmed(),
-    );
-    let payer = Keypair::from_base58_string(
-        "YOUR_BASE58_PRIVATE_KEY"
-    );
+mod batch;
+mod cli;
+mod config;
+mod context;
+mod nonce;
+mod offline;
+mod output;
+mod simulate;
+mod users;
+mod watch;
 
-    // Register user
-    let (user_pda, bump) = Pubkey::find_program_address(
-        &[b"user", payer.pubkey().as_ref()],
-        &program_id,
-    );
-    let ix_register = Instruction::new_with_borsh(
-        program_id,
-        &RegistryInstruction::RegisterUser { bump },
-        vec![
-            AccountMeta::new(payer.pubkey(), true),
-            AccountMeta::new(user_pda, false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    );
-    send_tx(&rpc, &payer, vec![ix_register])?;
-    println!("User registered at PDA: {}", user_pda);
+fn main() {
+    let cli = Cli::parse();
+    let output = cli.output;
+    if let Err(err) = run(cli) {
+        match output {
+            OutputFormat::Json if err.is::<AlreadyReported>() => {}
+            OutputFormat::Json => print_error(&err),
+            OutputFormat::Table => eprintln!("Error: {:?}", err),
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let ctx = Context::new(&cli)?;
 
-    // Transfer SOL
-    let recipient = Pubkey::from_str("Recipient111111111111111111111111111111111")?;
-    let ix_sol = Instruction::new_with_borsh(
-        program_id,
-        &RegistryInstruction::TransferSol { amount: 1_000_000 },
-        vec![
-            AccountMeta::new(payer.pubkey(), true),
-            AccountMeta::new(recipient, false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    );
-    send_tx(&rpc, &payer, vec![ix_sol])?;
-    println!("0.001 SOL transferred");
+    match cli.command {
+        Command::RegisterUser => register_user(&ctx),
+        Command::TransferSol { recipient, amount } => transfer_sol(&ctx, &recipient, &amount),
+        Command::TransferSpl { mint, recipient, amount, mint_decimals } => transfer_spl(&ctx, &mint, &recipient, &amount, mint_decimals),
+        Command::ValidateTxn { account, pre_balance, expected_decrease } => validate_txn(&ctx, &account, pre_balance, expected_decrease),
+        Command::Batch { input, results, checkpoint, max_per_tx, concurrency } => batch::run(&ctx, BatchOptions { input, results, checkpoint, max_per_tx, concurrency }),
+        Command::ShowUser { owner } => users::show(&ctx, &owner),
+        Command::ListUsers => users::list(&ctx),
+        Command::Watch { poll, poll_interval, ws_url } => watch::run(&ctx, WatchOptions { poll, poll_interval, ws_url }),
+        Command::Nonce(command) => nonce::run(&ctx, command),
+        Command::Submit { transaction } => submit(&ctx, &transaction),
+    }
+}
+
+fn register_user(ctx: &Context) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let owner = ctx.payer_pubkey()?;
+    let (user_pda, _) = find_user_address(&program_id, &owner);
+    let ix = register_user_ix(&program_id, &owner);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new().field("owner", owner.to_string()).field("pda", user_pda.to_string()).tx(&sent).print(ctx.output);
+    }
+    Ok(())
+}
 
-    // Transfer SPL Token
-    let mint = Pubkey::from_str("TokenMint111111111111111111111111111111111")?;
-    let from_ata = get_associated_token_address(&payer.pubkey(), &mint);
-    let to_pubkey = Pubkey::from_str("Recipient111111111111111111111111111111111")?;
-    let to_ata = get_associated_token_address(&to_pubkey, &mint);
-    let ix_spl = Instruction::new_with_borsh(
-        program_id,
-        &RegistryInstruction::TransferSpl { amount: 10 },
-        vec![
-            AccountMeta::new(payer.pubkey(), true),
-            AccountMeta::new_readonly(token_program_id(), false),
-            AccountMeta::new(from_ata, false),
-            AccountMeta::new(to_ata, false),
-            AccountMeta::new_readonly(mint, false),
-        ],
-    );
-    send_tx(&rpc, &payer, vec![ix_spl])?;
-    println!("10 SPL tokens transferred");
+fn transfer_sol(ctx: &Context, recipient: &Pubkey, amount: &str) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let lamports = ui_amount_to_base_units(amount, SOL_DECIMALS)?;
+    let ix = transfer_sol_ix(&program_id, &ctx.payer_pubkey()?, recipient, lamports);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new().field("recipient", recipient.to_string()).field("amountSol", amount).field("lamports", lamports).tx(&sent).print(ctx.output);
+    }
+    Ok(())
+}
 
-    // Validate Transaction Info
-    let acct = Pubkey::from_str("AccountToCheck111111111111111111111111111111111")?;
-    let pre = rpc.get_balance(&acct)?;
-    let ix_val = Instruction::new_with_borsh(
-        program_id,
-        &RegistryInstruction::ValidateTxn { pre_balance: pre },
-        vec![AccountMeta::new_readonly(acct, false)],
-    );
-    send_tx(&rpc, &payer, vec![ix_val])?;
-    println!("Transaction validation invoked");
+fn transfer_spl(ctx: &Context, mint: &Pubkey, recipient: &Pubkey, amount: &str, mint_decimals: Option<u8>) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let decimals = match mint_decimals {
+        Some(decimals) => decimals,
+        None if ctx.sign_only => bail!("--mint-decimals is required with --sign-only"),
+        None => fetch_mint_decimals(&ctx.rpc, mint)?,
+    };
+    let units = ui_amount_to_base_units(amount, decimals)?;
+    let ix = transfer_spl_ix(&program_id, &ctx.payer_pubkey()?, recipient, mint, units);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new()
+            .field("mint", mint.to_string())
+            .field("recipient", recipient.to_string())
+            .field("amount", amount)
+            .field("baseUnits", units)
+            .tx(&sent)
+            .print(ctx.output);
+    }
+    Ok(())
+}
 
+fn validate_txn(ctx: &Context, account: &Pubkey, pre_balance: Option<u64>, expected_decrease: u64) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let pre = match pre_balance {
+        Some(pre) => pre,
+        None if ctx.sign_only => bail!("--pre-balance is required with --sign-only"),
+        None => fetch_validated_balance(&ctx.rpc, account)?,
+    };
+    let ix = validate_txn_ix(&program_id, account, pre, expected_decrease);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new().field("account", account.to_string()).field("preBalance", pre).field("expectedDecrease", expected_decrease).tx(&sent).print(ctx.output);
+    }
     Ok(())
 }
 
-fn send_tx(
-    rpc: &RpcClient,
-    payer: &Keypair,
-    instructions: Vec<Instruction>,
-) -> Result<()> {
-    let recent_hash = rpc.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&payer.pubkey()),
-        &[payer],
-        recent_hash,
-    );
-    rpc.send_and_confirm_transaction(&tx)?;
+fn submit(ctx: &Context, source: &str) -> Result<()> {
+    let tx = read_transaction(source)?;
+    if let Some(sent) = ctx.submit_tx(tx)? {
+        Report::new().tx(&sent).print(ctx.output);
+    }
     Ok(())
 }
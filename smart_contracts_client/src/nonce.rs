@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use solana_client::nonce_utils;
+use solana_nonce::state::State as NonceState;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_system_interface::instruction as system_instruction;
+
+use smart_contracts_client::amount::{SOL_DECIMALS, base_units_to_ui_amount, ui_amount_to_base_units};
+
+use crate::cli::NonceCommand;
+use crate::config::read_keypair_path;
+use crate::context::Context;
+use crate::output::Report;
+
+pub fn run(ctx: &Context, command: NonceCommand) -> Result<()> {
+    if ctx.nonce.is_some() {
+        bail!("--nonce cannot be combined with the nonce subcommands");
+    }
+    match command {
+        NonceCommand::Create { nonce_keypair, amount, authority } => create(ctx, &nonce_keypair, amount.as_deref(), authority),
+        NonceCommand::Advance { nonce_account } => advance(ctx, &nonce_account),
+        NonceCommand::Show { nonce_account } => show(ctx, &nonce_account),
+        NonceCommand::Withdraw { nonce_account, destination, amount } => withdraw(ctx, &nonce_account, &destination, &amount),
+    }
+}
+
+fn create(ctx: &Context, nonce_keypair: &Path, amount: Option<&str>, authority: Option<Pubkey>) -> Result<()> {
+    let nonce_keypair = read_keypair_path(nonce_keypair)?;
+    let authority = match authority {
+        Some(authority) => authority,
+        None => ctx.nonce_authority_pubkey()?,
+    };
+    let minimum = ctx.rpc.get_minimum_balance_for_rent_exemption(NonceState::size())?;
+    let lamports = nonce_lamports(amount, minimum)?;
+
+    let instructions = system_instruction::create_nonce_account(&ctx.fee_payer_pubkey()?, &nonce_keypair.pubkey(), &authority, lamports);
+    if let Some(sent) = ctx.send_tx_with_signers(instructions, &[&nonce_keypair])? {
+        Report::new().field("nonceAccount", nonce_keypair.pubkey().to_string()).field("authority", authority.to_string()).field("lamports", lamports).tx(&sent).print(ctx.output);
+    }
+    Ok(())
+}
+
+/// Lamports to fund a new nonce account with: `amount` SOL, or the rent
+/// exempt `minimum` when no amount is given.
+fn nonce_lamports(amount: Option<&str>, minimum: u64) -> Result<u64> {
+    let lamports = match amount {
+        Some(amount) => ui_amount_to_base_units(amount, SOL_DECIMALS)?,
+        None => minimum,
+    };
+    if lamports < minimum {
+        bail!("nonce account needs at least {} SOL to be rent exempt", base_units_to_ui_amount(minimum, SOL_DECIMALS));
+    }
+    Ok(lamports)
+}
+
+fn advance(ctx: &Context, nonce_account: &Pubkey) -> Result<()> {
+    let ix = system_instruction::advance_nonce_account(nonce_account, &ctx.nonce_authority_pubkey()?);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new().field("nonceAccount", nonce_account.to_string()).field("nonce", ctx.nonce_blockhash(nonce_account)?.to_string()).tx(&sent).print(ctx.output);
+    }
+    Ok(())
+}
+
+fn show(ctx: &Context, nonce_account: &Pubkey) -> Result<()> {
+    let account = nonce_utils::get_account_with_commitment(&ctx.rpc, nonce_account, ctx.rpc.commitment())?;
+    let data = nonce_utils::data_from_account(&account)?;
+    Report::new()
+        .field("nonceAccount", nonce_account.to_string())
+        .field("balanceSol", base_units_to_ui_amount(account.lamports, SOL_DECIMALS))
+        .field("authority", data.authority.to_string())
+        .field("nonce", data.blockhash().to_string())
+        .field("lamportsPerSignature", data.get_lamports_per_signature())
+        .print(ctx.output);
+    Ok(())
+}
+
+fn withdraw(ctx: &Context, nonce_account: &Pubkey, destination: &Pubkey, amount: &str) -> Result<()> {
+    let lamports = ui_amount_to_base_units(amount, SOL_DECIMALS)?;
+    let ix = system_instruction::withdraw_nonce_account(nonce_account, &ctx.nonce_authority_pubkey()?, destination, lamports);
+    if let Some(sent) = ctx.send_tx(vec![ix])? {
+        Report::new()
+            .field("nonceAccount", nonce_account.to_string())
+            .field("destination", destination.to_string())
+            .field("amountSol", amount)
+            .field("lamports", lamports)
+            .tx(&sent)
+            .print(ctx.output);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_accounts_are_funded_to_at_least_the_rent_minimum() {
+        assert_eq!(nonce_lamports(None, 1_447_680).unwrap(), 1_447_680);
+        assert_eq!(nonce_lamports(Some("0.5"), 1_447_680).unwrap(), 500_000_000);
+        let err = nonce_lamports(Some("0.001"), 1_447_680).unwrap_err();
+        assert!(err.to_string().contains("at least 0.00144768 SOL"));
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+
+use crate::cli::{OutputFormat, TxFormat};
+use crate::output::Report;
+
+/// JSON form of a `--sign-only` transaction. `transaction` carries the
+/// bincode bytes in base64; the other fields are informational.
+/// `--output json` prints the same fields, so either can be submitted.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionDump {
+    blockhash: String,
+    fee_payer: String,
+    signers: Vec<SignerStatus>,
+    transaction: String,
+}
+
+/// The only field `submit` needs from a JSON transaction.
+#[derive(Debug, Deserialize)]
+struct EncodedTransaction {
+    transaction: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SignerStatus {
+    pubkey: String,
+    signature: Option<String>,
+}
+
+/// Required signers of `tx` that have not signed it yet.
+pub fn missing_signers(tx: &Transaction) -> Vec<Pubkey> {
+    signer_keys(tx)
+        .iter()
+        .zip(&tx.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// Writes `tx` to `out_file` in `format`, or prints it when unset. With
+/// `--output json` a report holding the transaction is printed either way.
+pub fn write_transaction(tx: &Transaction, format: TxFormat, out_file: Option<&Path>, output: OutputFormat) -> Result<()> {
+    if let Some(path) = out_file {
+        fs::write(path, format!("{}\n", encode_transaction(tx, format)?)).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    match output {
+        OutputFormat::Json => {
+            let dump = dump_transaction(tx)?;
+            Report::new()
+                .field("blockhash", dump.blockhash)
+                .field("feePayer", dump.fee_payer)
+                .field("signers", dump.signers)
+                .field("transaction", dump.transaction)
+                .field("outFile", out_file.map(|path| path.display().to_string()))
+                .print(output);
+        }
+        OutputFormat::Table if out_file.is_none() => println!("{}", encode_transaction(tx, format)?),
+        OutputFormat::Table => {}
+    }
+    let missing = missing_signers(tx);
+    if !missing.is_empty() {
+        eprintln!("Missing signatures from: {}", missing.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(", "));
+    }
+    Ok(())
+}
+
+/// Reads a transaction written by `write_transaction`; `-` reads stdin.
+pub fn read_transaction(source: &str) -> Result<Transaction> {
+    let input = if source == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).context("failed to read transaction from stdin")?;
+        input
+    } else {
+        fs::read_to_string(source).with_context(|| format!("failed to read {}", source))?
+    };
+    decode_transaction(&input)
+}
+
+pub fn encode_transaction(tx: &Transaction, format: TxFormat) -> Result<String> {
+    match format {
+        TxFormat::Base64 => Ok(BASE64.encode(bincode::serialize(tx)?)),
+        TxFormat::Json => Ok(serde_json::to_string_pretty(&dump_transaction(tx)?)?),
+    }
+}
+
+fn dump_transaction(tx: &Transaction) -> Result<TransactionDump> {
+    Ok(TransactionDump {
+        blockhash: tx.message.recent_blockhash.to_string(),
+        fee_payer: tx.message.account_keys.first().map(|key| key.to_string()).unwrap_or_default(),
+        signers: signer_keys(tx)
+            .iter()
+            .zip(&tx.signatures)
+            .map(|(key, signature)| SignerStatus {
+                pubkey: key.to_string(),
+                signature: (*signature != Signature::default()).then(|| signature.to_string()),
+            })
+            .collect(),
+        transaction: BASE64.encode(bincode::serialize(tx)?),
+    })
+}
+
+pub fn decode_transaction(input: &str) -> Result<Transaction> {
+    let input = input.trim();
+    let encoded = if input.starts_with('{') {
+        serde_json::from_str::<EncodedTransaction>(input).context("invalid transaction JSON")?.transaction
+    } else {
+        input.to_string()
+    };
+    let bytes = BASE64.decode(encoded).context("transaction is not valid base64")?;
+    bincode::deserialize(&bytes).context("failed to decode transaction")
+}
+
+fn signer_keys(tx: &Transaction) -> &[Pubkey] {
+    let count = tx.message.header.num_required_signatures as usize;
+    &tx.message.account_keys[..count.min(tx.message.account_keys.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::Message;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use super::*;
+
+    /// A transfer from `from` paid by `fee_payer`, signed by `fee_payer` only.
+    fn partially_signed(fee_payer: &Keypair, from: &Pubkey) -> Transaction {
+        let ix = solana_system_interface::instruction::transfer(from, &Pubkey::new_unique(), 1);
+        let mut tx = Transaction::new_unsigned(Message::new(&[ix], Some(&fee_payer.pubkey())));
+        tx.try_partial_sign(&[fee_payer], Hash::new_unique()).unwrap();
+        tx
+    }
+
+    #[test]
+    fn lists_the_signers_still_missing() {
+        let fee_payer = Keypair::new();
+        let from = Pubkey::new_unique();
+        let tx = partially_signed(&fee_payer, &from);
+        assert_eq!(missing_signers(&tx), vec![from]);
+
+        let dump = dump_transaction(&tx).unwrap();
+        assert_eq!(dump.fee_payer, fee_payer.pubkey().to_string());
+        assert_eq!(dump.signers.len(), 2);
+        assert!(dump.signers[0].signature.is_some());
+        assert_eq!(dump.signers[1].pubkey, from.to_string());
+        assert!(dump.signers[1].signature.is_none());
+    }
+
+    #[test]
+    fn both_formats_decode_back_to_the_transaction() {
+        let tx = partially_signed(&Keypair::new(), &Pubkey::new_unique());
+        for format in [TxFormat::Base64, TxFormat::Json] {
+            let encoded = encode_transaction(&tx, format).unwrap();
+            assert_eq!(decode_transaction(&format!("{}\n", encoded)).unwrap(), tx);
+        }
+    }
+
+    #[test]
+    fn json_reports_can_be_submitted() {
+        let tx = partially_signed(&Keypair::new(), &Pubkey::new_unique());
+        let dump = dump_transaction(&tx).unwrap();
+        let report = Report::new().field("blockhash", dump.blockhash).field("transaction", dump.transaction).field("outFile", None::<String>);
+        assert_eq!(decode_transaction(&serde_json::to_string_pretty(&report).unwrap()).unwrap(), tx);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(decode_transaction("not base64!").is_err());
+        assert!(decode_transaction("{\"blockhash\": \"x\"}").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
+use serde_json::Value;
+
+use crate::cli::OutputFormat;
+use crate::context::SentTx;
+
+/// Result of a subcommand: named fields printed as a two-column table or
+/// as a JSON object, in the order they were added.
+#[derive(Default)]
+pub struct Report {
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, key: &'static str, value: impl Serialize) -> Self {
+        self.fields.push((key, serde_json::to_value(value).unwrap_or(Value::Null)));
+        self
+    }
+
+    /// Adds the signature, slot and fee of a sent transaction.
+    pub fn tx(self, sent: &SentTx) -> Self {
+        self.field("signature", sent.signature.to_string()).field("slot", sent.slot).field("feeLamports", sent.fee)
+    }
+
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self).unwrap_or_default()),
+            OutputFormat::Table => {
+                let width = self.fields.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+                for (key, value) in &self.fields {
+                    println!("{:<width$}  {}", key, display_value(value), width = width);
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Report {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (key, value) in &self.fields {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Error returned after a failure has already been printed as JSON, so
+/// `main` only sets the exit code.
+#[derive(Debug)]
+pub struct AlreadyReported;
+
+impl std::fmt::Display for AlreadyReported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("failure already reported")
+    }
+}
+
+impl std::error::Error for AlreadyReported {}
+
+/// Prints a failed command as a JSON object so scripts can parse it.
+pub fn print_error(error: &anyhow::Error) {
+    let report = Report::new().field("status", "failed").field("error", format!("{:#}", error));
+    report.print(OutputFormat::Json);
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_keep_fields_in_insertion_order() {
+        let report = Report::new().field("status", "ok").field("amount", 5).field("fee", None::<u64>);
+        assert_eq!(serde_json::to_string(&report).unwrap(), r#"{"status":"ok","amount":5,"fee":null}"#);
+    }
+
+    #[test]
+    fn table_values_drop_quotes_and_show_missing_values_as_dash() {
+        assert_eq!(display_value(&Value::String("ok".to_string())), "ok");
+        assert_eq!(display_value(&Value::Null), "-");
+        assert_eq!(display_value(&serde_json::json!(42)), "42");
+        assert_eq!(display_value(&serde_json::json!(["a"])), r#"["a"]"#);
+    }
+}
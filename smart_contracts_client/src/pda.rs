@@ -0,0 +1 @@
+pub use smart_contracts_types::seeds::{USER_SEED, find_user_address};
@@ -0,0 +1,139 @@
+use anyhow::{Result, bail};
+use serde::Serialize;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use smart_contracts_client::error::describe_transaction_error;
+
+use crate::cli::OutputFormat;
+use crate::output::{AlreadyReported, Report};
+
+#[derive(Serialize)]
+struct BalanceChange {
+    address: String,
+    pre: u64,
+    post: u64,
+}
+
+/// Simulates `tx` and prints compute units, balance changes of the
+/// non-program accounts it touches and the program logs.
+pub fn simulate_tx(rpc: &RpcClient, program_id: Option<&Pubkey>, tx: &Transaction, output: OutputFormat) -> Result<()> {
+    let addresses = balance_addresses(&tx.message);
+    let pre_accounts = rpc.get_multiple_accounts(&addresses)?;
+    let result = rpc
+        .simulate_transaction_with_config(
+            tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: true,
+                commitment: Some(rpc.commitment()),
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: addresses.iter().map(|key| key.to_string()).collect(),
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )?
+        .value;
+
+    let post_accounts = result.accounts.unwrap_or_default();
+    let balances: Vec<BalanceChange> = addresses
+        .iter()
+        .enumerate()
+        .map(|(i, address)| BalanceChange {
+            address: address.to_string(),
+            pre: pre_accounts.get(i).and_then(|account| account.as_ref()).map_or(0, |account| account.lamports),
+            post: post_accounts.get(i).and_then(|account| account.as_ref()).map_or(0, |account| account.lamports),
+        })
+        .collect();
+    let logs = result.logs.unwrap_or_default();
+    let error = result.err.as_ref().map(|err| describe_error(err, tx, program_id));
+
+    match output {
+        OutputFormat::Json => Report::new()
+            .field("status", if error.is_none() { "simulated" } else { "failed" })
+            .field("error", &error)
+            .field("unitsConsumed", result.units_consumed)
+            .field("balances", &balances)
+            .field("logs", &logs)
+            .print(output),
+        OutputFormat::Table => {
+            match &error {
+                None => println!("Simulation succeeded"),
+                Some(error) => println!("Simulation failed: {}", error),
+            }
+            if let Some(units) = result.units_consumed {
+                println!("Compute units consumed: {}", units);
+            }
+
+            println!("Balances (lamports):");
+            for balance in &balances {
+                println!("  {}  {} -> {} ({:+})", balance.address, balance.pre, balance.post, balance.post as i128 - balance.pre as i128);
+            }
+
+            println!("Program logs:");
+            for line in &logs {
+                println!("  {}", line);
+            }
+        }
+    }
+
+    if let Some(error) = error {
+        if let OutputFormat::Json = output {
+            return Err(AlreadyReported.into());
+        }
+        bail!("simulation failed: {}", error);
+    }
+    Ok(())
+}
+
+/// Accounts of `message` whose balances are reported, every account but the
+/// invoked programs.
+fn balance_addresses(message: &Message) -> Vec<Pubkey> {
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !message.is_key_called_as_program(*i))
+        .map(|(_, key)| *key)
+        .collect()
+}
+
+fn describe_error(err: &TransactionError, tx: &Transaction, program_id: Option<&Pubkey>) -> String {
+    match program_id {
+        Some(program_id) => describe_transaction_error(err, &tx.message, program_id),
+        None => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::InstructionError;
+    use smart_contracts_client::instruction::{transfer_sol_ix, validate_txn_ix};
+
+    use super::*;
+
+    #[test]
+    fn reports_balances_of_every_account_but_the_programs() {
+        let (program_id, from, to) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = Message::new(&[transfer_sol_ix(&program_id, &from, &to, 1)], Some(&from));
+
+        let addresses = balance_addresses(&message);
+        assert!(addresses.contains(&from) && addresses.contains(&to));
+        assert!(!addresses.contains(&program_id));
+    }
+
+    #[test]
+    fn names_registry_errors_of_registry_instructions() {
+        let (program_id, payer, account) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let tx = Transaction::new_unsigned(Message::new(&[validate_txn_ix(&program_id, &account, 10, 5)], Some(&payer)));
+        let err = TransactionError::InstructionError(0, InstructionError::Custom(3));
+
+        assert!(describe_error(&err, &tx, Some(&program_id)).contains("Balance did not decrease by the expected amount"));
+        assert_eq!(describe_error(&err, &tx, None), err.to_string());
+        assert_eq!(describe_error(&err, &tx, Some(&Pubkey::new_unique())), err.to_string());
+    }
+}
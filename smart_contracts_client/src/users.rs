@@ -0,0 +1,103 @@
+use anyhow::{Result, bail};
+use chrono::DateTime;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use smart_contracts_client::account::{fetch_all_user_accounts, fetch_user_account_at};
+use smart_contracts_client::pda::find_user_address;
+use smart_contracts_types::state::UserAccount;
+
+use crate::cli::OutputFormat;
+use crate::context::Context;
+use crate::output::Report;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserRow {
+    address: String,
+    owner: String,
+    is_initialized: bool,
+    created_at: u64,
+    created_at_utc: String,
+}
+
+impl UserRow {
+    fn new(address: &Pubkey, user: &UserAccount) -> Self {
+        Self {
+            address: address.to_string(),
+            owner: user.owner.to_string(),
+            is_initialized: user.is_initialized,
+            created_at: user.created_at,
+            created_at_utc: format_timestamp(user.created_at),
+        }
+    }
+}
+
+pub fn show(ctx: &Context, owner: &Pubkey) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let (user_pda, _) = find_user_address(&program_id, owner);
+    let Some(user) = fetch_user_account_at(&ctx.rpc, &program_id, &user_pda)? else {
+        bail!("{} is not registered (no account at PDA {})", owner, user_pda);
+    };
+    let row = UserRow::new(&user_pda, &user);
+    Report::new()
+        .field("address", row.address)
+        .field("owner", row.owner)
+        .field("isInitialized", row.is_initialized)
+        .field("createdAt", row.created_at)
+        .field("createdAtUtc", row.created_at_utc)
+        .print(ctx.output);
+    Ok(())
+}
+
+pub fn list(ctx: &Context) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    let mut users = fetch_all_user_accounts(&ctx.rpc, &program_id)?;
+    users.sort_by_key(|(_, user)| user.created_at);
+    let rows: Vec<UserRow> = users.iter().map(|(address, user)| UserRow::new(address, user)).collect();
+
+    match ctx.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        OutputFormat::Table => {
+            println!("{:<44}  {:<44}  CREATED AT", "PDA", "OWNER");
+            for row in &rows {
+                println!("{:<44}  {:<44}  {}", row.address, row.owner, row.created_at_utc);
+            }
+            println!("{} user(s)", rows.len());
+        }
+    }
+    Ok(())
+}
+
+/// Formats a unix timestamp from the program clock as UTC.
+fn format_timestamp(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map_or_else(|| format!("invalid timestamp {}", timestamp), |time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_program_timestamps_as_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_timestamp(u64::MAX), format!("invalid timestamp {}", u64::MAX));
+    }
+
+    #[test]
+    fn user_rows_serialize_in_camel_case() {
+        let owner = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let row = UserRow::new(&address, &UserAccount { is_initialized: true, owner, created_at: 0 });
+        let json = serde_json::to_value(&row).unwrap();
+        assert_eq!(json["address"], address.to_string());
+        assert_eq!(json["owner"], owner.to_string());
+        assert_eq!(json["isInitialized"], true);
+        assert_eq!(json["createdAt"], 0);
+        assert_eq!(json["createdAtUtc"], "1970-01-01 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,194 @@
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status_client_types::UiTransactionEncoding;
+
+use smart_contracts_client::events::{RegistryEvent, parse_registry_logs};
+
+use crate::cli::OutputFormat;
+use crate::context::Context;
+
+/// Most signatures `getSignaturesForAddress` returns per request.
+const SIGNATURES_PAGE_LIMIT: usize = 1000;
+
+pub struct WatchOptions {
+    pub poll: bool,
+    pub poll_interval: u64,
+    pub ws_url: Option<String>,
+}
+
+/// One transaction that mentioned the registry program.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Activity {
+    signature: String,
+    slot: u64,
+    status: &'static str,
+    error: Option<String>,
+    events: Vec<RegistryEvent>,
+}
+
+impl Activity {
+    fn new(program_id: &Pubkey, signature: String, slot: u64, err: Option<&TransactionError>, logs: &[String]) -> Self {
+        Self {
+            signature,
+            slot,
+            status: if err.is_none() { "ok" } else { "failed" },
+            error: err.map(|err| err.to_string()),
+            events: parse_registry_logs(program_id, logs),
+        }
+    }
+
+    fn print(&self, output: OutputFormat) {
+        match output {
+            OutputFormat::Json => println!("{}", serde_json::to_string(self).unwrap_or_default()),
+            OutputFormat::Table => {
+                match &self.error {
+                    None => println!("{}  {}  {}", self.slot, self.signature, self.status),
+                    Some(error) => println!("{}  {}  {}: {}", self.slot, self.signature, self.status, error),
+                }
+                for event in &self.events {
+                    println!("    {}", event);
+                }
+            }
+        }
+    }
+}
+
+/// Streams registry activity until interrupted, over a `logsSubscribe`
+/// WebSocket when possible and by polling `getSignaturesForAddress` otherwise.
+pub fn run(ctx: &Context, options: WatchOptions) -> Result<()> {
+    let program_id = ctx.program_id()?;
+    if !options.poll {
+        let ws_url = options.ws_url.unwrap_or_else(|| websocket_url(&ctx.rpc.url()));
+        match subscribe(ctx, &program_id, &ws_url) {
+            Ok(()) => eprintln!("Log subscription closed, polling getSignaturesForAddress instead"),
+            Err(err) => eprintln!("Log subscription to {} failed ({:#}), polling getSignaturesForAddress instead", ws_url, err),
+        }
+    }
+    poll(ctx, &program_id, Duration::from_secs(options.poll_interval))
+}
+
+fn subscribe(ctx: &Context, program_id: &Pubkey, ws_url: &str) -> Result<()> {
+    let (_subscription, receiver) = PubsubClient::logs_subscribe(
+        ws_url,
+        RpcTransactionLogsFilter::Mentions(vec![program_id.to_string()]),
+        RpcTransactionLogsConfig { commitment: Some(ctx.rpc.commitment()) },
+    )?;
+    eprintln!("Watching {} over {}", program_id, ws_url);
+    while let Ok(response) = receiver.recv() {
+        let logs = response.value;
+        Activity::new(program_id, logs.signature, response.context.slot, logs.err.as_ref(), &logs.logs).print(ctx.output);
+    }
+    Ok(())
+}
+
+fn poll(ctx: &Context, program_id: &Pubkey, interval: Duration) -> Result<()> {
+    // Start from the newest signature so only new activity is printed.
+    let mut until = signatures_page(ctx, program_id, None, None, 1)?.first().map(|entry| Signature::from_str(&entry.signature)).transpose()?;
+    eprintln!("Polling {} every {}s", program_id, interval.as_secs());
+    loop {
+        thread::sleep(interval);
+        let entries = match signatures_since(ctx, program_id, until) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("getSignaturesForAddress failed: {:#}", err);
+                continue;
+            }
+        };
+        if let Some(newest) = entries.first() {
+            until = Some(Signature::from_str(&newest.signature)?);
+        }
+        for entry in entries.into_iter().rev() {
+            let logs = transaction_logs(ctx, &Signature::from_str(&entry.signature)?);
+            Activity::new(program_id, entry.signature, entry.slot, entry.err.as_ref(), &logs).print(ctx.output);
+        }
+    }
+}
+
+/// Signatures mentioning `program_id` newer than `until`, newest first.
+/// Pages back with `before` until `until` is reached, so a burst larger than
+/// one page between two polls is not dropped.
+fn signatures_since(ctx: &Context, program_id: &Pubkey, until: Option<Signature>) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let mut entries = Vec::new();
+    let mut before = None;
+    loop {
+        let page = signatures_page(ctx, program_id, before, until, SIGNATURES_PAGE_LIMIT)?;
+        let more = page.len() == SIGNATURES_PAGE_LIMIT;
+        before = page.last().map(|entry| Signature::from_str(&entry.signature)).transpose()?;
+        entries.extend(page);
+        if !more {
+            return Ok(entries);
+        }
+    }
+}
+
+/// One page of signatures mentioning `program_id` between `until` and
+/// `before`, newest first.
+fn signatures_page(
+    ctx: &Context,
+    program_id: &Pubkey,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    limit: usize,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before,
+        until,
+        limit: Some(limit),
+        commitment: Some(ctx.rpc.commitment()),
+    };
+    Ok(ctx.rpc.get_signatures_for_address_with_config(program_id, config)?)
+}
+
+/// Log messages of a transaction, empty if it cannot be fetched.
+fn transaction_logs(ctx: &Context, signature: &Signature) -> Vec<String> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(ctx.rpc.commitment()),
+        max_supported_transaction_version: Some(0),
+    };
+    ctx.rpc
+        .get_transaction_with_config(signature, config)
+        .ok()
+        .and_then(|tx| tx.transaction.meta)
+        .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+        .unwrap_or_default()
+}
+
+/// Derives the WebSocket endpoint from an RPC URL the way the Solana CLI
+/// does: `ws`/`wss` scheme, and the next port up when one is given. Port
+/// 65535 has no next port and is kept.
+fn websocket_url(rpc_url: &str) -> String {
+    let url = rpc_url.replacen("https://", "wss://", 1).replacen("http://", "ws://", 1);
+    let (head, tail) = url.split_at(url.find("://").map_or(0, |i| i + 3));
+    let (host, path) = tail.split_at(tail.find('/').unwrap_or(tail.len()));
+    match host.rsplit_once(':').and_then(|(name, port)| Some((name, port.parse::<u16>().ok()?))) {
+        Some((name, port)) => format!("{}{}:{}{}", head, name, port.checked_add(1).unwrap_or(port), path),
+        None => url.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_websocket_url() {
+        assert_eq!(websocket_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8900");
+        assert_eq!(websocket_url("https://api.devnet.solana.com"), "wss://api.devnet.solana.com");
+        assert_eq!(websocket_url("https://rpc.example.com:443/v1/key"), "wss://rpc.example.com:444/v1/key");
+        assert_eq!(websocket_url("http://localhost/path:1"), "ws://localhost/path:1");
+        assert_eq!(websocket_url("http://127.0.0.1:65535"), "ws://127.0.0.1:65535");
+    }
+}
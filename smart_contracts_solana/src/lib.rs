@@ -1,5 +1,4 @@
 pub mod entrypoint;
-pub mod error;
-pub mod instruction;
 pub mod processor;
-pub mod state;
+
+pub use smart_contracts_types::{error, instruction, state};
@@ -1,10 +1,12 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::{account_info::{next_account_info, AccountInfo}, entrypoint::ProgramResult, msg, program::invoke, program_error::ProgramError, pubkey::Pubkey, sysvar::clock::Clock, sysvar::Sysvar};
+use solana_program::{account_info::{next_account_info, AccountInfo}, entrypoint::ProgramResult, msg, program::{invoke, invoke_signed}, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, rent::Rent, sysvar::clock::Clock, sysvar::Sysvar};
 use solana_system_interface::instruction as system_instruction;
 use spl_token::instruction as token_instruction;
-use crate::{
+use smart_contracts_types::{
     error::RegistryError,
+    event::RegistryEvent,
     instruction::RegistryInstruction,
+    seeds::user_seeds,
     state::UserAccount,
 };
 
@@ -27,8 +29,8 @@ impl Processor {
             RegistryInstruction::TransferSpl { amount } => {
                 Self::transfer_spl(accounts, amount)
             }
-            RegistryInstruction::ValidateTxn { pre_balance } => {
-                Self::validate_txn(accounts, pre_balance)
+            RegistryInstruction::ValidateTxn { pre_balance, expected_decrease } => {
+                Self::validate_txn(accounts, pre_balance, expected_decrease)
             }
         }
     }
@@ -41,12 +43,62 @@ impl Processor {
         let account_info_iter = &mut accounts.iter();
         let payer = next_account_info(account_info_iter)?;
         let user_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
 
         // Derive PDA
-        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"user", payer.key.as_ref()], program_id);
+        let (pda, bump_seed) = Pubkey::find_program_address(&user_seeds(payer.key), program_id);
         if pda != *user_account.key {
             return Err(ProgramError::InvalidAccountData);
         }
+        if bump != bump_seed {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Create the PDA on first registration
+        if user_account.data_is_empty() {
+            let rent = Rent::get()?;
+            let required = rent.minimum_balance(UserAccount::LEN);
+            let [prefix, owner] = user_seeds(payer.key);
+            let signer_seeds: &[&[u8]] = &[prefix, owner, &[bump]];
+            if user_account.lamports() == 0 {
+                let ix = system_instruction::create_account(
+                    payer.key,
+                    user_account.key,
+                    required,
+                    UserAccount::LEN as u64,
+                    program_id,
+                );
+                invoke_signed(
+                    &ix,
+                    &[payer.clone(), user_account.clone(), system_program.clone()],
+                    &[signer_seeds],
+                )?;
+            } else {
+                // Anyone can send lamports to the address beforehand, which
+                // makes `create_account` fail: top the rent up instead, then
+                // allocate and assign the account.
+                let top_up = required.saturating_sub(user_account.lamports());
+                if top_up > 0 {
+                    invoke(
+                        &system_instruction::transfer(payer.key, user_account.key, top_up),
+                        &[payer.clone(), user_account.clone(), system_program.clone()],
+                    )?;
+                }
+                invoke_signed(
+                    &system_instruction::allocate(user_account.key, UserAccount::LEN as u64),
+                    &[user_account.clone(), system_program.clone()],
+                    &[signer_seeds],
+                )?;
+                invoke_signed(
+                    &system_instruction::assign(user_account.key, program_id),
+                    &[user_account.clone(), system_program.clone()],
+                    &[signer_seeds],
+                )?;
+            }
+        }
+        if user_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
         // Initialize account
         let mut data = user_account.try_borrow_mut_data()?;
@@ -58,9 +110,9 @@ impl Processor {
         state.owner = *payer.key;
         let clock = Clock::get()?;
         state.created_at = clock.unix_timestamp as u64;
-        state.serialize(&mut *data)?;
+        state.serialize(&mut &mut data[..])?;
 
-        msg!("User registered: {}", payer.key);
+        msg!("{}", RegistryEvent::UserRegistered { owner: *payer.key });
         Ok(())
     }
 
@@ -74,7 +126,7 @@ impl Processor {
         // invoke system transfer
         let ix = system_instruction::transfer(from.key, to.key, amount);
         invoke(&ix, &[from.clone(), to.clone()])?;
-        msg!("Transferred {} lamports", amount);
+        msg!("{}", RegistryEvent::SolTransferred { lamports: amount });
         Ok(())
     }
 
@@ -87,7 +139,7 @@ impl Processor {
         let token_program = next_account_info(account_info_iter)?;
         let from_ata = next_account_info(account_info_iter)?;
         let to_ata = next_account_info(account_info_iter)?;
-        let mint = next_account_info(account_info_iter)?;
+        let _mint = next_account_info(account_info_iter)?;
 
         let ix = token_instruction::transfer(
             token_program.key,
@@ -103,21 +155,27 @@ impl Processor {
             payer.clone(),
             token_program.clone(),
         ])?;
-        msg!("Transferred {} tokens", amount);
+        msg!("{}", RegistryEvent::TokensTransferred { amount });
         Ok(())
     }
 
     fn validate_txn(
         accounts: &[AccountInfo],
         pre_balance: u64,
+        expected_decrease: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let acct = next_account_info(account_info_iter)?;
-        let lamports = acct.lamports();
-        if lamports < pre_balance {
-            msg!("Balance decreased: {} -> {}", pre_balance, lamports);
+        // Fees are paid in lamports, so token transfers are checked against
+        // the token amount, which the fee does not touch
+        let balance = if *acct.owner == spl_token::id() {
+            spl_token::state::Account::unpack(&acct.try_borrow_data()?)?.amount
         } else {
-            msg!("Balance increased or same: {} -> {}", pre_balance, lamports);
+            acct.lamports()
+        };
+        msg!("{}", RegistryEvent::BalanceValidated { pre_balance, balance, decreased: balance < pre_balance });
+        if pre_balance.checked_sub(balance).is_none_or(|decrease| decrease < expected_decrease) {
+            return Err(RegistryError::BalanceNotDecreased.into());
         }
         Ok(())
     }
@@ -0,0 +1,246 @@
+use borsh::BorshDeserialize;
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext, processor};
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_system_interface::instruction as system_instruction;
+use solana_system_interface::program as system_program;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account;
+
+use smart_contracts_solana::error::RegistryError;
+use smart_contracts_solana::instruction::RegistryInstruction;
+use smart_contracts_solana::processor::Processor;
+use smart_contracts_solana::state::UserAccount;
+use smart_contracts_types::seeds::find_user_address;
+
+async fn start() -> (ProgramTestContext, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("smart_contracts_solana", program_id, processor!(Processor::process));
+    (program_test.start_with_context().await, program_id)
+}
+
+/// Signs `instructions` with the context payer plus `signers` and processes them.
+async fn send(context: &mut ProgramTestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+    let payer = context.payer.insecure_clone();
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut keypairs = vec![&payer];
+    keypairs.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &keypairs, blockhash);
+    context.banks_client.process_transaction(tx).await
+}
+
+fn register_user_ix(program_id: &Pubkey, payer: &Pubkey, user_account: &Pubkey, bump: u8) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &RegistryInstruction::RegisterUser { bump },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*user_account, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+fn assert_instruction_error(err: BanksClientError, expected: InstructionError) {
+    assert_eq!(err.unwrap(), TransactionError::InstructionError(0, expected));
+}
+
+#[tokio::test]
+async fn register_user_creates_the_pda() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let (pda, bump) = find_user_address(&program_id, &payer);
+
+    send(&mut context, &[register_user_ix(&program_id, &payer, &pda, bump)], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(pda).await.unwrap().expect("user account created");
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), UserAccount::LEN);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(UserAccount::LEN));
+    let user = UserAccount::try_from_slice(&account.data).unwrap();
+    assert!(user.is_initialized);
+    assert_eq!(user.owner, payer);
+    assert!(user.created_at > 0);
+}
+
+#[tokio::test]
+async fn register_user_accepts_a_pre_funded_pda() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let (pda, bump) = find_user_address(&program_id, &payer);
+    let rent = Rent::default().minimum_balance(UserAccount::LEN);
+    // Enough for an empty system account, short of the rent of a `UserAccount`
+    send(&mut context, &[system_instruction::transfer(&payer, &pda, Rent::default().minimum_balance(0))], &[]).await.unwrap();
+
+    send(&mut context, &[register_user_ix(&program_id, &payer, &pda, bump)], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(pda).await.unwrap().expect("user account created");
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), UserAccount::LEN);
+    assert_eq!(account.lamports, rent);
+    let user = UserAccount::try_from_slice(&account.data).unwrap();
+    assert!(user.is_initialized);
+    assert_eq!(user.owner, payer);
+}
+
+#[tokio::test]
+async fn register_user_twice_fails_with_already_registered() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let (pda, bump) = find_user_address(&program_id, &payer);
+    let ix = register_user_ix(&program_id, &payer, &pda, bump);
+
+    send(&mut context, std::slice::from_ref(&ix), &[]).await.unwrap();
+    let err = send(&mut context, &[ix], &[]).await.unwrap_err();
+
+    assert_instruction_error(err, InstructionError::Custom(RegistryError::AlreadyRegistered as u32));
+}
+
+#[tokio::test]
+async fn register_user_rejects_a_wrong_pda() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let other = Pubkey::new_unique();
+    let (other_pda, bump) = find_user_address(&program_id, &other);
+
+    let err = send(&mut context, &[register_user_ix(&program_id, &payer, &other_pda, bump)], &[]).await.unwrap_err();
+
+    assert_instruction_error(err, InstructionError::InvalidAccountData);
+}
+
+#[tokio::test]
+async fn register_user_rejects_a_non_canonical_bump() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let (pda, bump) = find_user_address(&program_id, &payer);
+
+    let err = send(&mut context, &[register_user_ix(&program_id, &payer, &pda, bump.wrapping_sub(1))], &[]).await.unwrap_err();
+
+    assert_instruction_error(err, InstructionError::InvalidSeeds);
+}
+
+#[tokio::test]
+async fn transfer_sol_moves_lamports() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let recipient = Pubkey::new_unique();
+    let amount = 5_000_000;
+    let ix = Instruction::new_with_borsh(
+        program_id,
+        &RegistryInstruction::TransferSol { amount },
+        vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new(recipient, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+
+    send(&mut context, &[ix], &[]).await.unwrap();
+
+    assert_eq!(context.banks_client.get_balance(recipient).await.unwrap(), amount);
+}
+
+#[tokio::test]
+async fn transfer_spl_moves_tokens_between_associated_token_accounts() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let mint = Keypair::new();
+    let recipient = Pubkey::new_unique();
+    let from_ata = get_associated_token_address(&payer, &mint.pubkey());
+    let to_ata = get_associated_token_address(&recipient, &mint.pubkey());
+
+    let setup = [
+        system_instruction::create_account(&payer, &mint.pubkey(), Rent::default().minimum_balance(spl_token::state::Mint::LEN), spl_token::state::Mint::LEN as u64, &spl_token::id()),
+        spl_token::instruction::initialize_mint2(&spl_token::id(), &mint.pubkey(), &payer, None, 6).unwrap(),
+        create_associated_token_account(&payer, &payer, &mint.pubkey(), &spl_token::id()),
+        create_associated_token_account(&payer, &recipient, &mint.pubkey(), &spl_token::id()),
+        spl_token::instruction::mint_to(&spl_token::id(), &mint.pubkey(), &from_ata, &payer, &[], 1_000_000).unwrap(),
+    ];
+    send(&mut context, &setup, &[&mint]).await.unwrap();
+
+    let ix = Instruction::new_with_borsh(
+        program_id,
+        &RegistryInstruction::TransferSpl { amount: 250_000 },
+        vec![
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(from_ata, false),
+            AccountMeta::new(to_ata, false),
+            AccountMeta::new_readonly(mint.pubkey(), false),
+        ],
+    );
+    send(&mut context, &[ix], &[]).await.unwrap();
+
+    let token_balance = |data: Vec<u8>| spl_token::state::Account::unpack(&data).unwrap().amount;
+    let from = context.banks_client.get_account(from_ata).await.unwrap().unwrap();
+    let to = context.banks_client.get_account(to_ata).await.unwrap().unwrap();
+    assert_eq!(token_balance(from.data), 750_000);
+    assert_eq!(token_balance(to.data), 250_000);
+}
+
+fn validate_txn_ix(program_id: &Pubkey, account: &Pubkey, pre_balance: u64, expected_decrease: u64) -> Instruction {
+    Instruction::new_with_borsh(*program_id, &RegistryInstruction::ValidateTxn { pre_balance, expected_decrease }, vec![AccountMeta::new_readonly(*account, false)])
+}
+
+#[tokio::test]
+async fn validate_txn_accepts_the_expected_lamport_decrease() {
+    let (mut context, program_id) = start().await;
+    let watched = Keypair::new();
+    let payer = context.payer.pubkey();
+    send(&mut context, &[system_instruction::transfer(&payer, &watched.pubkey(), 1_000_000)], &[]).await.unwrap();
+
+    for (pre_balance, expected_decrease) in [(1_500_000, 500_000), (2_000_000, 500_000), (1_000_000, 0)] {
+        send(&mut context, &[validate_txn_ix(&program_id, &watched.pubkey(), pre_balance, expected_decrease)], &[]).await.unwrap();
+    }
+
+    assert_eq!(context.banks_client.get_balance(watched.pubkey()).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn validate_txn_rejects_a_smaller_or_negative_decrease() {
+    let (mut context, program_id) = start().await;
+    let watched = Keypair::new();
+    let payer = context.payer.pubkey();
+    send(&mut context, &[system_instruction::transfer(&payer, &watched.pubkey(), 1_000_000)], &[]).await.unwrap();
+
+    for (pre_balance, expected_decrease) in [(1_400_000, 500_000), (0, 0), (u64::MAX, u64::MAX)] {
+        let err = send(&mut context, &[validate_txn_ix(&program_id, &watched.pubkey(), pre_balance, expected_decrease)], &[]).await.unwrap_err();
+        assert_instruction_error(err, InstructionError::Custom(RegistryError::BalanceNotDecreased as u32));
+    }
+}
+
+/// Paying the fee lowers the lamports of the sender, so token transfers are
+/// checked against the token amount of the account instead.
+#[tokio::test]
+async fn validate_txn_checks_the_token_amount_of_token_accounts() {
+    let (mut context, program_id) = start().await;
+    let payer = context.payer.pubkey();
+    let mint = Keypair::new();
+    let ata = get_associated_token_address(&payer, &mint.pubkey());
+    let setup = [
+        system_instruction::create_account(&payer, &mint.pubkey(), Rent::default().minimum_balance(spl_token::state::Mint::LEN), spl_token::state::Mint::LEN as u64, &spl_token::id()),
+        spl_token::instruction::initialize_mint2(&spl_token::id(), &mint.pubkey(), &payer, None, 6).unwrap(),
+        create_associated_token_account(&payer, &payer, &mint.pubkey(), &spl_token::id()),
+        spl_token::instruction::mint_to(&spl_token::id(), &mint.pubkey(), &ata, &payer, &[], 1_000_000).unwrap(),
+    ];
+    send(&mut context, &setup, &[&mint]).await.unwrap();
+
+    send(&mut context, &[validate_txn_ix(&program_id, &ata, 1_250_000, 250_000)], &[]).await.unwrap();
+    let err = send(&mut context, &[validate_txn_ix(&program_id, &ata, 1_000_000, 1)], &[]).await.unwrap_err();
+
+    assert_instruction_error(err, InstructionError::Custom(RegistryError::BalanceNotDecreased as u32));
+}
+
+#[tokio::test]
+async fn invalid_instruction_data_is_rejected() {
+    let (mut context, program_id) = start().await;
+    let ix = Instruction::new_with_bytes(program_id, &[42], vec![]);
+
+    let err = send(&mut context, &[ix], &[]).await.unwrap_err();
+
+    assert_instruction_error(err, InstructionError::InvalidInstructionData);
+}
@@ -0,0 +1,111 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// Coins the service handles, with their database ids, mints and decimals.
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coin {
+    Litecoin = 1,
+    USDT = 2,
+    USDC = 3,
+    Solana = 4,
+}
+
+impl Coin {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Coin::Litecoin => "litecoin",
+            Coin::USDT => "usdt",
+            Coin::USDC => "usdc",
+            Coin::Solana => "solana",
+        }
+    }
+
+    pub fn from_i16(i: i16) -> Option<Self> {
+        match i {
+            1 => Some(Coin::Litecoin),
+            2 => Some(Coin::USDT),
+            3 => Some(Coin::USDC),
+            4 => Some(Coin::Solana),
+            _ => None,
+        }
+    }
+
+    pub fn to_i16(&self) -> i16 {
+        *self as i16
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        [Coin::Litecoin, Coin::USDT, Coin::USDC, Coin::Solana].into_iter().find(|coin| coin.name().eq_ignore_ascii_case(s))
+    }
+
+    /// Mint address of SPL tokens, or a short tag for native coins.
+    pub fn mint(&self) -> &'static str {
+        match self {
+            Coin::Litecoin => "ltc",
+            Coin::USDT => "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+            Coin::USDC => "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            Coin::Solana => "sol",
+        }
+    }
+
+    pub fn from_mint(mint: &str) -> Option<Self> {
+        match mint {
+            "ltc" => Some(Coin::Litecoin),
+            "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB" => Some(Coin::USDT),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v" => Some(Coin::USDC),
+            "sol" => Some(Coin::Solana),
+            _ => None,
+        }
+    }
+
+    /// Whether the coin is an SPL token on Solana.
+    pub fn is_spl(&self) -> bool {
+        matches!(self, Coin::USDT | Coin::USDC)
+    }
+
+    /// Number of decimal places for each token
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Coin::Litecoin => 8,
+            Coin::USDT => 6,
+            Coin::USDC => 6,
+            Coin::Solana => 9,
+        }
+    }
+}
+
+/// Renders the variant name (`Litecoin`, `USDT`, ...), as the service always
+/// has; [`Coin::name`] is the lowercase form matched against coin names.
+impl fmt::Display for Coin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn displays_the_variant_name() {
+        let rendered = [Coin::Litecoin, Coin::USDT, Coin::USDC, Coin::Solana].map(|coin| coin.to_string());
+        assert_eq!(rendered, ["Litecoin", "USDT", "USDC", "Solana"]);
+    }
+
+    #[test]
+    fn round_trips_names_ids_and_mints() {
+        for coin in [Coin::Litecoin, Coin::USDT, Coin::USDC, Coin::Solana] {
+            assert_eq!(Coin::from_name(coin.name()), Some(coin));
+            assert_eq!(Coin::from_name(&coin.to_string()), Some(coin));
+            assert_eq!(Coin::from_i16(coin.to_i16()), Some(coin));
+            assert_eq!(Coin::from_mint(coin.mint()), Some(coin));
+        }
+    }
+}
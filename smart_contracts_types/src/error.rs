@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Invalid Instruction")]
+    InvalidInstruction,
+    #[error("User already registered")]
+    AlreadyRegistered,
+    #[error("Arithmetic overflow")]
+    MathOverflow,
+    #[error("Balance did not decrease by the expected amount")]
+    BalanceNotDecreased,
+}
+
+impl RegistryError {
+    /// Maps a `ProgramError::Custom` code back to the error that produced it.
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(RegistryError::InvalidInstruction),
+            1 => Some(RegistryError::AlreadyRegistered),
+            2 => Some(RegistryError::MathOverflow),
+            3 => Some(RegistryError::BalanceNotDecreased),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<RegistryError> for solana_program_error::ProgramError {
+    fn from(e: RegistryError) -> Self {
+        solana_msg::msg!("{}", e);
+        solana_program_error::ProgramError::Custom(e as u32)
+    }
+}
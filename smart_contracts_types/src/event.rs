@@ -0,0 +1,105 @@
+use core::fmt;
+use core::str::FromStr;
+
+use solana_pubkey::Pubkey;
+
+/// Activity the registry program reports through `msg!`. `Display` renders
+/// the exact log line the program writes and `parse` reads it back, so the
+/// program and the log consumers cannot drift apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryEvent {
+    UserRegistered { owner: Pubkey },
+    SolTransferred { lamports: u64 },
+    TokensTransferred { amount: u64 },
+    BalanceValidated { pre_balance: u64, balance: u64, decreased: bool },
+}
+
+const USER_REGISTERED: &str = "User registered: ";
+const TRANSFERRED: &str = "Transferred ";
+const BALANCE_DECREASED: &str = "Balance decreased: ";
+const BALANCE_NOT_DECREASED: &str = "Balance increased or same: ";
+
+impl fmt::Display for RegistryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryEvent::UserRegistered { owner } => write!(f, "{}{}", USER_REGISTERED, owner),
+            RegistryEvent::SolTransferred { lamports } => write!(f, "{}{} lamports", TRANSFERRED, lamports),
+            RegistryEvent::TokensTransferred { amount } => write!(f, "{}{} tokens", TRANSFERRED, amount),
+            RegistryEvent::BalanceValidated { pre_balance, balance, decreased } => {
+                let prefix = if *decreased { BALANCE_DECREASED } else { BALANCE_NOT_DECREASED };
+                write!(f, "{}{} -> {}", prefix, pre_balance, balance)
+            }
+        }
+    }
+}
+
+impl RegistryEvent {
+    /// Parses one program log message (without the `Program log: ` prefix).
+    pub fn parse(message: &str) -> Option<Self> {
+        if let Some(owner) = message.strip_prefix(USER_REGISTERED) {
+            return Pubkey::from_str(owner.trim()).ok().map(|owner| RegistryEvent::UserRegistered { owner });
+        }
+        if let Some(rest) = message.strip_prefix(TRANSFERRED) {
+            let (amount, unit) = rest.split_once(' ')?;
+            let amount = amount.parse().ok()?;
+            return match unit {
+                "lamports" => Some(RegistryEvent::SolTransferred { lamports: amount }),
+                "tokens" => Some(RegistryEvent::TokensTransferred { amount }),
+                _ => None,
+            };
+        }
+        for (prefix, decreased) in [(BALANCE_DECREASED, true), (BALANCE_NOT_DECREASED, false)] {
+            if let Some(rest) = message.strip_prefix(prefix) {
+                let (pre_balance, balance) = rest.split_once(" -> ")?;
+                return Some(RegistryEvent::BalanceValidated {
+                    pre_balance: pre_balance.trim().parse().ok()?,
+                    balance: balance.trim().parse().ok()?,
+                    decreased,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    fn assert_round_trip(event: RegistryEvent) {
+        assert_eq!(RegistryEvent::parse(&event.to_string()), Some(event));
+    }
+
+    #[test]
+    fn user_registered_round_trips() {
+        assert_round_trip(RegistryEvent::UserRegistered { owner: Pubkey::new_unique() });
+    }
+
+    #[test]
+    fn sol_transferred_round_trips() {
+        assert_round_trip(RegistryEvent::SolTransferred { lamports: 0 });
+        assert_round_trip(RegistryEvent::SolTransferred { lamports: u64::MAX });
+    }
+
+    #[test]
+    fn tokens_transferred_round_trips() {
+        assert_round_trip(RegistryEvent::TokensTransferred { amount: 250_000 });
+    }
+
+    #[test]
+    fn balance_validated_round_trips() {
+        assert_round_trip(RegistryEvent::BalanceValidated { pre_balance: 10, balance: 5, decreased: true });
+        assert_round_trip(RegistryEvent::BalanceValidated { pre_balance: 5, balance: 5, decreased: false });
+    }
+
+    #[test]
+    fn rejects_other_messages() {
+        for message in ["", "User registered: not-a-key", "Transferred 5 coins", "Transferred x lamports", "Balance decreased: 1", "Balance decreased: a -> 1"] {
+            assert_eq!(RegistryEvent::parse(message), None, "parsed `{}`", message);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "std")]
+use solana_program_error::ProgramError;
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum RegistryInstruction {
     /// 0: Register user
     /// Accounts:
-    ///   [signer] payer
+    ///   [signer, writable] payer
     ///   [writable] user_account (PDA)
+    ///   [] system_program
     RegisterUser { bump: u8 },
 
     /// 1: Transfer SOL
@@ -23,16 +26,17 @@ pub enum RegistryInstruction {
     ///   [] mint
     TransferSpl { amount: u64 },
 
-    /// 3: Validate Transaction (compare pre/post balances)
+    /// 3: Validate Transaction (compare pre/post balances). Fails unless the
+    /// balance dropped by at least `expected_decrease`: the token amount of
+    /// an SPL token account, the lamports of any other account.
     /// Accounts:
-    ///   [] system_program
-    ///   [writable] account_to_check
-    ValidateTxn { pre_balance: u64 },
+    ///   [] account_to_check
+    ValidateTxn { pre_balance: u64, expected_decrease: u64 },
 }
 
+#[cfg(feature = "std")]
 impl RegistryInstruction {
-    pub fn unpack(input: &[u8]) -> Result<Self, solana_program::program_error::ProgramError> {
-        Self::try_from_slice(input)
-            .map_err(|_| solana_program::program_error::ProgramError::InvalidInstructionData)
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)
     }
 }
@@ -0,0 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+//! Types shared by the registry program, its client and the coins service, so
+//! that instruction encodings, account layouts, PDA seeds, log events and coin
+//! metadata are defined once.
+
+pub mod coin;
+pub mod error;
+pub mod event;
+pub mod instruction;
+pub mod seeds;
+pub mod state;
@@ -0,0 +1,14 @@
+use solana_pubkey::Pubkey;
+
+/// Seed prefix of the `UserAccount` PDA.
+pub const USER_SEED: &[u8] = b"user";
+
+/// Seeds of the `UserAccount` PDA of `owner`, without the bump.
+pub fn user_seeds(owner: &Pubkey) -> [&[u8]; 2] {
+    [USER_SEED, owner.as_ref()]
+}
+
+/// Derives the registry PDA holding the `UserAccount` of `owner`.
+pub fn find_user_address(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&user_seeds(owner), program_id)
+}
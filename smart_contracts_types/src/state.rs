@@ -0,0 +1,25 @@
+use borsh::io::{Read, Result as IoResult, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_pubkey::Pubkey;
+
+// 1. User account data
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UserAccount {
+    pub is_initialized: bool,
+    #[borsh(serialize_with = "serialize_pubkey", deserialize_with = "deserialize_pubkey")]
+    pub owner: Pubkey,
+    pub created_at: u64, // Unix timestamp
+}
+
+impl UserAccount {
+    pub const LEN: usize = 1 + 32 + 8;
+}
+
+// `Pubkey` only implements Borsh with std, so its 32 bytes are written directly.
+fn serialize_pubkey<W: Write>(pubkey: &Pubkey, writer: &mut W) -> IoResult<()> {
+    writer.write_all(pubkey.as_ref())
+}
+
+fn deserialize_pubkey<R: Read>(reader: &mut R) -> IoResult<Pubkey> {
+    <[u8; 32]>::deserialize_reader(reader).map(Pubkey::new_from_array)
+}